@@ -7,14 +7,19 @@ use fontdue::{
 use log::info;
 use smart_leds::RGB8;
 
+use std::path::PathBuf;
 use std::sync::{
     atomic::{AtomicBool, AtomicI8, Ordering},
     Mutex,
 };
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use tetris_lib::{
-    common::{GameController, LedDisplay, Timer, SCREEN_HEIGHT, SCREEN_WIDTH},
+    audio::NoAudio,
+    color::{preview, ColorPipeline, CorrectedDisplay},
+    common::{GameController, LedBuffer, LedDisplay, Timer, SCREEN_HEIGHT, SCREEN_WIDTH},
     games::run_game_menu,
+    layout::LedLayout,
+    storage::Storage,
 };
 
 // Global state for the game display and input
@@ -253,22 +258,77 @@ impl RenderContext {
 }
 
 // Timer implementation for Android
-pub struct AndroidTimer;
+struct AndroidTimer {
+    start: Instant,
+}
+
+impl AndroidTimer {
+    fn new() -> Self {
+        Self {
+            start: Instant::now(),
+        }
+    }
+}
 
 impl Timer for AndroidTimer {
     async fn sleep_millis(&self, millis: u64) {
         std::thread::sleep(Duration::from_millis(millis));
     }
+
+    fn now_millis(&self) -> u64 {
+        self.start.elapsed().as_millis() as u64
+    }
+}
+
+// Storage implementation for Android, one file per key in the app's internal
+// files directory
+pub struct AndroidStorage {
+    dir: Option<PathBuf>,
+}
+
+impl AndroidStorage {
+    pub fn new(app: &AndroidApp) -> Self {
+        let dir = app.internal_data_path();
+        if dir.is_none() {
+            log::error!("No internal data path, high scores won't be kept");
+        }
+        Self { dir }
+    }
+
+    fn path(&self, key: &str) -> Option<PathBuf> {
+        self.dir.as_ref().map(|dir| dir.join(format!("{key}.bin")))
+    }
+}
+
+impl Storage for AndroidStorage {
+    async fn load(&mut self, key: &str, buf: &mut [u8]) -> Option<usize> {
+        let data = std::fs::read(self.path(key)?).ok()?;
+        buf.get_mut(..data.len())?.copy_from_slice(&data);
+        Some(data.len())
+    }
+
+    async fn save(&mut self, key: &str, data: &[u8]) {
+        let (Some(dir), Some(path)) = (&self.dir, self.path(key)) else {
+            return;
+        };
+        if let Err(err) = std::fs::create_dir_all(dir).and_then(|_| std::fs::write(&path, data)) {
+            log::error!("Failed to save {}: {err}", path.display());
+        }
+    }
 }
 
 // Display implementation for Android
 pub struct AndroidDisplay {
     app: AndroidApp,
+    layout: LedLayout,
 }
 
 impl AndroidDisplay {
     pub fn new(app: AndroidApp) -> Self {
-        Self { app }
+        Self {
+            app,
+            layout: LedLayout::default(),
+        }
     }
 
     fn draw_touch_controls(
@@ -558,11 +618,8 @@ impl AndroidDisplay {
                     // Draw each LED pixel as a scaled block
                     for led_y in 0..SCREEN_HEIGHT {
                         for led_x in 0..SCREEN_WIDTH {
-                            let led_idx = if led_y % 2 == 0 {
-                                led_y * SCREEN_WIDTH + (SCREEN_WIDTH - 1 - led_x)
-                            } else {
-                                led_y * SCREEN_WIDTH + led_x
-                            };
+                            let led_idx =
+                                self.layout.index(led_x, led_y, SCREEN_WIDTH, SCREEN_HEIGHT);
 
                             let led = leds[led_idx];
 
@@ -571,15 +628,15 @@ impl AndroidDisplay {
                                 // Convert black pixels to dark gray (equal RGB values for true gray)
                                 (12u8, 12u8, 12u8) // Dark gray RGB values - all equal for neutral gray
                             } else {
-                                // Keep original color for non-black pixels
-                                (led.r, led.g, led.b)
+                                // Show the color corrected LED the way it looks on hardware
+                                let color = preview(led);
+                                (color.r, color.g, color.b)
                             };
 
                             // For R5G6B5 format: 5 bits red, 6 bits green, 5 bits blue
-                            // Scale LED color from 0-31 to appropriate bit ranges
-                            let r5 = (r as u16).min(31); // 5 bits: 0-31
-                            let g6 = (g as u16 * 63 / 31).min(63); // 6 bits: 0-63
-                            let b5 = (b as u16).min(31); // 5 bits: 0-31
+                            let r5 = r as u16 >> 3; // 5 bits: 0-31
+                            let g6 = g as u16 >> 2; // 6 bits: 0-63
+                            let b5 = b as u16 >> 3; // 5 bits: 0-31
 
                             // Pack into 16-bit R5G6B5 format: RRRRRGGGGGGBBBBB
                             let rgb565 = (r5 << 11) | (g6 << 5) | b5;
@@ -638,15 +695,21 @@ impl AndroidDisplay {
     }
 }
 
-impl LedDisplay for AndroidDisplay {
-    async fn write(&mut self, leds: &[RGB8; 256]) {
-        if let Ok(mut led_array) = LEDS.lock() {
-            led_array.copy_from_slice(leds);
+impl LedDisplay<SCREEN_WIDTH, SCREEN_HEIGHT> for AndroidDisplay {
+    async fn write(&mut self, leds: &LedBuffer<SCREEN_WIDTH, SCREEN_HEIGHT>) {
+        let mut led_array = [RGB8::new(0, 0, 0); 256];
+        led_array.copy_from_slice(leds.as_flattened());
+        if let Ok(mut shared) = LEDS.lock() {
+            *shared = led_array;
         }
         SHOULD_UPDATE_DISPLAY.store(true, Ordering::Relaxed);
 
         // Actually render to the screen
-        self.render_to_native_window(leds);
+        self.render_to_native_window(&led_array);
+    }
+
+    fn layout(&self) -> LedLayout {
+        self.layout
     }
 }
 
@@ -1209,6 +1272,13 @@ impl GameController for AndroidController {
 
         current && !prev
     }
+
+    fn ab_held(&self) -> bool {
+        self.ensure_input_processed();
+
+        INPUT_STATE.a_pressed.load(Ordering::Relaxed)
+            && INPUT_STATE.b_pressed.load(Ordering::Relaxed)
+    }
 }
 
 // Main entry point using android-activity
@@ -1221,9 +1291,11 @@ fn android_main(app: AndroidApp) {
     let game_app = app.clone();
     let _game_handle = std::thread::spawn(move || {
         // Create a simple async runtime using futures-executor
-        let mut display = AndroidDisplay::new(game_app.clone());
+        let mut display =
+            CorrectedDisplay::new(AndroidDisplay::new(game_app.clone()), ColorPipeline::new());
+        let mut storage = AndroidStorage::new(&game_app);
         let mut controller = AndroidController::new(game_app);
-        let timer = AndroidTimer;
+        let timer = AndroidTimer::new();
 
         let seed_fn = || {
             std::time::SystemTime::now()
@@ -1236,7 +1308,15 @@ fn android_main(app: AndroidApp) {
 
         // Use a simple blocking async runtime
         pollster::block_on(async {
-            run_game_menu(&mut display, &mut controller, &timer, seed_fn).await;
+            run_game_menu(
+                &mut display,
+                &mut NoAudio,
+                &mut controller,
+                &timer,
+                &mut storage,
+                seed_fn,
+            )
+            .await;
         });
     });
 
@@ -0,0 +1,125 @@
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+use tetris_lib::audio::{duration_millis, samples, Audio, Note};
+use tetris_lib::log::{error, info};
+
+// Sample rate of the rendered WAV files
+const SAMPLE_RATE: u32 = 22050;
+
+// Shorter melodies don't ring the bell, a terminal can't play them anyway and
+// a bell for every shot would be too much
+const BELL_MIN_MILLIS: u32 = 200;
+
+// Sound output of the console: the terminal bell, or every note rendered into
+// a WAV file
+pub enum ConsoleAudio {
+    Bell,
+    Wav(WavAudio),
+}
+
+impl ConsoleAudio {
+    // Writes the WAV file, if sounds are being rendered
+    pub fn finish(self) {
+        if let ConsoleAudio::Wav(wav) = self {
+            wav.finish();
+        }
+    }
+
+    fn ring(&self, millis: u32) {
+        if millis >= BELL_MIN_MILLIS {
+            let mut stdout = std::io::stdout();
+            let _ = stdout.write_all(b"\x07");
+            let _ = stdout.flush();
+        }
+    }
+}
+
+impl Audio for ConsoleAudio {
+    fn play(&mut self, melody: &'static [Note]) {
+        match self {
+            ConsoleAudio::Bell => self.ring(duration_millis(melody)),
+            ConsoleAudio::Wav(wav) => wav.render(melody),
+        }
+    }
+
+    fn tone(&mut self, note: Note) {
+        match self {
+            ConsoleAudio::Bell => self.ring(note.millis as u32),
+            ConsoleAudio::Wav(wav) => wav.render(&[note]),
+        }
+    }
+
+    fn stop(&mut self) {
+        if let ConsoleAudio::Wav(wav) = self {
+            wav.cut();
+        }
+    }
+}
+
+// Renders the sounds into a mono 16 bit WAV file, each one at the time it was
+// played, so the file can be listened to next to a recording or checked in
+// tests
+pub struct WavAudio {
+    path: PathBuf,
+    start: Instant,
+    samples: Vec<i16>,
+}
+
+impl WavAudio {
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            start: Instant::now(),
+            samples: Vec::new(),
+        }
+    }
+
+    // Index of the sample playing now
+    fn now(&self) -> usize {
+        (self.start.elapsed().as_millis() as u64 * SAMPLE_RATE as u64 / 1000) as usize
+    }
+
+    // Cuts off whatever is still playing
+    fn cut(&mut self) {
+        let now = self.now();
+        self.samples.resize(now, 0);
+    }
+
+    fn render(&mut self, melody: &[Note]) {
+        self.cut();
+        self.samples.extend(samples(melody, SAMPLE_RATE));
+    }
+
+    fn finish(self) {
+        match write_wav(&self.path, &self.samples) {
+            Ok(()) => info!(
+                "Wrote {} ms of sound to {}",
+                self.samples.len() as u64 * 1000 / SAMPLE_RATE as u64,
+                self.path.display()
+            ),
+            Err(err) => error!("Failed to write {}: {}", self.path.display(), err),
+        }
+    }
+}
+
+fn write_wav(path: &Path, samples: &[i16]) -> std::io::Result<()> {
+    let data_len = (samples.len() * 2) as u32;
+    let mut out = Vec::with_capacity(44 + data_len as usize);
+    out.extend_from_slice(b"RIFF");
+    out.extend_from_slice(&(36 + data_len).to_le_bytes());
+    out.extend_from_slice(b"WAVEfmt ");
+    out.extend_from_slice(&16u32.to_le_bytes()); // Format chunk size
+    out.extend_from_slice(&1u16.to_le_bytes()); // PCM
+    out.extend_from_slice(&1u16.to_le_bytes()); // Mono
+    out.extend_from_slice(&SAMPLE_RATE.to_le_bytes());
+    out.extend_from_slice(&(SAMPLE_RATE * 2).to_le_bytes()); // Bytes per second
+    out.extend_from_slice(&2u16.to_le_bytes()); // Bytes per sample
+    out.extend_from_slice(&16u16.to_le_bytes()); // Bits per sample
+    out.extend_from_slice(b"data");
+    out.extend_from_slice(&data_len.to_le_bytes());
+    for sample in samples {
+        out.extend_from_slice(&sample.to_le_bytes());
+    }
+    std::fs::write(path, out)
+}
@@ -1,6 +1,6 @@
 use std::io::{self, Read};
 use std::sync::{Arc, Mutex};
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use tetris_lib::common::GameController;
 use tetris_lib::log::debug;
 
@@ -69,11 +69,53 @@ enum KeyEvent {
     Quit,
     ButtonA,
     ButtonB,
+    HoldAB,
     None,
 }
 
+// Longest delay before a key held down starts repeating
+const KEY_REPEAT_DELAY: Duration = Duration::from_millis(700);
+// Longest pause between two repeats of a key held down
+const KEY_REPEAT_GAP: Duration = Duration::from_millis(200);
+
+// Key events of a key that may be held down
+#[derive(Debug, Clone, Copy)]
+struct KeyRepeat {
+    last: Instant,
+    // Whether the last event repeated the one before
+    repeating: bool,
+}
+
+impl KeyRepeat {
+    // Follows `previous` events of the key with one now
+    fn next(previous: Option<KeyRepeat>) -> Self {
+        let now = Instant::now();
+        let repeating = previous.is_some_and(|previous| {
+            let gap = if previous.repeating {
+                KEY_REPEAT_GAP
+            } else {
+                KEY_REPEAT_DELAY
+            };
+            now - previous.last < gap
+        });
+        Self {
+            last: now,
+            repeating,
+        }
+    }
+
+    // The terminal only tells keys held down by their repeats: the key is held
+    // from its first repeat until the repeats stop
+    fn is_held(&self) -> bool {
+        self.repeating && self.last.elapsed() < KEY_REPEAT_GAP
+    }
+}
+
 pub struct SimpleConsoleController {
     current_key: Arc<Mutex<KeyEvent>>,
+    // The terminal only reports one key at a time, so holding A and B down
+    // together has a key of its own
+    hold_ab: Arc<Mutex<Option<KeyRepeat>>>,
     _input_thread: std::thread::JoinHandle<()>,
 }
 
@@ -81,12 +123,17 @@ impl SimpleConsoleController {
     pub fn new() -> Self {
         let current_key = Arc::new(Mutex::new(KeyEvent::None));
         let current_key_clone = current_key.clone();
+        let hold_ab = Arc::new(Mutex::new(None));
+        let hold_ab_clone = hold_ab.clone();
 
         // Input processing thread (like machine.py)
         let input_thread = std::thread::spawn(move || {
             loop {
                 let key = Self::read_key();
-                if key != KeyEvent::None {
+                if key == KeyEvent::HoldAB {
+                    let mut hold_ab = hold_ab_clone.lock().unwrap();
+                    *hold_ab = Some(KeyRepeat::next(*hold_ab));
+                } else if key != KeyEvent::None {
                     {
                         let mut current = current_key_clone.lock().unwrap();
                         *current = key.clone();
@@ -105,6 +152,7 @@ impl SimpleConsoleController {
 
         Self {
             current_key,
+            hold_ab,
             _input_thread: input_thread,
         }
     }
@@ -141,6 +189,7 @@ impl SimpleConsoleController {
                     b's' | b'S' => KeyEvent::Down,
                     b'q' | b'Q' => KeyEvent::ButtonA,
                     b'e' | b'E' => KeyEvent::ButtonB,
+                    b'x' | b'X' => KeyEvent::HoldAB,
                     _ => {
                         debug!("Unknown key code {}", ch);
                         KeyEvent::None
@@ -225,4 +274,11 @@ impl GameController for SimpleConsoleController {
             _ => false, // No button press detected
         }
     }
+
+    fn ab_held(&self) -> bool {
+        self.hold_ab
+            .lock()
+            .unwrap()
+            .is_some_and(|hold| hold.is_held())
+    }
 }
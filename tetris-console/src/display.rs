@@ -1,32 +1,48 @@
-use smart_leds::RGB8;
 use std::io::{self, Write};
-use tetris_lib::common::{LedDisplay, SCREEN_HEIGHT, SCREEN_WIDTH};
+use tetris_lib::color::preview;
+use tetris_lib::common::{LedBuffer, LedDisplay};
+use tetris_lib::tiling::Tiling;
 
-// Simple console display implementation
-pub struct SimpleConsoleDisplay;
+// Simple console display implementation, works with any panel geometry.
+// It receives the LED strip and uses the tiling to put every LED back where it
+// sits on the canvas, so it draws exactly what the chained panels would show.
+// The LEDs arrive color corrected, like on hardware.
+pub struct SimpleConsoleDisplay {
+    tiling: Tiling<'static>,
+}
 
-impl LedDisplay for SimpleConsoleDisplay {
-    async fn write(&mut self, leds: &[RGB8; 256]) {
+impl SimpleConsoleDisplay {
+    pub fn new(tiling: Tiling<'static>) -> Self {
+        Self { tiling }
+    }
+}
+
+impl<const W: usize, const H: usize> LedDisplay<W, H> for SimpleConsoleDisplay {
+    async fn write(&mut self, leds: &LedBuffer<W, H>) {
         // Move cursor up to overwrite previous frame
-        let _ = io::stdout().write_all(format!("\x1b[{}A", SCREEN_HEIGHT * 2 + 1).as_bytes());
+        let _ = io::stdout().write_all(format!("\x1b[{}A", H * 2 + 1).as_bytes());
 
-        for y in 0..SCREEN_HEIGHT {
+        let strip = leds.as_flattened();
+        for y in 0..H {
             for _ in 0..2 {
                 // Double height for better visibility
-                for x in 0..SCREEN_WIDTH {
-                    let actual_x = if y % 2 == 0 { SCREEN_WIDTH - 1 - x } else { x };
-                    let idx = y * SCREEN_WIDTH + actual_x;
-
-                    if idx < leds.len() {
-                        let color = &leds[idx];
-                        let red = color.r as u16 * 20;
-                        let green = color.g as u16 * 20;
-                        let blue = color.b as u16 * 20;
-                        let _ = io::stdout().write_all(
-                            format!("\x1b[38;2;{};{};{}m####\x1b[0m", red, green, blue).as_bytes(),
-                        );
-                    } else {
-                        let _ = io::stdout().write_all(b"    ");
+                for x in 0..W {
+                    match self.tiling.index(x, y) {
+                        Some(idx) => {
+                            // Show what the LED would look like
+                            let color = preview(strip[idx]);
+                            let _ = io::stdout().write_all(
+                                format!(
+                                    "\x1b[38;2;{};{};{}m####\x1b[0m",
+                                    color.r, color.g, color.b
+                                )
+                                .as_bytes(),
+                            );
+                        }
+                        // No panel covers this part of the canvas
+                        None => {
+                            let _ = io::stdout().write_all(b"    ");
+                        }
                     }
                 }
                 let _ = io::stdout().write_all(b"\n");
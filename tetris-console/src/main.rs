@@ -1,19 +1,401 @@
-use std::time::Duration;
-use tetris_lib::{common::Timer, games::run_game_menu};
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+use tetris_lib::{
+    color::{ColorPipeline, CorrectedDisplay, DEFAULT_BRIGHTNESS},
+    common::{Game, Timer, SCREEN_HEIGHT, SCREEN_WIDTH},
+    games::tetris::{self, TetrisBot, TetrisGame, Weights},
+    games::{play, run_game_menu, GAME_NAMES},
+    layout::LedLayout,
+    log::{error, info},
+    replay::{RecordingController, Replay, ReplayError, ReplayHeader, ReplaySink},
+    settings::Settings,
+    tiling::{Tile, TiledDisplay, Tiling},
+};
 
+mod audio;
 mod control;
 mod display;
+mod storage;
 
+use audio::{ConsoleAudio, WavAudio};
 use control::{enable_raw_mode, restore_terminal, SimpleConsoleController};
 use display::SimpleConsoleDisplay;
+use storage::FileStorage;
+
+// Printed for `--help`
+const USAGE: &str = "\
+Usage: tetris-console [OPTIONS]
+
+Options:
+  --size WxH                        canvas size: 8x32 (default), 16x16, 32x8,
+                                    16x32 or 32x32
+  --layout WIRING[:CORNER][:flipped]
+                                    wiring of a single panel
+  --tile X,Y,WxH[,ROTATION][,LAYOUT]
+                                    a panel of a chained canvas, repeated
+  --brightness 0-255                LED brightness
+  --record FILE [--game NAME]       play a single game and record it
+  --replay FILE                     play a recorded game back
+  --wav FILE                        render the sounds of --record or --replay
+  --bench SEEDS [--weights HEIGHT,LINES,HOLES,BUMPINESS]
+                                    let the Tetris bot play headless
+  -h, --help                        print this help
+
+Keys:
+  arrows or WASD    joystick
+  Space or Enter    joystick button
+  Q and E           buttons A and B
+  X                 A and B held down together, keep it held for a second to
+                    quit a game: the terminal can't tell that Q and E are both
+                    held, X counts as held once it repeats
+  Esc or Ctrl+C     exit
+";
+
+// Canvas geometries the console emulator can be started with
+const SUPPORTED_SIZES: [(usize, usize); 5] = [(8, 32), (16, 16), (32, 8), (16, 32), (32, 32)];
 
 // Console timer implementation
-pub struct ConsoleTimer;
+struct ConsoleTimer {
+    start: Instant,
+}
+
+impl ConsoleTimer {
+    fn new() -> Self {
+        Self {
+            start: Instant::now(),
+        }
+    }
+}
 
 impl Timer for ConsoleTimer {
     async fn sleep_millis(&self, millis: u64) {
         tokio::time::sleep(Duration::from_millis(millis)).await;
     }
+
+    fn now_millis(&self) -> u64 {
+        self.start.elapsed().as_millis() as u64
+    }
+}
+
+// Replay file the recorded ticks are written to
+struct ReplayFile(std::fs::File);
+
+impl ReplaySink for ReplayFile {
+    fn write(&mut self, bytes: &[u8]) {
+        if let Err(err) = self.0.write_all(bytes) {
+            error!("Failed to write replay: {}", err);
+        }
+    }
+}
+
+// What to run on the canvas
+enum Mode {
+    Menu,
+    // Play a single game and record it
+    Record { game: u8, path: PathBuf },
+    // Play back a recorded game
+    Replay { path: PathBuf },
+    // Let the Tetris bot play seeds 1 to `seeds` headless
+    Bench { seeds: u32, weights: Weights },
+}
+
+// Command line options
+struct Options {
+    size: (usize, usize),
+    tiling: Tiling<'static>,
+    brightness: u8,
+    audio: ConsoleAudio,
+    mode: Mode,
+}
+
+// Parse `--size WxH`, `--layout WIRING[:CORNER][:flipped]`, any number of
+// `--tile X,Y,WxH[,ROTATION][,LAYOUT]`, `--brightness 0-255`,
+// `--record FILE [--game NAME]` or `--replay FILE` and `--wav FILE`, or
+// `--bench SEEDS [--weights HEIGHT,LINES,HOLES,BUMPINESS]` from the
+// command line, defaulting to the game menu on a single serpentine 8x32 panel.
+// `--help` prints the usage and the keys and exits.
+fn parse_args() -> Result<Options, String> {
+    let mut args = std::env::args().skip(1);
+    let mut size = (SCREEN_WIDTH, SCREEN_HEIGHT);
+    let mut layout = LedLayout::default();
+    let mut tiles = Vec::new();
+    let mut brightness = DEFAULT_BRIGHTNESS;
+    let mut game = 0;
+    let mut record = None;
+    let mut replay = None;
+    let mut wav = None;
+    let mut bench = None;
+    let mut weights = Weights::default();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => {
+                print!("{USAGE}");
+                std::process::exit(0);
+            }
+            "--game" => {
+                let value = args.next().ok_or("--game requires a game name")?;
+                game = GAME_NAMES
+                    .iter()
+                    .position(|name| *name == value)
+                    .ok_or_else(|| {
+                        format!(
+                            "unknown game '{value}', expected one of: {}",
+                            GAME_NAMES.join(", ")
+                        )
+                    })? as u8;
+            }
+            "--record" => {
+                record = Some(args.next().ok_or("--record requires a file name")?.into());
+            }
+            "--replay" => {
+                replay = Some(args.next().ok_or("--replay requires a file name")?.into());
+            }
+            "--bench" => {
+                let value = args.next().ok_or("--bench requires a number of seeds")?;
+                let seeds = value
+                    .parse()
+                    .map_err(|_| format!("invalid number of seeds '{value}'"))?;
+                bench = Some(seeds);
+            }
+            "--weights" => {
+                let value = args
+                    .next()
+                    .ok_or("--weights requires a value like -51,76,-36,-18")?;
+                let parsed = value
+                    .split(',')
+                    .map(str::parse)
+                    .collect::<Result<Vec<i32>, _>>()
+                    .map_err(|_| format!("invalid weights '{value}'"))?;
+                let [height, lines, holes, bumpiness] = parsed[..] else {
+                    return Err(format!(
+                        "invalid weights '{value}', expected HEIGHT,LINES,HOLES,BUMPINESS"
+                    ));
+                };
+                weights = Weights {
+                    height,
+                    lines,
+                    holes,
+                    bumpiness,
+                };
+            }
+            "--wav" => {
+                wav = Some(args.next().ok_or("--wav requires a file name")?.into());
+            }
+            "--brightness" => {
+                let value = args
+                    .next()
+                    .ok_or("--brightness requires a value from 0 to 255")?;
+                brightness = value
+                    .parse()
+                    .map_err(|_| format!("invalid brightness '{value}', expected 0 to 255"))?;
+            }
+            "--tile" => {
+                let value = args
+                    .next()
+                    .ok_or("--tile requires a value like 8,0,8x32,180")?;
+                let tile: Tile = value
+                    .parse()
+                    .map_err(|_| format!("invalid tile '{value}'"))?;
+                tiles.push(tile);
+            }
+            "--layout" => {
+                let value = args
+                    .next()
+                    .ok_or("--layout requires a value like serpentine:top-right")?;
+                layout = value
+                    .parse()
+                    .map_err(|_| format!("invalid layout '{value}'"))?;
+            }
+            "--size" => {
+                let value = args.next().ok_or("--size requires a value like 8x32")?;
+                let (width, height) = value
+                    .split_once('x')
+                    .ok_or_else(|| format!("invalid size '{value}', expected WxH"))?;
+                size = (
+                    width
+                        .parse()
+                        .map_err(|_| format!("invalid width '{width}'"))?,
+                    height
+                        .parse()
+                        .map_err(|_| format!("invalid height '{height}'"))?,
+                );
+            }
+            _ => return Err(format!("unknown argument '{arg}', see --help")),
+        }
+    }
+
+    if !SUPPORTED_SIZES.contains(&size) {
+        let supported: Vec<String> = SUPPORTED_SIZES
+            .iter()
+            .map(|(w, h)| format!("{w}x{h}"))
+            .collect();
+        return Err(format!(
+            "unsupported size {}x{}, expected one of: {}",
+            size.0,
+            size.1,
+            supported.join(", ")
+        ));
+    }
+
+    // Without explicit tiles the canvas is a single panel
+    if tiles.is_empty() {
+        tiles.push(Tile::new(0, 0, size.0, size.1).with_layout(layout));
+    }
+    let tiling = Tiling::new(tiles.leak());
+    if !tiling.fits(size.0, size.1) {
+        return Err(format!(
+            "tiles overlap or don't fit the {}x{} canvas",
+            size.0, size.1
+        ));
+    }
+
+    let mode = match (record, replay, bench) {
+        (Some(_), Some(_), _) => return Err("--record and --replay can't be combined".into()),
+        (Some(_), _, Some(_)) | (_, Some(_), Some(_)) => {
+            return Err("--bench can't be combined with --record or --replay".into())
+        }
+        (Some(path), None, None) => Mode::Record { game, path },
+        (None, Some(path), None) => Mode::Replay { path },
+        (None, None, Some(seeds)) => Mode::Bench { seeds, weights },
+        (None, None, None) => Mode::Menu,
+    };
+
+    // The menu never returns, so the file would never be written
+    let audio = match wav {
+        Some(_) if matches!(mode, Mode::Menu | Mode::Bench { .. }) => {
+            return Err("--wav requires --record or --replay".into())
+        }
+        Some(path) => ConsoleAudio::Wav(WavAudio::new(path)),
+        None => ConsoleAudio::Bell,
+    };
+
+    Ok(Options {
+        size,
+        tiling,
+        brightness,
+        audio,
+        mode,
+    })
+}
+
+fn seed() -> u32 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u32
+}
+
+// Ticks a benchmark game may last, well over an hour of play
+const BENCH_TICKS: u32 = 100_000;
+
+// Let the Tetris bot with `weights` play seeds 1 to `seeds` without a display
+// and report the lines it cleared
+fn bench<const W: usize, const H: usize>(seeds: u32, weights: Weights) -> Result<(), String> {
+    if !TetrisGame::<W, H>::fits(W, H) {
+        return Err(format!("Tetris doesn't fit a {W}x{H} canvas"));
+    }
+    let settings = Settings::default();
+    let mut total = 0;
+    for seed in 1..=seeds {
+        let bot = TetrisBot::new(weights);
+        let lines = tetris::benchmark::<W, H>(seed, &settings, bot, BENCH_TICKS);
+        println!("seed {seed}: {lines} lines");
+        total += lines as u64;
+    }
+    println!(
+        "{:?} on {W}x{H}: {:.1} lines per seed",
+        weights,
+        total as f64 / seeds.max(1) as f64
+    );
+    Ok(())
+}
+
+async fn run<const W: usize, const H: usize>(
+    tiling: Tiling<'static>,
+    brightness: u8,
+    mut audio: ConsoleAudio,
+    mode: Mode,
+) -> Result<(), Box<dyn std::error::Error>> {
+    // Same color pipeline as on hardware, so the terminal shows the LED colors
+    let pipeline = ColorPipeline::new().with_brightness(brightness);
+    let output = CorrectedDisplay::new(SimpleConsoleDisplay::new(tiling), pipeline);
+    let mut display = TiledDisplay::new(output, tiling);
+    let timer = ConsoleTimer::new();
+
+    match mode {
+        Mode::Menu => {
+            let mut controller = SimpleConsoleController::new();
+            let mut storage = FileStorage::new();
+            // Use the extracted game menu loop
+            run_game_menu::<_, _, _, _, _, _, W, H>(
+                &mut display,
+                &mut audio,
+                &mut controller,
+                &timer,
+                &mut storage,
+                seed,
+            )
+            .await;
+        }
+        Mode::Record { game, path } => {
+            // Played with the level and controls picked in the menu
+            let settings = Settings::load(&mut FileStorage::new())
+                .await
+                .unwrap_or_default();
+            let header = ReplayHeader::new(game, W, H, seed(), &settings);
+            let file = ReplayFile(std::fs::File::create(&path)?);
+            let mut controller =
+                RecordingController::new(SimpleConsoleController::new(), file, header);
+            let outcome = play::<_, _, _, _, W, H>(
+                game,
+                header.seed,
+                &header.settings(),
+                &mut display,
+                &mut audio,
+                &mut controller,
+                &timer,
+            )
+            .await;
+            controller.finish();
+            let name = GAME_NAMES[game as usize];
+            let Some(outcome) = outcome else {
+                std::fs::remove_file(&path)?;
+                return Err(format!("{name} doesn't fit a {W}x{H} canvas").into());
+            };
+            info!("Recorded {} to {}: {:?}", name, path.display(), outcome);
+        }
+        Mode::Replay { path } => {
+            let data = std::fs::read(&path)?;
+            let replay = Replay::parse_for(&data, W, H).map_err(|err| match err {
+                ReplayError::WrongSize(w, h) => {
+                    format!("replay was recorded on a {w}x{h} canvas, not {W}x{H}")
+                }
+                err => format!("invalid replay: {err:?}"),
+            })?;
+            let mut controller = replay.controller();
+            let outcome = play::<_, _, _, _, W, H>(
+                replay.header.game,
+                replay.header.seed,
+                &replay.header.settings(),
+                &mut display,
+                &mut audio,
+                &mut controller,
+                &timer,
+            )
+            .await
+            .ok_or_else(|| {
+                format!(
+                    "game {} of the replay is unknown or doesn't fit a {W}x{H} canvas",
+                    replay.header.game
+                )
+            })?;
+            info!("Replay finished: {:?}", outcome);
+        }
+        Mode::Bench { seeds, weights } => bench::<W, H>(seeds, weights)?,
+    }
+    audio.finish();
+    Ok(())
 }
 
 #[tokio::main(flavor = "current_thread")]
@@ -24,28 +406,39 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         .parse_default_env()
         .init();
 
-    // Enable raw terminal mode like machine.py
-    enable_raw_mode();
+    let options = parse_args()?;
 
-    // Set up Ctrl+C handler to restore terminal
-    ctrlc::set_handler(move || {
-        restore_terminal();
-        println!("\nTerminal restored. Goodbye!");
-        std::process::exit(0);
-    })?;
-
-    let mut display = SimpleConsoleDisplay;
-    let mut controller = SimpleConsoleController::new();
-    let timer = ConsoleTimer;
-
-    // Use the extracted game menu loop
-    run_game_menu(&mut display, &mut controller, &timer, || {
-        std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap()
-            .as_millis() as u32
-    })
-    .await;
+    // Benchmarks only print, they leave the terminal alone
+    let interactive = !matches!(options.mode, Mode::Bench { .. });
+    if interactive {
+        // Enable raw terminal mode like machine.py
+        enable_raw_mode();
 
-    Ok(())
+        // Set up Ctrl+C handler to restore terminal
+        ctrlc::set_handler(move || {
+            restore_terminal();
+            println!("\nTerminal restored. Goodbye!");
+            std::process::exit(0);
+        })?;
+    }
+
+    let Options {
+        size,
+        tiling,
+        brightness,
+        audio,
+        mode,
+    } = options;
+    let result = match size {
+        (16, 16) => run::<16, 16>(tiling, brightness, audio, mode).await,
+        (32, 8) => run::<32, 8>(tiling, brightness, audio, mode).await,
+        (16, 32) => run::<16, 32>(tiling, brightness, audio, mode).await,
+        (32, 32) => run::<32, 32>(tiling, brightness, audio, mode).await,
+        _ => run::<SCREEN_WIDTH, SCREEN_HEIGHT>(tiling, brightness, audio, mode).await,
+    };
+
+    if interactive {
+        restore_terminal();
+    }
+    result
 }
@@ -0,0 +1,46 @@
+use std::path::PathBuf;
+use tetris_lib::log::error;
+use tetris_lib::storage::Storage;
+
+// Storage keeping every key in its own file under the XDG data directory
+// ($XDG_DATA_HOME/tetris-console, or ~/.local/share/tetris-console)
+pub struct FileStorage {
+    dir: Option<PathBuf>,
+}
+
+impl FileStorage {
+    pub fn new() -> Self {
+        let dir = std::env::var_os("XDG_DATA_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| {
+                std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/share"))
+            })
+            .map(|dir| dir.join("tetris-console"));
+        if dir.is_none() {
+            error!("No data directory, high scores won't be kept");
+        }
+        Self { dir }
+    }
+
+    fn path(&self, key: &str) -> Option<PathBuf> {
+        self.dir.as_ref().map(|dir| dir.join(format!("{key}.bin")))
+    }
+}
+
+impl Storage for FileStorage {
+    async fn load(&mut self, key: &str, buf: &mut [u8]) -> Option<usize> {
+        let data = std::fs::read(self.path(key)?).ok()?;
+        buf.get_mut(..data.len())?.copy_from_slice(&data);
+        Some(data.len())
+    }
+
+    async fn save(&mut self, key: &str, data: &[u8]) {
+        let (Some(dir), Some(path)) = (&self.dir, self.path(key)) else {
+            return;
+        };
+        if let Err(err) = std::fs::create_dir_all(dir).and_then(|_| std::fs::write(&path, data)) {
+            error!("Failed to save {}: {}", path.display(), err);
+        }
+    }
+}
@@ -0,0 +1,106 @@
+use embassy_futures::select::{select, Either};
+use embassy_rp::pwm::{Config as PwmConfig, Pwm};
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::signal::Signal;
+use embassy_time::Timer;
+use tetris_lib::audio::{Audio, Note};
+
+// The PWM counts the system clock divided down so that audible frequencies fit
+// the 16 bit counter: 125 MHz / 64 is 1.95 MHz, down to 30 Hz
+const SYS_CLOCK_HZ: u32 = 125_000_000;
+const DIVIDER: u8 = 64;
+
+// What the buzzer task should play next
+enum Command {
+    Play(&'static [Note]),
+    Tone(Note),
+    Stop,
+}
+
+static COMMAND: Signal<CriticalSectionRawMutex, Command> = Signal::new();
+
+// Audio for the games, it hands the notes over to the buzzer task and returns
+// right away
+pub struct BuzzerAudio;
+
+impl Audio for BuzzerAudio {
+    fn play(&mut self, melody: &'static [Note]) {
+        COMMAND.signal(Command::Play(melody));
+    }
+
+    fn tone(&mut self, note: Note) {
+        COMMAND.signal(Command::Tone(note));
+    }
+
+    fn stop(&mut self) {
+        COMMAND.signal(Command::Stop);
+    }
+}
+
+// Piezo buzzer on channel B of a PWM slice, playing square waves at 50% duty
+pub struct Buzzer {
+    pwm: Pwm<'static>,
+    config: PwmConfig,
+}
+
+impl Buzzer {
+    pub fn new(pwm: Pwm<'static>) -> Self {
+        let mut config = PwmConfig::default();
+        config.divider = DIVIDER.into();
+        config.compare_b = 0;
+        let mut buzzer = Self { pwm, config };
+        buzzer.set(0);
+        buzzer
+    }
+
+    // Sets the frequency of the square wave, 0 silences the buzzer
+    fn set(&mut self, freq_hz: u16) {
+        if freq_hz == 0 {
+            self.config.compare_b = 0;
+        } else {
+            let top = (SYS_CLOCK_HZ / DIVIDER as u32 / freq_hz as u32).saturating_sub(1);
+            self.config.top = top.min(u16::MAX as u32) as u16;
+            self.config.compare_b = self.config.top / 2;
+        }
+        self.pwm.set_config(&self.config);
+    }
+
+    // Plays `note`, returns the command that cut it off, if any
+    async fn play_note(&mut self, note: Note) -> Option<Command> {
+        self.set(note.freq_hz);
+        match select(Timer::after_millis(note.millis as u64), COMMAND.wait()).await {
+            Either::First(()) => None,
+            Either::Second(command) => Some(command),
+        }
+    }
+
+    pub async fn run(mut self) -> ! {
+        let mut next = None;
+        loop {
+            let command = match next.take() {
+                Some(command) => command,
+                None => COMMAND.wait().await,
+            };
+            next = match command {
+                Command::Play(melody) => {
+                    let mut interrupted = None;
+                    for &note in melody {
+                        interrupted = self.play_note(note).await;
+                        if interrupted.is_some() {
+                            break;
+                        }
+                    }
+                    interrupted
+                }
+                Command::Tone(note) => self.play_note(note).await,
+                Command::Stop => None,
+            };
+            self.set(0);
+        }
+    }
+}
+
+#[embassy_executor::task]
+pub async fn buzzer_task(buzzer: Buzzer) {
+    buzzer.run().await;
+}
@@ -1,4 +1,5 @@
 use core::marker::Sized;
+use core::sync::atomic::{AtomicBool, Ordering};
 use embassy_rp::adc::{Adc, Channel};
 use embassy_rp::gpio::Input;
 use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
@@ -8,11 +9,16 @@ use embassy_sync::signal::Signal;
 pub static JOYSTICK_BUTTON_SIGNAL: Signal<CriticalSectionRawMutex, bool> = Signal::new();
 pub static BUTTON_A_SIGNAL: Signal<CriticalSectionRawMutex, bool> = Signal::new();
 pub static BUTTON_B_SIGNAL: Signal<CriticalSectionRawMutex, bool> = Signal::new();
+// Whether the buttons are held down right now
+pub static JOYSTICK_BUTTON_HELD: AtomicBool = AtomicBool::new(false);
+pub static BUTTON_A_HELD: AtomicBool = AtomicBool::new(false);
+pub static BUTTON_B_HELD: AtomicBool = AtomicBool::new(false);
 
 // Hardware button wrapper for running in tasks
 pub struct ButtonHardware {
     button: Input<'static>,
     signal: &'static Signal<CriticalSectionRawMutex, bool>,
+    held: &'static AtomicBool,
 }
 
 impl ButtonHardware {
@@ -20,6 +26,7 @@ impl ButtonHardware {
         Self {
             button,
             signal: &JOYSTICK_BUTTON_SIGNAL,
+            held: &JOYSTICK_BUTTON_HELD,
         }
     }
 
@@ -27,6 +34,7 @@ impl ButtonHardware {
         Self {
             button,
             signal: &BUTTON_A_SIGNAL,
+            held: &BUTTON_A_HELD,
         }
     }
 
@@ -34,6 +42,7 @@ impl ButtonHardware {
         Self {
             button,
             signal: &BUTTON_B_SIGNAL,
+            held: &BUTTON_B_HELD,
         }
     }
 
@@ -44,9 +53,14 @@ impl ButtonHardware {
 
             // Signal that button was pressed
             self.signal.signal(true);
+            self.held.store(true, Ordering::Relaxed);
 
             // Debounce delay
             embassy_time::Timer::after_millis(200).await;
+
+            // Wait for the button to be released
+            self.button.wait_for_high().await;
+            self.held.store(false, Ordering::Relaxed);
         }
     }
 }
@@ -138,4 +152,8 @@ impl<'a> tetris_lib::common::GameController for Control<'a> {
     fn b_was_pressed(&self) -> bool {
         BUTTON_B_SIGNAL.try_take().unwrap_or(false)
     }
+
+    fn ab_held(&self) -> bool {
+        BUTTON_A_HELD.load(Ordering::Relaxed) && BUTTON_B_HELD.load(Ordering::Relaxed)
+    }
 }
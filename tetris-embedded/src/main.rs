@@ -1,25 +1,33 @@
 #![no_std]
 #![no_main]
 
+use crate::audio::{buzzer_task, Buzzer, BuzzerAudio};
 use crate::control::{
     button_a_task, button_b_task, joystick_button_task, ButtonHardware, Control, Joystick,
 };
+use crate::storage::FlashStorage;
 use defmt::*;
 use embassy_executor::Spawner;
 use embassy_rp::adc::InterruptHandler as AdcInterruptHandler;
 use embassy_rp::adc::{Adc, Channel, Config};
 use embassy_rp::bind_interrupts;
+use embassy_rp::flash::Flash;
 use embassy_rp::gpio::{Input, Pull};
 use embassy_rp::peripherals::PIO0;
 use embassy_rp::pio::{InterruptHandler, Pio};
 use embassy_rp::pio_programs::ws2812::{PioWs2812, PioWs2812Program};
+use embassy_rp::pwm::{Config as PwmConfig, Pwm};
 use embassy_time::{Instant, Timer as EmbassyTimer};
-use smart_leds::RGB8;
-use tetris_lib::common::{LedDisplay, Timer};
+use tetris_lib::color::{ColorPipeline, CorrectedDisplay};
+use tetris_lib::common::{LedBuffer, LedDisplay, Timer, SCREEN_HEIGHT, SCREEN_WIDTH};
 use tetris_lib::games::run_game_menu;
+use tetris_lib::layout::LedLayout;
+use tetris_lib::tiling::{Tile, TiledDisplay, Tiling};
 use {defmt_rtt as _, panic_probe as _};
 
+mod audio;
 mod control;
+mod storage;
 
 // Embedded timer implementation
 pub struct EmbeddedTimer;
@@ -28,21 +36,37 @@ impl Timer for EmbeddedTimer {
     async fn sleep_millis(&self, millis: u64) {
         EmbassyTimer::after_millis(millis).await;
     }
+
+    fn now_millis(&self) -> u64 {
+        Instant::now().as_millis()
+    }
 }
 
-// Wrapper type to implement LedDisplay for PioWs2812
-pub struct Ws2812Display<'a>(PioWs2812<'a, PIO0, 0, 256>);
+// Canvas covered by the panels chained on the strip. To chain more panels,
+// grow the canvas and add a tile for each panel in the order the strip runs
+// through them, e.g. `Tile::new(8, 0, 8, 32).rotated(Rotation::Deg180)`.
+const CANVAS_WIDTH: usize = SCREEN_WIDTH;
+const CANVAS_HEIGHT: usize = SCREEN_HEIGHT;
+const LED_COUNT: usize = CANVAS_WIDTH * CANVAS_HEIGHT;
+
+static PANELS: [Tile; 1] =
+    [Tile::new(0, 0, SCREEN_WIDTH, SCREEN_HEIGHT).with_layout(LedLayout::SERPENTINE)];
+
+// Wrapper type to implement LedDisplay for PioWs2812, it receives the LEDs in
+// strip order from the TiledDisplay, already color corrected
+pub struct Ws2812Display<'a>(PioWs2812<'a, PIO0, 0, LED_COUNT>);
 
 impl<'a> Ws2812Display<'a> {
-    pub fn new(ws2812: PioWs2812<'a, PIO0, 0, 256>) -> Self {
+    pub fn new(ws2812: PioWs2812<'a, PIO0, 0, LED_COUNT>) -> Self {
         Self(ws2812)
     }
 }
 
 // Implement LedDisplay for our wrapper
-impl LedDisplay for Ws2812Display<'_> {
-    async fn write(&mut self, leds: &[RGB8; 256]) {
-        self.0.write(leds).await;
+impl LedDisplay<CANVAS_WIDTH, CANVAS_HEIGHT> for Ws2812Display<'_> {
+    async fn write(&mut self, leds: &LedBuffer<CANVAS_WIDTH, CANVAS_HEIGHT>) {
+        let strip: &[_; LED_COUNT] = leds.as_flattened().try_into().unwrap();
+        self.0.write(strip).await;
     }
 }
 
@@ -63,7 +87,8 @@ async fn main(spawner: Spawner) {
 
     let program = PioWs2812Program::new(&mut common);
     let ws2812 = PioWs2812::new(&mut common, sm0, p.DMA_CH0, p.PIN_13, &program);
-    let mut display = Ws2812Display::new(ws2812);
+    let output = CorrectedDisplay::new(Ws2812Display::new(ws2812), ColorPipeline::new());
+    let mut display = TiledDisplay::new(output, Tiling::new(&PANELS));
 
     // Hardware setup
     let adc_reader = Adc::new(p.ADC, Irqs, Config::default());
@@ -85,14 +110,29 @@ async fn main(spawner: Spawner) {
     spawner.spawn(button_a_task(button_a_hw)).unwrap();
     spawner.spawn(button_b_task(button_b_hw)).unwrap();
 
+    // Piezo buzzer between GPIO 15 (PWM slice 7, channel B) and ground
+    let buzzer = Buzzer::new(Pwm::new_output_b(
+        p.PWM_SLICE7,
+        p.PIN_15,
+        PwmConfig::default(),
+    ));
+    spawner.spawn(buzzer_task(buzzer)).unwrap();
+
     // Create game controller (no longer needs to own button hardware)
     let joystick = Joystick::new(adc_reader, adc_pin_x, adc_pin_y);
     let mut control = Control::new(joystick);
     let timer = EmbeddedTimer;
+    let mut audio = BuzzerAudio;
+    let mut storage = FlashStorage::new(Flash::new_blocking(p.FLASH));
 
     info!("Starting main menu loop");
-    run_game_menu(&mut display, &mut control, &timer, || {
-        Instant::now().as_ticks() as u32
-    })
+    run_game_menu(
+        &mut display,
+        &mut audio,
+        &mut control,
+        &timer,
+        &mut storage,
+        || Instant::now().as_ticks() as u32,
+    )
     .await;
 }
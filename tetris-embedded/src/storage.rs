@@ -0,0 +1,71 @@
+use defmt::*;
+use embassy_rp::flash::{Blocking, Flash, ERASE_SIZE};
+use embassy_rp::peripherals::FLASH;
+use tetris_lib::storage::Storage;
+
+// Size of the flash chip on the board
+pub const FLASH_SIZE: usize = 2 * 1024 * 1024;
+
+// The last sectors of the flash are kept out of the program image (see
+// memory.x), each key gets one of them
+const RESERVED_SECTORS: usize = 4;
+const STORAGE_OFFSET: usize = FLASH_SIZE - RESERVED_SECTORS * ERASE_SIZE;
+
+// Keys stored in flash, a key's index is its sector
+const KEYS: [&str; 2] = ["highscores", "settings"];
+
+// Each sector starts with the length of the value (u16, little endian), an
+// erased sector reads as 0xffff and holds no value
+const LEN_SIZE: usize = 2;
+
+// Storage implementation keeping values in reserved flash sectors
+pub struct FlashStorage<'d> {
+    flash: Flash<'d, FLASH, Blocking, FLASH_SIZE>,
+}
+
+impl<'d> FlashStorage<'d> {
+    pub fn new(flash: Flash<'d, FLASH, Blocking, FLASH_SIZE>) -> Self {
+        Self { flash }
+    }
+
+    fn sector(key: &str) -> Option<u32> {
+        let index = KEYS.iter().position(|k| *k == key)?;
+        Some((STORAGE_OFFSET + index * ERASE_SIZE) as u32)
+    }
+}
+
+impl Storage for FlashStorage<'_> {
+    async fn load(&mut self, key: &str, buf: &mut [u8]) -> Option<usize> {
+        let sector = Self::sector(key)?;
+        let mut len = [0; LEN_SIZE];
+        self.flash.blocking_read(sector, &mut len).ok()?;
+        let len = u16::from_le_bytes(len) as usize;
+        if len > ERASE_SIZE - LEN_SIZE || len > buf.len() {
+            return None;
+        }
+        self.flash
+            .blocking_read(sector + LEN_SIZE as u32, &mut buf[..len])
+            .ok()?;
+        Some(len)
+    }
+
+    async fn save(&mut self, key: &str, data: &[u8]) {
+        let Some(sector) = Self::sector(key) else {
+            warn!("No flash sector for key {}", key);
+            return;
+        };
+        if data.len() > ERASE_SIZE - LEN_SIZE {
+            warn!("Value of {} doesn't fit a flash sector", key);
+            return;
+        }
+        let len = (data.len() as u16).to_le_bytes();
+        let result = self
+            .flash
+            .blocking_erase(sector, sector + ERASE_SIZE as u32)
+            .and_then(|_| self.flash.blocking_write(sector, &len))
+            .and_then(|_| self.flash.blocking_write(sector + LEN_SIZE as u32, data));
+        if let Err(err) = result {
+            error!("Failed to save {}: {}", key, err);
+        }
+    }
+}
@@ -0,0 +1,267 @@
+// Sound
+//
+// Games don't drive the speaker themselves: like pixels they only describe
+// what happened, by raising a sound effect during `update`. The driver hands
+// the effect to the platform's `Audio`, which plays the notes in the
+// background (a PWM buzzer, WebAudio oscillators, a WAV file). Only square
+// wave tones are used, which is all a piezo buzzer can do.
+
+/// A tone of `freq_hz` lasting `millis`, a frequency of 0 is a rest
+#[derive(Default, Copy, Clone, PartialEq, Eq, Debug)]
+pub struct Note {
+    pub freq_hz: u16,
+    pub millis: u16,
+}
+
+impl Note {
+    pub const fn new(freq_hz: u16, millis: u16) -> Self {
+        Self { freq_hz, millis }
+    }
+
+    pub const fn rest(millis: u16) -> Self {
+        Self::new(0, millis)
+    }
+
+    pub fn is_rest(&self) -> bool {
+        self.freq_hz == 0
+    }
+}
+
+// Pitches, in Hz
+pub const C4: u16 = 262;
+pub const E4: u16 = 330;
+pub const G4: u16 = 392;
+pub const A4: u16 = 440;
+pub const C5: u16 = 523;
+pub const D5: u16 = 587;
+pub const E5: u16 = 659;
+pub const F5: u16 = 698;
+pub const G5: u16 = 784;
+pub const A5: u16 = 880;
+pub const C6: u16 = 1047;
+pub const E6: u16 = 1319;
+pub const G6: u16 = 1568;
+
+/// Sound effects, from the least to the most important: when a tick raises
+/// several, the most important one is played
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum Sfx {
+    /// A shot is fired
+    Fire,
+    /// A piece lands
+    Lock,
+    /// The snake eats an apple
+    Apple,
+    /// A power-up is collected
+    Powerup,
+    /// Something is hit or destroyed
+    Hit,
+    /// A line is cleared
+    LineClear,
+    /// The player crashes and loses a life
+    Crash,
+    GameOver,
+    /// Played when the menu comes up
+    Jingle,
+}
+
+// Melodies of the sound effects
+const FIRE: [Note; 2] = [Note::new(G6, 15), Note::new(E6, 15)];
+const LOCK: [Note; 1] = [Note::new(C4, 20)];
+const APPLE: [Note; 2] = [Note::new(E5, 40), Note::new(A5, 60)];
+const POWERUP: [Note; 4] = [
+    Note::new(C5, 40),
+    Note::new(E5, 40),
+    Note::new(G5, 40),
+    Note::new(C6, 80),
+];
+const HIT: [Note; 2] = [Note::new(A4, 30), Note::new(E4, 50)];
+const LINE_CLEAR: [Note; 3] = [Note::new(C5, 50), Note::new(E5, 50), Note::new(G5, 100)];
+const CRASH: [Note; 3] = [Note::new(G4, 60), Note::new(E4, 60), Note::new(C4, 120)];
+const GAME_OVER: [Note; 5] = [
+    Note::new(G4, 150),
+    Note::rest(30),
+    Note::new(E4, 150),
+    Note::rest(30),
+    Note::new(C4, 400),
+];
+const JINGLE: [Note; 7] = [
+    Note::new(C5, 80),
+    Note::new(E5, 80),
+    Note::new(G5, 80),
+    Note::rest(40),
+    Note::new(F5, 80),
+    Note::new(D5, 80),
+    Note::new(C6, 200),
+];
+
+impl Sfx {
+    pub fn melody(self) -> &'static [Note] {
+        match self {
+            Sfx::Fire => &FIRE,
+            Sfx::Lock => &LOCK,
+            Sfx::Apple => &APPLE,
+            Sfx::Powerup => &POWERUP,
+            Sfx::Hit => &HIT,
+            Sfx::LineClear => &LINE_CLEAR,
+            Sfx::Crash => &CRASH,
+            Sfx::GameOver => &GAME_OVER,
+            Sfx::Jingle => &JINGLE,
+        }
+    }
+}
+
+/// Total length of `melody`
+pub fn duration_millis(melody: &[Note]) -> u32 {
+    melody.iter().map(|note| note.millis as u32).sum()
+}
+
+/// Amplitude of the square wave rendered by `samples`
+pub const AMPLITUDE: i16 = i16::MAX / 4;
+
+/// `melody` as a square wave sampled `sample_rate` times per second, for
+/// backends that play or store PCM samples
+pub fn samples(melody: &[Note], sample_rate: u32) -> impl Iterator<Item = i16> + '_ {
+    melody.iter().flat_map(move |note| {
+        let count = note.millis as u32 * sample_rate / 1000;
+        // Twice the frequency: the wave flips every half period
+        let flips = 2 * note.freq_hz as u32;
+        (0..count).map(move |i| {
+            if note.is_rest() {
+                0
+            } else if (i as u64 * flips as u64 / sample_rate as u64).is_multiple_of(2) {
+                AMPLITUDE
+            } else {
+                -AMPLITUDE
+            }
+        })
+    })
+}
+
+/// Keeps the most important of the sounds raised during a tick
+pub fn raise(sound: &mut Option<Sfx>, sfx: Sfx) {
+    *sound = (*sound).max(Some(sfx));
+}
+
+/// Plays notes in the background
+pub trait Audio {
+    /// Starts playing `melody`, cutting off whatever is playing
+    fn play(&mut self, melody: &'static [Note]);
+    /// Starts playing a single tone, cutting off whatever is playing
+    fn tone(&mut self, note: Note);
+    /// Silences the speaker
+    fn stop(&mut self);
+
+    fn play_sfx(&mut self, sfx: Sfx) {
+        self.play(sfx.melody());
+    }
+}
+
+impl<A: Audio + ?Sized> Audio for &mut A {
+    fn play(&mut self, melody: &'static [Note]) {
+        (**self).play(melody);
+    }
+
+    fn tone(&mut self, note: Note) {
+        (**self).tone(note);
+    }
+
+    fn stop(&mut self) {
+        (**self).stop();
+    }
+}
+
+/// Audio for platforms without a speaker
+#[derive(Default, Copy, Clone, Debug)]
+pub struct NoAudio;
+
+impl Audio for NoAudio {
+    fn play(&mut self, _melody: &'static [Note]) {}
+
+    fn tone(&mut self, _note: Note) {}
+
+    fn stop(&mut self) {}
+}
+
+/// Audio that can be muted from the menu
+pub struct Mute<A> {
+    output: A,
+    muted: bool,
+}
+
+impl<A: Audio> Mute<A> {
+    pub fn new(output: A) -> Self {
+        Self {
+            output,
+            muted: false,
+        }
+    }
+
+    pub fn is_muted(&self) -> bool {
+        self.muted
+    }
+
+    pub fn set_muted(&mut self, muted: bool) {
+        if muted {
+            self.output.stop();
+        }
+        self.muted = muted;
+    }
+}
+
+impl<A: Audio> Audio for Mute<A> {
+    fn play(&mut self, melody: &'static [Note]) {
+        if !self.muted {
+            self.output.play(melody);
+        }
+    }
+
+    fn tone(&mut self, note: Note) {
+        if !self.muted {
+            self.output.tone(note);
+        }
+    }
+
+    fn stop(&mut self) {
+        self.output.stop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::RecordingAudio;
+    use alloc::vec::Vec;
+
+    #[test]
+    fn important_sounds_win() {
+        let mut sound = None;
+        raise(&mut sound, Sfx::Hit);
+        raise(&mut sound, Sfx::Fire);
+        assert_eq!(sound, Some(Sfx::Hit));
+        raise(&mut sound, Sfx::GameOver);
+        assert_eq!(sound, Some(Sfx::GameOver));
+    }
+
+    #[test]
+    fn melodies_render_to_square_waves() {
+        let melody = [Note::new(1000, 10), Note::rest(5)];
+        assert_eq!(duration_millis(&melody), 15);
+        let wave: Vec<i16> = samples(&melody, 8000).collect();
+        assert_eq!(wave.len(), 120);
+        // 1 kHz at 8 kHz: 4 samples up, 4 samples down
+        let (up, down) = (AMPLITUDE, -AMPLITUDE);
+        assert_eq!(wave[..8], [up, up, up, up, down, down, down, down]);
+        assert!(wave[80..].iter().all(|&sample| sample == 0));
+    }
+
+    #[test]
+    fn muted_audio_stays_silent() {
+        let mut audio = Mute::new(RecordingAudio::new());
+        audio.play_sfx(Sfx::Apple);
+        audio.set_muted(true);
+        audio.play_sfx(Sfx::Hit);
+        assert!(audio.is_muted());
+        assert_eq!(audio.output.played(), &[Sfx::Apple.melody()]);
+    }
+}
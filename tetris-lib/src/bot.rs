@@ -0,0 +1,279 @@
+// Computer players
+//
+// A bot plays a game the way a person does, through a `GameController`, and
+// decides every tick by looking at the game. The driver needs the game
+// mutably while it runs, so the game sits in a `RefCell` shared by the driver
+// (through `SharedGame`) and the bot's controller (through `BotController`),
+// which borrow it in turns: the controller while the input is read, the
+// driver while it updates and draws.
+//
+// Bots live next to their games, they need to see the game's private state.
+
+use core::cell::RefCell;
+
+use crate::audio::Sfx;
+use crate::common::{EndReason, FrameBuffer, Game, GameController, GameOutcome, InputSnapshot};
+
+/// Decides the input of a game, tick by tick
+pub trait Bot<G> {
+    /// Input for the next tick of `game`
+    fn input(&mut self, game: &G) -> InputSnapshot;
+}
+
+/// Bot for games that run by themselves
+#[derive(Default, Copy, Clone, Debug)]
+pub struct IdleBot;
+
+impl<G> Bot<G> for IdleBot {
+    fn input(&mut self, _game: &G) -> InputSnapshot {
+        InputSnapshot::default()
+    }
+}
+
+/// A game the driver runs while a `BotController` looks at it. Bots never
+/// hold A and B, so the game ends as soon as a `DemoController` does, without
+/// waiting for the quit gesture.
+pub struct SharedGame<'a, G>(pub &'a RefCell<G>);
+
+impl<G, const W: usize, const H: usize> Game<W, H> for SharedGame<'_, G>
+where
+    G: Game<W, H>,
+{
+    const MIN_WIDTH: usize = G::MIN_WIDTH;
+    const MIN_HEIGHT: usize = G::MIN_HEIGHT;
+    const TICK_MILLIS: u64 = G::TICK_MILLIS;
+
+    fn update(&mut self, input: InputSnapshot) -> Option<GameOutcome> {
+        if input.ab_held {
+            return Some(self.outcome(EndReason::Quit));
+        }
+        self.0.borrow_mut().update(input)
+    }
+
+    fn outcome(&self, reason: EndReason) -> GameOutcome {
+        self.0.borrow().outcome(reason)
+    }
+
+    fn draw(&self, screen: &mut FrameBuffer<W, H>) {
+        self.0.borrow().draw(screen);
+    }
+
+    fn take_sound(&mut self) -> Option<Sfx> {
+        self.0.borrow_mut().take_sound()
+    }
+}
+
+/// Controller asking `bot` for the input of every tick (read with
+/// `read_input` or `read_x`, like `ReplayController`)
+pub struct BotController<'a, G, B> {
+    game: &'a RefCell<G>,
+    bot: B,
+    current: InputSnapshot,
+}
+
+impl<'a, G, B: Bot<G>> BotController<'a, G, B> {
+    pub fn new(game: &'a RefCell<G>, bot: B) -> Self {
+        Self {
+            game,
+            bot,
+            current: InputSnapshot::default(),
+        }
+    }
+
+    fn advance(&mut self) -> InputSnapshot {
+        self.current = self.bot.input(&self.game.borrow());
+        self.current
+    }
+}
+
+impl<G, B: Bot<G>> GameController for BotController<'_, G, B> {
+    async fn read_x(&mut self) -> i8 {
+        self.advance().x
+    }
+
+    async fn read_y(&mut self) -> i8 {
+        self.current.y
+    }
+
+    fn joystick_was_pressed(&self) -> bool {
+        self.current.joystick
+    }
+
+    fn a_was_pressed(&self) -> bool {
+        self.current.a
+    }
+
+    fn b_was_pressed(&self) -> bool {
+        self.current.b
+    }
+
+    fn ab_held(&self) -> bool {
+        self.current.ab_held
+    }
+
+    async fn read_input(&mut self) -> InputSnapshot {
+        self.advance()
+    }
+}
+
+/// Input ending the demo, A and B held down like for the quit gesture of the
+/// driver
+const QUIT: InputSnapshot = InputSnapshot {
+    x: 0,
+    y: 0,
+    joystick: false,
+    a: false,
+    b: false,
+    ab_held: true,
+};
+
+/// Controller for demo play: `bot` plays for `ticks` ticks, or until the
+/// player touches their controller, and then quits the game
+pub struct DemoController<'a, P, C> {
+    player: &'a mut P,
+    bot: C,
+    ticks_left: u32,
+    interrupted: bool,
+    current: InputSnapshot,
+}
+
+impl<'a, P: GameController, C: GameController> DemoController<'a, P, C> {
+    pub fn new(player: &'a mut P, bot: C, ticks: u32) -> Self {
+        Self {
+            player,
+            bot,
+            ticks_left: ticks,
+            interrupted: false,
+            current: InputSnapshot::default(),
+        }
+    }
+
+    /// Whether the player ended the demo
+    pub fn interrupted(&self) -> bool {
+        self.interrupted
+    }
+
+    async fn advance(&mut self) -> InputSnapshot {
+        if self.player.read_input().await != InputSnapshot::default() {
+            self.interrupted = true;
+        }
+        self.current = if self.interrupted || self.ticks_left == 0 {
+            QUIT
+        } else {
+            self.ticks_left -= 1;
+            self.bot.read_input().await
+        };
+        self.current
+    }
+}
+
+impl<P: GameController, C: GameController> GameController for DemoController<'_, P, C> {
+    async fn read_x(&mut self) -> i8 {
+        self.advance().await.x
+    }
+
+    async fn read_y(&mut self) -> i8 {
+        self.current.y
+    }
+
+    fn joystick_was_pressed(&self) -> bool {
+        self.current.joystick
+    }
+
+    fn a_was_pressed(&self) -> bool {
+        self.current.a
+    }
+
+    fn b_was_pressed(&self) -> bool {
+        self.current.b
+    }
+
+    fn ab_held(&self) -> bool {
+        self.current.ab_held
+    }
+
+    async fn read_input(&mut self) -> InputSnapshot {
+        self.advance().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::NoAudio;
+    use crate::common::Prng;
+    use crate::driver::run_game;
+    use crate::games::life::LifeGame;
+    use crate::games::snake::{SnakeBot, SnakeGame};
+    use crate::games::tetris::{TetrisBot, TetrisGame};
+    use crate::settings::Settings;
+    use crate::testing::{block_on, joystick, RecordingDisplay, ScriptedController, VirtualTimer};
+
+    #[test]
+    fn demos_end_on_time_or_input() {
+        let timer = VirtualTimer::with_limit(60 * 60 * 1000);
+        let mut display = RecordingDisplay::<8, 16>::new();
+
+        let game = RefCell::new(LifeGame::<8, 16>::new(Prng::new(1)));
+        let mut player = ScriptedController::new([]);
+        let bot = BotController::new(&game, IdleBot);
+        let mut demo = DemoController::new(&mut player, bot, 100);
+        let outcome = block_on(run_game(
+            &mut SharedGame(&game),
+            &mut display,
+            &mut NoAudio,
+            &mut demo,
+            &timer,
+        ));
+        assert_eq!(outcome.reason, EndReason::Quit);
+        assert_eq!(
+            outcome.duration_millis,
+            100 * LifeGame::<8, 16>::TICK_MILLIS
+        );
+        assert!(!demo.interrupted());
+
+        let game = RefCell::new(LifeGame::<8, 16>::new(Prng::new(1)));
+        let mut player =
+            ScriptedController::new([InputSnapshot::default(); 10]).then_repeat(joystick(1, 0));
+        let bot = BotController::new(&game, IdleBot);
+        let mut demo = DemoController::new(&mut player, bot, 100);
+        let outcome = block_on(run_game(
+            &mut SharedGame(&game),
+            &mut display,
+            &mut NoAudio,
+            &mut demo,
+            &timer,
+        ));
+        assert_eq!(outcome.duration_millis, 10 * LifeGame::<8, 16>::TICK_MILLIS);
+        assert!(demo.interrupted());
+    }
+
+    /// Plays `game` with `bot` for `ticks` ticks, returns the score
+    fn autoplay<G: Game<8, 16>, B: Bot<G>>(game: G, bot: B, ticks: u32) -> u32 {
+        let timer = VirtualTimer::new();
+        let game = RefCell::new(game);
+        let mut player = ScriptedController::new([]);
+        let mut demo = DemoController::new(&mut player, BotController::new(&game, bot), ticks);
+        let outcome = block_on(run_game(
+            &mut SharedGame(&game),
+            &mut RecordingDisplay::<8, 16>::new(),
+            &mut NoAudio,
+            &mut demo,
+            &timer,
+        ));
+        outcome.score
+    }
+
+    #[test]
+    fn bots_score() {
+        let settings = Settings::default();
+        let points = autoplay(
+            TetrisGame::new(Prng::new(3), &settings),
+            TetrisBot::default(),
+            5000,
+        );
+        assert!(points >= 5, "the tetris bot scored {points} points");
+        let apples = autoplay(SnakeGame::new(Prng::new(3), &settings), SnakeBot, 5000);
+        assert!(apples >= 5, "the snake bot ate {apples} apples");
+    }
+}
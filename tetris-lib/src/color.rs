@@ -0,0 +1,337 @@
+// Color pipeline
+//
+// Games paint with the palette in `common`, whose colors are meant as they
+// should look on screen. WS2812 LEDs are driven by PWM, so their light is
+// linear in the value sent to them while our eyes aren't: dim values look much
+// brighter than expected. Before a frame goes out to the strip the pipeline
+//
+//   1. scales every color by the user brightness,
+//   2. maps it through a gamma curve to the PWM duty the LED needs and
+//   3. dims the whole frame if it would draw more current than the power
+//      budget allows.
+//
+// Emulators run the same pipeline and turn the LED values back into screen
+// colors with `preview`, so they show what the hardware shows.
+
+use core::ops::Range;
+
+use smart_leds::RGB8;
+
+use crate::common::{LedBuffer, LedDisplay};
+use crate::layout::LedLayout;
+use crate::theme::{Theme, CLASSIC};
+
+/// Brightness the pipeline starts with
+pub const DEFAULT_BRIGHTNESS: u8 = 128;
+/// Current a USB port can supply, the default power budget
+pub const DEFAULT_POWER_LIMIT_MILLIAMPS: u32 = 500;
+/// Current drawn by a single channel of a WS2812 at full duty
+const MILLIAMPS_PER_CHANNEL: u32 = 20;
+
+/// Integer square root
+const fn isqrt(n: u64) -> u64 {
+    if n < 2 {
+        return n;
+    }
+    let mut x = n;
+    let mut y = x.div_ceil(2);
+    while y < x {
+        x = y;
+        y = (x + n / x) / 2;
+    }
+    x
+}
+
+/// Gamma 2.5 curve: `255 * (i / 255)^2.5`, computed as
+/// `sqrt(i^5 / 255^3)` in 8.8 fixed point
+const fn gamma_table() -> [u8; 256] {
+    let mut table = [0; 256];
+    let mut i = 0;
+    while i < 256 {
+        let fixed = isqrt((i as u64).pow(5) * 65536 / 255u64.pow(3));
+        table[i] = ((fixed + 128) / 256) as u8;
+        i += 1;
+    }
+    table
+}
+
+/// Inverse of the gamma curve: the smallest input giving at least `v`
+const fn inverse_gamma_table(gamma: &[u8; 256]) -> [u8; 256] {
+    let mut table = [0; 256];
+    let mut v = 0;
+    let mut i = 0;
+    while v < 256 {
+        while (gamma[i] as usize) < v {
+            i += 1;
+        }
+        table[v] = i as u8;
+        v += 1;
+    }
+    table
+}
+
+const GAMMA: [u8; 256] = gamma_table();
+const INVERSE_GAMMA: [u8; 256] = inverse_gamma_table(&GAMMA);
+
+/// `color` at `level` / 255 of its brightness, for fades and trails
+pub fn fade(color: RGB8, level: u8) -> RGB8 {
+    mix(RGB8::default(), color, level)
+}
+
+/// Blend from `from` (level 0) to `to` (level 255), for gradients
+pub fn mix(from: RGB8, to: RGB8, level: u8) -> RGB8 {
+    let channel = |a: u8, b: u8| {
+        let (a, b, level) = (a as u16, b as u16, level as u16);
+        ((a * (255 - level) + b * level + 127) / 255) as u8
+    };
+    RGB8::new(
+        channel(from.r, to.r),
+        channel(from.g, to.g),
+        channel(from.b, to.b),
+    )
+}
+
+/// Screen color showing what an LED driven with `led` looks like
+pub fn preview(led: RGB8) -> RGB8 {
+    RGB8::new(
+        INVERSE_GAMMA[led.r as usize],
+        INVERSE_GAMMA[led.g as usize],
+        INVERSE_GAMMA[led.b as usize],
+    )
+}
+
+/// Current the strip draws showing `leds` (not counting the LEDs' own idle
+/// current)
+pub fn milliamps(leds: &[RGB8]) -> u32 {
+    let duty: u32 = leds
+        .iter()
+        .map(|led| led.r as u32 + led.g as u32 + led.b as u32)
+        .sum();
+    duty * MILLIAMPS_PER_CHANNEL / 255
+}
+
+/// Theme, brightness, gamma correction and power limiting for the LED strip
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct ColorPipeline {
+    theme: &'static Theme,
+    brightness: u8,
+    max_milliamps: Option<u32>,
+}
+
+impl ColorPipeline {
+    pub const fn new() -> Self {
+        Self {
+            theme: &CLASSIC,
+            brightness: DEFAULT_BRIGHTNESS,
+            max_milliamps: Some(DEFAULT_POWER_LIMIT_MILLIAMPS),
+        }
+    }
+
+    /// Renders frames in `theme`
+    pub const fn with_theme(mut self, theme: &'static Theme) -> Self {
+        self.theme = theme;
+        self
+    }
+
+    pub const fn with_brightness(mut self, brightness: u8) -> Self {
+        self.brightness = brightness;
+        self
+    }
+
+    /// Dims frames that would draw more than `max_milliamps`, `None` for no
+    /// limit
+    pub const fn with_power_limit(mut self, max_milliamps: Option<u32>) -> Self {
+        self.max_milliamps = max_milliamps;
+        self
+    }
+
+    pub fn brightness(&self) -> u8 {
+        self.brightness
+    }
+
+    pub fn set_brightness(&mut self, brightness: u8) {
+        self.brightness = brightness;
+    }
+
+    pub fn theme(&self) -> &'static Theme {
+        self.theme
+    }
+
+    pub fn set_theme(&mut self, theme: &'static Theme) {
+        self.theme = theme;
+    }
+
+    /// LED value for a palette color, at the current brightness
+    pub fn correct(&self, color: RGB8) -> RGB8 {
+        let channel = |value: u8| {
+            let scaled = (value as u16 * self.brightness as u16).div_ceil(255);
+            GAMMA[scaled as usize]
+        };
+        RGB8::new(channel(color.r), channel(color.g), channel(color.b))
+    }
+
+    /// Turns a frame of palette colors into LED values, returns whether the
+    /// frame had to be dimmed to stay within the power budget
+    pub fn apply(&self, leds: &mut [RGB8]) -> bool {
+        for led in leds.iter_mut() {
+            *led = self.correct(*led);
+        }
+
+        let Some(max_milliamps) = self.max_milliamps else {
+            return false;
+        };
+        let milliamps = milliamps(leds);
+        if milliamps <= max_milliamps {
+            return false;
+        }
+        // The current is linear in the duty, so scaling all LEDs by the same
+        // factor gets the frame within the budget
+        let scale = |value: u8| (value as u64 * max_milliamps as u64 / milliamps as u64) as u8;
+        for led in leds.iter_mut() {
+            *led = RGB8::new(scale(led.r), scale(led.g), scale(led.b));
+        }
+        true
+    }
+}
+
+impl Default for ColorPipeline {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Display running every frame through a `ColorPipeline` before passing it on
+/// to the `output` display
+pub struct CorrectedDisplay<D, const W: usize, const H: usize> {
+    output: D,
+    pipeline: ColorPipeline,
+    leds: LedBuffer<W, H>,
+    /// Every LED has to be rewritten: the pipeline changed or the last frame
+    /// was dimmed for the power budget
+    stale: bool,
+}
+
+impl<D, const W: usize, const H: usize> CorrectedDisplay<D, W, H>
+where
+    D: LedDisplay<W, H>,
+{
+    pub fn new(output: D, pipeline: ColorPipeline) -> Self {
+        Self {
+            output,
+            pipeline,
+            leds: [[RGB8::default(); W]; H],
+            stale: true,
+        }
+    }
+
+    pub fn output(&mut self) -> &mut D {
+        &mut self.output
+    }
+
+    pub fn pipeline(&mut self) -> &mut ColorPipeline {
+        self.stale = true;
+        &mut self.pipeline
+    }
+}
+
+impl<D, const W: usize, const H: usize> LedDisplay<W, H> for CorrectedDisplay<D, W, H>
+where
+    D: LedDisplay<W, H>,
+{
+    async fn write(&mut self, leds: &LedBuffer<W, H>) {
+        self.leds = *leds;
+        self.stale = self.pipeline.apply(self.leds.as_flattened_mut());
+        self.output.write(&self.leds).await;
+    }
+
+    async fn write_dirty(&mut self, leds: &LedBuffer<W, H>, dirty: Range<usize>) {
+        self.leds = *leds;
+        let dimmed = self.pipeline.apply(self.leds.as_flattened_mut());
+        // Dimming changes the LEDs outside of the dirty range too
+        if dimmed || self.stale {
+            self.output.write(&self.leds).await;
+        } else {
+            self.output.write_dirty(&self.leds, dirty).await;
+        }
+        self.stale = dimmed;
+    }
+
+    fn layout(&self) -> LedLayout {
+        self.output.layout()
+    }
+
+    fn brightness(&self) -> Option<u8> {
+        Some(self.pipeline.brightness())
+    }
+
+    fn set_brightness(&mut self, brightness: u8) {
+        if brightness != self.pipeline.brightness() {
+            self.pipeline().set_brightness(brightness);
+        }
+    }
+
+    fn theme(&self) -> &'static Theme {
+        self.pipeline.theme()
+    }
+
+    fn set_theme(&mut self, theme: &'static Theme) {
+        self.pipeline.set_theme(theme);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::{BRICK, RED};
+
+    #[test]
+    fn gamma_curve() {
+        assert_eq!(GAMMA[0], 0);
+        assert_eq!(GAMMA[255], 255);
+        assert!(GAMMA.windows(2).all(|pair| pair[0] <= pair[1]));
+        // Half the perceived brightness takes less than a fifth of the duty
+        assert_eq!(GAMMA[128], 46);
+        // Previews show the color the LED value was made from
+        let full = ColorPipeline::new().with_brightness(255);
+        for value in 0..=255u8 {
+            let led = full.correct(RGB8::new(value, value, value));
+            assert_eq!(full.correct(preview(led)), led);
+        }
+    }
+
+    #[test]
+    fn brightness_scales_colors() {
+        let full = ColorPipeline::new().with_brightness(255);
+        let half = ColorPipeline::new().with_brightness(128);
+        assert!(half.correct(RED).r < full.correct(RED).r);
+        assert_eq!(
+            ColorPipeline::new().with_brightness(0).correct(RED),
+            RGB8::default()
+        );
+    }
+
+    #[test]
+    fn fades_and_gradients() {
+        assert_eq!(fade(RED, 255), RED);
+        assert_eq!(fade(RED, 0), RGB8::default());
+        assert_eq!(fade(RED, 128), RGB8::new(60, 0, 0));
+        assert_eq!(mix(RED, BRICK, 255), BRICK);
+        assert_eq!(mix(RED, BRICK, 0), RED);
+    }
+
+    #[test]
+    fn frames_stay_within_the_power_budget() {
+        let pipeline = ColorPipeline::new()
+            .with_brightness(255)
+            .with_power_limit(Some(300));
+        let mut leds = [BRICK; 256];
+        pipeline.apply(&mut leds);
+        assert!(milliamps(&leds) <= 300);
+        assert!(milliamps(&leds) > 250);
+
+        // Small frames are left alone
+        let mut leds = [BRICK; 4];
+        pipeline.apply(&mut leds);
+        assert_eq!(leds[0], pipeline.correct(BRICK));
+    }
+}
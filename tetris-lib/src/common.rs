@@ -1,65 +1,43 @@
+use core::ops::Range;
+
+use crate::audio::Sfx;
+use crate::color::fade;
 use crate::figure::Figure;
+use crate::font;
+use crate::layout::LedLayout;
+use crate::theme::{self, Theme};
 use smart_leds::RGB8;
 
+// Default panel geometry (a single 8x32 WS2812 matrix)
 pub const SCREEN_WIDTH: usize = 8;
 pub const SCREEN_HEIGHT: usize = 32;
 pub const SCREEN_SIZE: usize = SCREEN_WIDTH * SCREEN_HEIGHT;
 
-// Colors matching the Python version
-pub const BLACK: RGB8 = RGB8::new(0, 0, 0);
-pub const BRICK: RGB8 = RGB8::new(12, 2, 0);
-pub const RED: RGB8 = RGB8::new(6, 0, 0);
-pub const GREEN: RGB8 = RGB8::new(0, 6, 0);
-pub const BLUE: RGB8 = RGB8::new(0, 0, 6);
-pub const LIGHT_BLUE: RGB8 = RGB8::new(0, 6, 6);
-pub const PINK: RGB8 = RGB8::new(3, 0, 3);
-pub const YELLOW: RGB8 = RGB8::new(6, 6, 0);
-pub const DARK_GREEN: RGB8 = RGB8::new(0, 3, 0);
-pub const LIGHT_GREEN: RGB8 = RGB8::new(0, 9, 0);
-
-// Color indices
-pub const BLACK_IDX: u8 = 0;
-pub const BRICK_IDX: u8 = 1;
-pub const RED_IDX: u8 = 2;
-pub const GREEN_IDX: u8 = 3;
-pub const BLUE_IDX: u8 = 4;
-pub const LIGHT_BLUE_IDX: u8 = 5;
-pub const PINK_IDX: u8 = 6;
-pub const YELLOW_IDX: u8 = 7;
-pub const DARK_GREEN_IDX: u8 = 8;
-pub const LIGHT_GREEN_IDX: u8 = 9;
-
-pub type ColorsType = [RGB8; 10];
-pub const COLORS: ColorsType = [
-    BLACK,
-    BRICK,
-    RED,
-    GREEN,
-    BLUE,
-    LIGHT_BLUE,
-    PINK,
-    YELLOW,
-    DARK_GREEN,
-    LIGHT_GREEN,
-];
-
-trait ColorsIndexer {
-    fn at(&self, idx: u8) -> RGB8;
-}
+/// LED strip contents for a `W`x`H` panel, in wiring order, split into rows of `W` LEDs
+pub type LedBuffer<const W: usize, const H: usize> = [[RGB8; W]; H];
 
-impl ColorsIndexer for ColorsType {
-    fn at(&self, idx: u8) -> RGB8 {
-        let mut idx = idx as usize;
-        while idx >= self.len() {
-            idx -= self.len();
-        }
-        self[idx]
-    }
-}
+// Base colors of the classic theme, as they should look at full brightness.
+// The color pipeline (see `color`) turns them into LED values.
+pub const BLACK: RGB8 = RGB8::new(0, 0, 0);
+pub const BRICK: RGB8 = RGB8::new(240, 40, 0);
+pub const RED: RGB8 = RGB8::new(120, 0, 0);
+pub const GREEN: RGB8 = RGB8::new(0, 120, 0);
+pub const BLUE: RGB8 = RGB8::new(0, 0, 120);
+pub const LIGHT_BLUE: RGB8 = RGB8::new(0, 120, 120);
+pub const PINK: RGB8 = RGB8::new(60, 0, 60);
+pub const YELLOW: RGB8 = RGB8::new(120, 120, 0);
+pub const DARK_GREEN: RGB8 = RGB8::new(0, 60, 0);
+pub const LIGHT_GREEN: RGB8 = RGB8::new(0, 180, 0);
+
+/// Role of empty pixels, see `theme`
+pub const BLACK_IDX: u8 = theme::BACKGROUND;
 
 // Timer abstraction trait
 pub trait Timer {
     async fn sleep_millis(&self, millis: u64);
+
+    /// Milliseconds on a monotonic clock, counted from an arbitrary start
+    fn now_millis(&self) -> u64;
 }
 
 // Simple PRNG implementation
@@ -101,18 +79,18 @@ impl Dot {
         Dot::new(self.x + direction.x, self.y + direction.y)
     }
 
-    pub fn move_wrap(&self, direction: Dot) -> Dot {
+    pub fn move_wrap<const W: usize, const H: usize>(&self, direction: Dot) -> Dot {
         let mut new_dot = self.move_by(direction);
 
         if new_dot.x == -1 {
-            new_dot.x = SCREEN_WIDTH as i8 - 1;
-        } else if new_dot.x == SCREEN_WIDTH as i8 {
+            new_dot.x = W as i8 - 1;
+        } else if new_dot.x == W as i8 {
             new_dot.x = 0;
         }
 
         if new_dot.y == -1 {
-            new_dot.y = SCREEN_HEIGHT as i8 - 1;
-        } else if new_dot.y == SCREEN_HEIGHT as i8 {
+            new_dot.y = H as i8 - 1;
+        } else if new_dot.y == H as i8 {
             new_dot.y = 0;
         }
 
@@ -131,8 +109,8 @@ impl Dot {
         Dot::new(-self.x, -self.y)
     }
 
-    pub fn _outside(&self) -> bool {
-        self.x < 0 || self.x >= SCREEN_WIDTH as i8 || self.y < 0 || self.y >= SCREEN_HEIGHT as i8
+    pub fn _outside<const W: usize, const H: usize>(&self) -> bool {
+        self.x < 0 || self.x >= W as i8 || self.y < 0 || self.y >= H as i8
     }
 
     pub fn to_direction(mut self) -> Dot {
@@ -143,70 +121,139 @@ impl Dot {
     }
 }
 
-pub fn get_pixel(leds: &[RGB8], x: usize, y: usize) -> u8 {
-    let mut x = x;
-    if y % 2 == 0 {
-        x = 7 - x;
+/// Color of the LED showing pixel (`x`, `y`), black outside the panel
+pub fn get_pixel<const W: usize, const H: usize>(
+    leds: &LedBuffer<W, H>,
+    layout: &LedLayout,
+    x: usize,
+    y: usize,
+) -> RGB8 {
+    if x >= W || y >= H {
+        return BLACK;
     }
-    let idx = SCREEN_WIDTH * y + x;
-    for (i, color) in COLORS.iter().enumerate() {
-        if color == &leds[idx] {
-            return i as u8;
-        }
+    leds.as_flattened()[layout.index(x, y, W, H)]
+}
+
+/// Sets the LED showing pixel (`x`, `y`) to `color`
+pub fn set_pixel<const W: usize, const H: usize>(
+    leds: &mut LedBuffer<W, H>,
+    layout: &LedLayout,
+    x: usize,
+    y: usize,
+    color: RGB8,
+) {
+    if x < W && y < H {
+        leds.as_flattened_mut()[layout.index(x, y, W, H)] = color;
     }
-    0
 }
 
-pub fn set_pixel(leds: &mut [RGB8], x: usize, y: usize, color_idx: u8) {
-    let mut x = x;
-    if y % 2 == 0 {
-        x = 7 - x;
+/// Pixel of a `FrameBuffer`: a theme role (see `theme`), a role dimmed to
+/// a level out of 255 or any RGB color
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Pixel {
+    Palette(u8),
+    Shade(u8, u8),
+    Rgb(RGB8),
+}
+
+impl Pixel {
+    /// Pixel showing `color`, the first role of `theme` with that color if
+    /// there is one
+    pub fn from_color(color: RGB8, theme: &Theme) -> Self {
+        match theme.role(color) {
+            Some(role) => Pixel::Palette(role),
+            None => Pixel::Rgb(color),
+        }
+    }
+
+    /// Color of the pixel in `theme`
+    pub fn color(&self, theme: &Theme) -> RGB8 {
+        match *self {
+            Pixel::Palette(role) => theme.color(role),
+            Pixel::Shade(role, level) => fade(theme.color(role), level),
+            Pixel::Rgb(color) => color,
+        }
+    }
+
+    /// Role of the pixel, shaded and RGB pixels read as black
+    pub fn index(&self) -> u8 {
+        match *self {
+            Pixel::Palette(idx) => idx,
+            Pixel::Shade(..) | Pixel::Rgb(_) => BLACK_IDX,
+        }
     }
-    let idx = SCREEN_WIDTH * y + x;
-    if idx < leds.len() {
-        leds[idx] = COLORS.at(color_idx);
+}
+
+impl Default for Pixel {
+    fn default() -> Self {
+        Pixel::Palette(BLACK_IDX)
     }
 }
 
-pub struct FrameBuffer {
-    content: [u8; SCREEN_SIZE],
+/// Screen contents, mostly theme roles.
+///
+/// Pixels can also be painted with a dimmed role (`set_shade`) or any RGB
+/// color (`set_rgb`) for fades, trails and gradients. Those are decoration
+/// only: `get` and the collision checks see them as black.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct FrameBuffer<const W: usize = SCREEN_WIDTH, const H: usize = SCREEN_HEIGHT> {
+    content: [[Pixel; W]; H],
 }
 
-impl FrameBuffer {
+impl<const W: usize, const H: usize> FrameBuffer<W, H> {
     pub fn new() -> Self {
         Self {
-            content: [0; SCREEN_SIZE],
+            content: [[Pixel::default(); W]; H],
         }
     }
 
     pub fn clear(&mut self) {
-        self.content.fill(0);
+        self.content.as_flattened_mut().fill(Pixel::default());
     }
 
     pub fn clear_range(&mut self, from: usize, to: usize) {
-        for idx in from..to.min(SCREEN_SIZE) {
-            self.content[idx] = 0;
+        let content = self.content.as_flattened_mut();
+        let to = to.min(content.len());
+        if from < to {
+            content[from..to].fill(Pixel::default());
         }
     }
 
     pub fn set(&mut self, x: usize, y: usize, color: u8) {
-        if x < SCREEN_WIDTH && y < SCREEN_HEIGHT {
-            let idx = y * SCREEN_WIDTH + x;
-            self.content[idx] = color;
+        self.set_pixel(x, y, Pixel::Palette(color));
+    }
+
+    /// Paints (`x`, `y`) with `role` at `level` / 255 of its brightness
+    pub fn set_shade(&mut self, x: usize, y: usize, role: u8, level: u8) {
+        self.set_pixel(x, y, Pixel::Shade(role, level));
+    }
+
+    pub fn set_rgb(&mut self, x: usize, y: usize, color: RGB8) {
+        self.set_pixel(x, y, Pixel::Rgb(color));
+    }
+
+    pub fn set_pixel(&mut self, x: usize, y: usize, pixel: Pixel) {
+        if x < W && y < H {
+            self.content[y][x] = pixel;
         }
     }
 
+    /// Role at (`x`, `y`), see `Pixel::index`
     pub fn get(&self, x: usize, y: usize) -> u8 {
-        if x < SCREEN_WIDTH && y < SCREEN_HEIGHT {
-            self.content[y * SCREEN_WIDTH + x]
+        self.pixel(x, y).index()
+    }
+
+    pub fn pixel(&self, x: usize, y: usize) -> Pixel {
+        if x < W && y < H {
+            self.content[y][x]
         } else {
-            0
+            Pixel::default()
         }
     }
 
     fn available(&self, x: i8, y: i8, color: u8) -> bool {
-        if x >= 0 && x < SCREEN_WIDTH as i8 && y >= 0 && y < SCREEN_HEIGHT as i8 {
-            self.content[y as usize * SCREEN_WIDTH + x as usize] == color
+        if x >= 0 && (x as usize) < W && y >= 0 && (y as usize) < H {
+            self.get(x as usize, y as usize) == color
         } else {
             false
         }
@@ -226,48 +273,90 @@ impl FrameBuffer {
     }
 
     pub fn draw_figure(&mut self, x: i8, y: i8, figure: &Figure, color: u8) {
+        self.draw_figure_pixel(x, y, figure, Pixel::Palette(color));
+    }
+
+    /// Draws `figure` with `pixel`, e.g. in an RGB color no role has
+    pub fn draw_figure_pixel(&mut self, x: i8, y: i8, figure: &Figure, pixel: Pixel) {
         for row in 0..figure.height() {
             for col in 0..figure.width() {
                 if figure.get_bit(col, row) {
                     let px = x + col as i8;
                     let py = y + row as i8;
-                    if px >= 0 && px < SCREEN_WIDTH as i8 && py >= 0 && py < SCREEN_HEIGHT as i8 {
-                        self.set(px as usize, py as usize, color);
+                    if px >= 0 && py >= 0 {
+                        self.set_pixel(px as usize, py as usize, pixel);
                     }
                 }
             }
         }
     }
 
-    pub fn copy_from(&mut self, other: &FrameBuffer) {
-        self.content.copy_from_slice(&other.content);
+    /// Draws the glyph of `c` with its top left corner at (`x`, `y`), clipped
+    /// to the screen
+    pub fn draw_glyph(&mut self, x: i16, y: i16, c: char, color: u8) {
+        let visible =
+            |pos: i16, glyph: usize, size: usize| pos > -(glyph as i16) && pos < size as i16;
+        if visible(x, font::GLYPH_WIDTH, W) && visible(y, font::GLYPH_HEIGHT, H) {
+            self.draw_figure(x as i8, y as i8, &font::glyph(c), color);
+        }
     }
 
-    pub fn render(&self, leds: &mut [RGB8]) {
-        for (idx, &color_idx) in self.content.iter().enumerate() {
-            let x = idx % SCREEN_WIDTH;
-            let y = idx / SCREEN_WIDTH;
-            set_pixel(leds, x, y, color_idx);
+    /// Draws `text` on a single line starting at (`x`, `y`), clipped to the
+    /// screen, returns the width of the text
+    pub fn draw_text(&mut self, x: i16, y: i16, text: &str, color: u8) -> usize {
+        for (i, c) in text.chars().enumerate() {
+            self.draw_glyph(x + (i * font::ADVANCE) as i16, y, c, color);
         }
+        font::text_width(text)
     }
 
-    pub fn row_is_full(&self, row: usize) -> bool {
-        if row >= SCREEN_HEIGHT {
-            return false;
+    pub fn copy_from(&mut self, other: &FrameBuffer<W, H>) {
+        self.content = other.content;
+    }
+
+    /// Colors of the frame in `theme`, in the LED order of `layout`
+    pub fn render(&self, leds: &mut LedBuffer<W, H>, layout: &LedLayout, theme: &Theme) {
+        for (y, row) in self.content.iter().enumerate() {
+            for (x, pixel) in row.iter().enumerate() {
+                set_pixel(leds, layout, x, y, pixel.color(theme));
+            }
         }
-        for x in 0..SCREEN_WIDTH {
-            if self.content[row * SCREEN_WIDTH + x] == 0 {
-                return false;
+    }
+
+    /// Range of LEDs (in the strip order of `layout`) covering every pixel
+    /// that differs from `previous`, `None` if the frames are identical
+    pub fn dirty_leds(
+        &self,
+        previous: &FrameBuffer<W, H>,
+        layout: &LedLayout,
+    ) -> Option<Range<usize>> {
+        let mut dirty: Option<Range<usize>> = None;
+        for y in 0..H {
+            for x in 0..W {
+                if self.content[y][x] != previous.content[y][x] {
+                    let idx = layout.index(x, y, W, H);
+                    dirty = Some(match dirty {
+                        Some(range) => range.start.min(idx)..range.end.max(idx + 1),
+                        None => idx..idx + 1,
+                    });
+                }
             }
         }
-        true
+        dirty
+    }
+
+    pub fn row_is_full(&self, row: usize) -> bool {
+        if row >= H {
+            return false;
+        }
+        self.content[row]
+            .iter()
+            .all(|pixel| pixel.index() != BLACK_IDX)
     }
 
     pub fn try_clear_row(&mut self, row: usize) -> bool {
         if self.row_is_full(row) {
-            for x in 0..SCREEN_WIDTH {
-                self.content[row * SCREEN_WIDTH + x] = 0;
-            }
+            self.content[row].fill(Pixel::default());
             true
         } else {
             false
@@ -275,24 +364,28 @@ impl FrameBuffer {
     }
 
     pub fn row_is_empty(&self, row: usize) -> bool {
-        for x in 0..SCREEN_WIDTH {
-            if self.content[row * SCREEN_WIDTH + x] != 0 {
-                return false;
-            }
-        }
-        true
+        self.content[row]
+            .iter()
+            .all(|pixel| pixel.index() == BLACK_IDX)
     }
 
-    pub fn from_rows(rows: &[u32; 8], color: u8) -> FrameBuffer {
-        let mut buffer = FrameBuffer::new();
+    /// Builds a screen from an 8x32 title bitmap, centered on the panel (and
+    /// clipped if the panel is smaller than the bitmap)
+    pub fn from_rows(rows: &[u32; 8], color: u8) -> Self {
+        const ROWS_WIDTH: isize = 8;
+        const ROWS_HEIGHT: isize = 32;
+
+        let mut buffer = Self::new();
+        let offset_x = (W as isize - ROWS_WIDTH) / 2;
+        let offset_y = (H as isize - ROWS_HEIGHT) / 2;
 
-        for y in 0..SCREEN_HEIGHT {
+        for y in 0..ROWS_HEIGHT {
             for (x, row) in rows.iter().enumerate() {
-                if x < SCREEN_WIDTH {
-                    let bit = row >> (SCREEN_HEIGHT - y - 1) & 1;
-                    if bit == 1 {
-                        buffer.set(SCREEN_WIDTH - x - 1, y, color);
-                    }
+                let bit = row >> (ROWS_HEIGHT - y - 1) & 1;
+                let px = offset_x + ROWS_WIDTH - x as isize - 1;
+                let py = offset_y + y;
+                if bit == 1 && px >= 0 && py >= 0 {
+                    buffer.set(px as usize, py as usize, color);
                 }
             }
         }
@@ -301,15 +394,49 @@ impl FrameBuffer {
     }
 }
 
-impl Default for FrameBuffer {
+impl<const W: usize, const H: usize> Default for FrameBuffer<W, H> {
     fn default() -> Self {
         Self::new()
     }
 }
 
-/// Trait for LED display functionality
-pub trait LedDisplay {
-    async fn write(&mut self, leds: &[smart_leds::RGB8; 256]);
+/// Trait for LED display functionality of a `W`x`H` panel
+pub trait LedDisplay<const W: usize, const H: usize> {
+    async fn write(&mut self, leds: &LedBuffer<W, H>);
+
+    /// Shows `leds` knowing that only the LEDs in `dirty` (in strip order)
+    /// changed since the previous write. Displays that can update part of
+    /// the panel override it, the others write the whole frame.
+    async fn write_dirty(&mut self, leds: &LedBuffer<W, H>, dirty: Range<usize>) {
+        let _ = dirty;
+        self.write(leds).await;
+    }
+
+    /// How the LED strip is wired through the panel
+    fn layout(&self) -> LedLayout {
+        LedLayout::SERPENTINE
+    }
+
+    /// Brightness of the LEDs, `None` if the display has no brightness control
+    fn brightness(&self) -> Option<u8> {
+        None
+    }
+
+    /// Changes the brightness of the LEDs, ignored without a brightness
+    /// control
+    fn set_brightness(&mut self, brightness: u8) {
+        let _ = brightness;
+    }
+
+    /// Theme frames are rendered with before they are written
+    fn theme(&self) -> &'static Theme {
+        &theme::CLASSIC
+    }
+
+    /// Changes the theme, ignored by displays that always use the classic one
+    fn set_theme(&mut self, theme: &'static Theme) {
+        let _ = theme;
+    }
 }
 
 /// Trait for game controller functionality (joystick + button)
@@ -319,9 +446,197 @@ pub trait GameController {
     fn joystick_was_pressed(&self) -> bool;
     fn a_was_pressed(&self) -> bool;
     fn b_was_pressed(&self) -> bool;
+
+    /// Whether buttons A and B are both held down right now, the quit gesture
+    /// of the driver. Controllers that can't tell never report it.
+    fn ab_held(&self) -> bool {
+        false
+    }
+
+    /// Samples the whole controller once, the game driver calls it every tick
+    async fn read_input(&mut self) -> InputSnapshot {
+        InputSnapshot {
+            x: self.read_x().await,
+            y: self.read_y().await,
+            joystick: self.joystick_was_pressed(),
+            a: self.a_was_pressed(),
+            b: self.b_was_pressed(),
+            ab_held: self.ab_held(),
+        }
+    }
 }
 
-/// Game trait for different game implementations - using generics to avoid dyn issues
-pub trait Game {
-    async fn run(&mut self);
+/// Controller state for a single game tick
+#[derive(Default, Copy, Clone, PartialEq, Eq, Debug)]
+pub struct InputSnapshot {
+    /// Joystick deflection along x: -1, 0 or 1
+    pub x: i8,
+    /// Joystick deflection along y: -1 (up), 0 or 1 (down)
+    pub y: i8,
+    /// Joystick button was pressed since the previous tick
+    pub joystick: bool,
+    /// Button A was pressed since the previous tick
+    pub a: bool,
+    /// Button B was pressed since the previous tick
+    pub b: bool,
+    /// Buttons A and B are both held down
+    pub ab_held: bool,
+}
+
+/// Why a game ended
+#[derive(Default, Copy, Clone, PartialEq, Eq, Debug)]
+pub enum EndReason {
+    /// The player lost and dismissed the game over screen
+    #[default]
+    GameOver,
+    /// The player left the game with the quit gesture
+    Quit,
+    /// The player reached the goal of the game, like the end of a Tetris
+    /// sprint
+    Completed,
+}
+
+/// Result of a finished game
+#[derive(Default, Copy, Clone, PartialEq, Eq, Debug)]
+pub struct GameOutcome {
+    pub reason: EndReason,
+    pub score: u32,
+    /// Game specific tally: lines cleared, enemies destroyed, apples eaten or
+    /// generations simulated
+    pub count: u32,
+    /// Time played, filled in by the driver
+    pub duration_millis: u64,
+    /// The result is the time played, the shorter the better, rather than
+    /// the score
+    pub timed: bool,
+}
+
+/// Game trait for different game implementations - using generics to avoid dyn issues.
+///
+/// A game is a synchronous state machine: `update` advances it by one tick
+/// and `draw` paints the current state. Timing, input polling and output are
+/// left to the driver (see `driver::run_game`).
+pub trait Game<const W: usize, const H: usize> {
+    /// Smallest panel width the game can be played on
+    const MIN_WIDTH: usize;
+    /// Smallest panel height the game can be played on
+    const MIN_HEIGHT: usize;
+    /// Duration of a single tick
+    const TICK_MILLIS: u64;
+
+    /// Advances the game by one tick, returns the outcome once the game is over
+    fn update(&mut self, input: InputSnapshot) -> Option<GameOutcome>;
+
+    /// Result of the game so far, were it to end now for `reason`
+    fn outcome(&self, reason: EndReason) -> GameOutcome;
+
+    /// Draws the current state onto a cleared screen
+    fn draw(&self, screen: &mut FrameBuffer<W, H>);
+
+    /// Sound effect raised by the last `update`, if any
+    fn take_sound(&mut self) -> Option<Sfx> {
+        None
+    }
+
+    /// Whether a `width`x`height` panel is large enough for the game
+    fn fits(width: usize, height: usize) -> bool {
+        width >= Self::MIN_WIDTH && height >= Self::MIN_HEIGHT
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::theme::{CLASSIC, ENEMY, HAZARD, HIGH_CONTRAST, PLAYER};
+
+    #[test]
+    fn render_uses_panel_width() {
+        let mut screen = FrameBuffer::<16, 16>::new();
+        screen.set(0, 0, HAZARD);
+        screen.set(0, 1, PLAYER);
+        screen.set(15, 15, ENEMY);
+        // Out of bounds writes are ignored
+        screen.set(16, 0, ENEMY);
+
+        let layout = LedLayout::SERPENTINE;
+        let mut leds: LedBuffer<16, 16> = [[BLACK; 16]; 16];
+        screen.render(&mut leds, &layout, &CLASSIC);
+
+        // Even rows are mirrored
+        assert_eq!(leds[0][15], RED);
+        assert_eq!(leds[1][0], GREEN);
+        assert_eq!(leds[15][15], BLUE);
+        assert_eq!(get_pixel(&leds, &layout, 0, 0), RED);
+        assert_eq!(get_pixel(&leds, &layout, 15, 15), BLUE);
+    }
+
+    #[test]
+    fn rgb_pixels_are_kept() {
+        let dim = RGB8::new(20, 0, 0);
+        let mut screen = FrameBuffer::<8, 8>::new();
+        screen.set_rgb(1, 1, dim);
+        screen.set(2, 1, HAZARD);
+        // RGB pixels don't take part in the game logic
+        assert_eq!(screen.get(1, 1), BLACK_IDX);
+        assert!(!screen.collides(
+            1,
+            1,
+            &Figure {
+                data: 1,
+                wh: 0x11,
+                rotation: 0,
+            }
+        ));
+
+        let layout = LedLayout::SERPENTINE;
+        let mut leds = [[BLACK; 8]; 8];
+        screen.render(&mut leds, &layout, &CLASSIC);
+        assert_eq!(get_pixel(&leds, &layout, 1, 1), dim);
+        assert_eq!(Pixel::from_color(dim, &CLASSIC), Pixel::Rgb(dim));
+        assert_eq!(Pixel::from_color(RED, &CLASSIC), Pixel::Palette(HAZARD));
+    }
+
+    #[test]
+    fn frames_render_in_the_given_theme() {
+        let mut screen = FrameBuffer::<8, 8>::new();
+        screen.set(0, 0, PLAYER);
+        screen.set_shade(1, 0, PLAYER, 128);
+        // Shades follow the theme, and read as black
+        assert_eq!(screen.get(1, 0), BLACK_IDX);
+
+        let layout = LedLayout::SERPENTINE;
+        for theme in [&CLASSIC, &HIGH_CONTRAST] {
+            let mut leds = [[BLACK; 8]; 8];
+            screen.render(&mut leds, &layout, theme);
+            let player = theme.color(PLAYER);
+            assert_eq!(get_pixel(&leds, &layout, 0, 0), player);
+            assert_eq!(get_pixel(&leds, &layout, 1, 0), fade(player, 128));
+        }
+    }
+
+    #[test]
+    fn dirty_leds_cover_the_changes() {
+        let layout = LedLayout::SERPENTINE;
+        let previous = FrameBuffer::<8, 4>::new();
+        let mut screen = previous;
+        assert_eq!(screen.dirty_leds(&previous, &layout), None);
+
+        // The first row runs from right to left
+        screen.set(6, 0, PLAYER);
+        assert_eq!(screen.dirty_leds(&previous, &layout), Some(1..2));
+        screen.set(2, 1, PLAYER);
+        assert_eq!(screen.dirty_leds(&previous, &layout), Some(1..11));
+    }
+
+    #[test]
+    fn title_is_clipped_to_small_panels() {
+        let rows = [u32::MAX; 8];
+        let screen = FrameBuffer::<32, 8>::from_rows(&rows, PLAYER);
+
+        // The 8 columns of the title are centered horizontally
+        assert_eq!(screen.get(11, 0), BLACK_IDX);
+        assert_eq!(screen.get(12, 0), PLAYER);
+        assert_eq!(screen.get(19, 7), PLAYER);
+        assert_eq!(screen.get(20, 7), BLACK_IDX);
+    }
 }
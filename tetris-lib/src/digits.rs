@@ -19,6 +19,7 @@ const DIGITS_DATA: [Figure; 10] = [
     Figure {
         data: 0b111_101_101_101_111,
         wh: 3 << 4 | 5,
+        rotation: 0,
     },
     // 1:  #
     //    ##
@@ -28,6 +29,7 @@ const DIGITS_DATA: [Figure; 10] = [
     Figure {
         data: 0b010_110_010_010_010,
         wh: 3 << 4 | 5,
+        rotation: 0,
     },
     // 2: ###
     //      #
@@ -37,6 +39,7 @@ const DIGITS_DATA: [Figure; 10] = [
     Figure {
         data: 0b111_001_111_100_111,
         wh: 3 << 4 | 5,
+        rotation: 0,
     },
     // 3: ###
     //      #
@@ -46,6 +49,7 @@ const DIGITS_DATA: [Figure; 10] = [
     Figure {
         data: 0b111_001_111_001_111,
         wh: 3 << 4 | 5,
+        rotation: 0,
     },
     // 4: # #
     //    # #
@@ -55,6 +59,7 @@ const DIGITS_DATA: [Figure; 10] = [
     Figure {
         data: 0b101_101_111_001_001,
         wh: 3 << 4 | 5,
+        rotation: 0,
     },
     // 5: ###
     //    #
@@ -64,6 +69,7 @@ const DIGITS_DATA: [Figure; 10] = [
     Figure {
         data: 0b111_100_111_001_111,
         wh: 3 << 4 | 5,
+        rotation: 0,
     },
     // 6: ###
     //    #
@@ -73,6 +79,7 @@ const DIGITS_DATA: [Figure; 10] = [
     Figure {
         data: 0b111_100_111_101_111,
         wh: 3 << 4 | 5,
+        rotation: 0,
     },
     // 7: ###
     //      #
@@ -82,6 +89,7 @@ const DIGITS_DATA: [Figure; 10] = [
     Figure {
         data: 0b111_001_001_001_001,
         wh: 3 << 4 | 5,
+        rotation: 0,
     },
     // 8: ###
     //    # #
@@ -91,6 +99,7 @@ const DIGITS_DATA: [Figure; 10] = [
     Figure {
         data: 0b111_101_111_101_111,
         wh: 3 << 4 | 5,
+        rotation: 0,
     },
     // 9: ###
     //    # #
@@ -100,6 +109,7 @@ const DIGITS_DATA: [Figure; 10] = [
     Figure {
         data: 0b111_101_111_001_111,
         wh: 3 << 4 | 5,
+        rotation: 0,
     },
 ];
 
@@ -132,6 +142,7 @@ mod tests {
     use super::*;
 
     #[test]
+    #[allow(clippy::bool_assert_comparison)]
     fn test_digit_one_pattern() {
         let one = &DIGITS.0[1];
         // Verify the pattern matches the ASCII art
@@ -0,0 +1,313 @@
+// Game driver
+//
+// Games are plain state machines (see `common::Game`). The driver owns the
+// time and I/O side: it samples the controller, advances the game at a fixed
+// rate (see `scheduler`), plays the sounds it raises, renders the result and
+// waits for the next tick. It also handles the quit gesture, so
+// every game can be left the same way. Frames that didn't change aren't sent
+// to the display at all, and for the others the display learns which LEDs
+// changed.
+
+use smart_leds::RGB8;
+
+use crate::audio::Audio;
+use crate::common::{
+    EndReason, FrameBuffer, Game, GameController, GameOutcome, InputSnapshot, LedBuffer,
+    LedDisplay, Timer,
+};
+use crate::scheduler::Scheduler;
+use crate::theme::Theme;
+
+/// Holding A and B down together for this long quits the game
+pub const QUIT_HOLD_MILLIS: u64 = 1000;
+
+/// Tracks the A+B quit gesture
+#[derive(Default)]
+struct QuitGesture {
+    /// When A and B were first seen held down together
+    held_since: Option<u64>,
+}
+
+impl QuitGesture {
+    /// Feeds the input of the tick at `now`, returns true once the gesture is
+    /// complete
+    fn update(&mut self, input: &InputSnapshot, now: u64) -> bool {
+        if !input.ab_held {
+            self.held_since = None;
+            return false;
+        }
+        let since = *self.held_since.get_or_insert(now);
+        now - since >= QUIT_HOLD_MILLIS
+    }
+}
+
+/// Sends frames to a display, rendered in its theme, skipping frames
+/// identical to the one already shown and passing on which LEDs changed
+/// otherwise
+pub struct Presenter<const W: usize, const H: usize> {
+    leds: LedBuffer<W, H>,
+    /// Last frame sent and the theme it was rendered in
+    shown: Option<(FrameBuffer<W, H>, &'static Theme)>,
+}
+
+impl<const W: usize, const H: usize> Presenter<W, H> {
+    pub fn new() -> Self {
+        Self {
+            leds: [[RGB8::default(); W]; H],
+            shown: None,
+        }
+    }
+
+    /// Forgets what the panel shows, so that the next frame is written in
+    /// full. Needed after something else wrote to the display.
+    pub fn invalidate(&mut self) {
+        self.shown = None;
+    }
+
+    /// Shows `frame`, returns whether anything had to be written
+    pub async fn present<D>(&mut self, display: &mut D, frame: &FrameBuffer<W, H>) -> bool
+    where
+        D: LedDisplay<W, H>,
+    {
+        let layout = display.layout();
+        let theme = display.theme();
+        let dirty = match &self.shown {
+            // Another theme changes every LED
+            Some((shown, shown_theme)) if *shown_theme == theme => {
+                match frame.dirty_leds(shown, &layout) {
+                    Some(dirty) => Some(dirty),
+                    None => return false,
+                }
+            }
+            _ => None,
+        };
+
+        frame.render(&mut self.leds, &layout, theme);
+        match dirty {
+            Some(dirty) => display.write_dirty(&self.leds, dirty).await,
+            None => display.write(&self.leds).await,
+        }
+        self.shown = Some((*frame, theme));
+        true
+    }
+}
+
+impl<const W: usize, const H: usize> Default for Presenter<W, H> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Runs `game` tick by tick until it is over or the player quits.
+///
+/// Ticks are `G::TICK_MILLIS` apart on the clock of `timer`, however long
+/// reading the controller and writing the display take. The outcome's
+/// duration is the game time, i.e. the number of ticks played.
+pub async fn run_game<G, D, A, C, T, const W: usize, const H: usize>(
+    game: &mut G,
+    display: &mut D,
+    audio: &mut A,
+    controller: &mut C,
+    timer: &T,
+) -> GameOutcome
+where
+    G: Game<W, H>,
+    D: LedDisplay<W, H>,
+    A: Audio,
+    C: GameController,
+    T: Timer,
+{
+    let mut screen = FrameBuffer::<W, H>::new();
+    let mut presenter = Presenter::new();
+    let mut scheduler = Scheduler::new(G::TICK_MILLIS, timer.now_millis());
+    let mut quit = QuitGesture::default();
+    let mut elapsed = 0;
+
+    loop {
+        // Every tick reads its own input, so replays stay in step
+        for _ in 0..scheduler.due_ticks(timer.now_millis()) {
+            let input = controller.read_input().await;
+            let outcome = if quit.update(&input, elapsed) {
+                Some(game.outcome(EndReason::Quit))
+            } else {
+                game.update(input)
+            };
+            if let Some(sfx) = game.take_sound() {
+                audio.play_sfx(sfx);
+            }
+            if let Some(outcome) = outcome {
+                return GameOutcome {
+                    duration_millis: elapsed,
+                    ..outcome
+                };
+            }
+            elapsed += G::TICK_MILLIS;
+        }
+
+        if scheduler.frame_due(timer.now_millis()) {
+            screen.clear();
+            game.draw(&mut screen);
+            presenter.present(display, &screen).await;
+        }
+
+        let millis = scheduler.millis_until_next(timer.now_millis());
+        if millis > 0 {
+            timer.sleep_millis(millis).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::NoAudio;
+    use crate::common::Prng;
+    use crate::games::life::LifeGame;
+    use crate::testing::{block_on, hold_ab, RecordingDisplay, ScriptedController, VirtualTimer};
+    use crate::theme::{HIGH_CONTRAST, PLAYER};
+
+    fn buttons(a: bool, b: bool) -> InputSnapshot {
+        InputSnapshot {
+            a,
+            b,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn a_and_b_quit_the_game() {
+        // Life never ends on its own
+        let mut game = LifeGame::<8, 16>::new(Prng::new(1));
+        let mut display = RecordingDisplay::new();
+        let idle = InputSnapshot::default();
+        let mut script = [idle; 15];
+        script[10] = buttons(true, false);
+        let mut controller = ScriptedController::new(script).then_repeat(hold_ab());
+        let timer = VirtualTimer::with_limit(10_000);
+
+        let outcome = block_on(run_game(
+            &mut game,
+            &mut display,
+            &mut NoAudio,
+            &mut controller,
+            &timer,
+        ));
+        assert_eq!(outcome.reason, EndReason::Quit);
+        assert_eq!(outcome.duration_millis, 15 * 50 + QUIT_HOLD_MILLIS);
+        // Only the first frame, the new pattern picked with A and the one
+        // generation after it were sent
+        assert_eq!(display.frames().len(), 3);
+    }
+
+    #[test]
+    fn presenter_skips_unchanged_frames() {
+        let mut display = RecordingDisplay::<8, 8>::new();
+        let mut presenter = Presenter::new();
+        let mut frame = FrameBuffer::new();
+        frame.set(1, 1, 1);
+
+        assert!(block_on(presenter.present(&mut display, &frame)));
+        assert!(!block_on(presenter.present(&mut display, &frame)));
+        frame.set(2, 2, 1);
+        assert!(block_on(presenter.present(&mut display, &frame)));
+        presenter.invalidate();
+        assert!(block_on(presenter.present(&mut display, &frame)));
+        assert_eq!(display.frames().len(), 3);
+        assert_eq!(display.last_frame(), Some(&display.colors(&frame)));
+    }
+
+    #[test]
+    fn presenter_renders_in_the_display_theme() {
+        let mut display = RecordingDisplay::<8, 8>::new();
+        let mut presenter = Presenter::new();
+        let mut frame = FrameBuffer::new();
+        frame.set(1, 1, PLAYER);
+        assert!(block_on(presenter.present(&mut display, &frame)));
+
+        // The same frame is sent again in the new colors
+        display.set_theme(&HIGH_CONTRAST);
+        assert!(block_on(presenter.present(&mut display, &frame)));
+        assert!(!block_on(presenter.present(&mut display, &frame)));
+        assert_eq!(display.frames().len(), 2);
+        assert_eq!(
+            display.last_frame().unwrap()[1][1],
+            HIGH_CONTRAST.color(PLAYER)
+        );
+    }
+
+    /// Controller whose input takes a while to read, like the ADC on hardware
+    struct SlowController<'a> {
+        inner: ScriptedController,
+        timer: &'a VirtualTimer,
+    }
+
+    impl GameController for SlowController<'_> {
+        async fn read_x(&mut self) -> i8 {
+            self.read_input().await.x
+        }
+
+        async fn read_y(&mut self) -> i8 {
+            self.inner.read_y().await
+        }
+
+        fn joystick_was_pressed(&self) -> bool {
+            self.inner.joystick_was_pressed()
+        }
+
+        fn a_was_pressed(&self) -> bool {
+            self.inner.a_was_pressed()
+        }
+
+        fn b_was_pressed(&self) -> bool {
+            self.inner.b_was_pressed()
+        }
+
+        fn ab_held(&self) -> bool {
+            self.inner.ab_held()
+        }
+
+        async fn read_input(&mut self) -> InputSnapshot {
+            self.timer.sleep_millis(30).await;
+            self.inner.read_input().await
+        }
+    }
+
+    #[test]
+    fn slow_input_doesnt_slow_the_game() {
+        let mut game = LifeGame::<8, 16>::new(Prng::new(1));
+        let mut display = RecordingDisplay::new();
+        let script = [InputSnapshot::default(); 19];
+        let timer = VirtualTimer::with_limit(10_000);
+        let mut controller = SlowController {
+            inner: ScriptedController::new(script).then_repeat(hold_ab()),
+            timer: &timer,
+        };
+
+        let outcome = block_on(run_game(
+            &mut game,
+            &mut display,
+            &mut NoAudio,
+            &mut controller,
+            &timer,
+        ));
+        assert_eq!(outcome.duration_millis, 19 * 50 + QUIT_HOLD_MILLIS);
+        // Ticks stayed 50 ms apart, the last input was read 30 ms after the
+        // last tick started
+        assert_eq!(timer.elapsed_millis(), 19 * 50 + QUIT_HOLD_MILLIS + 30);
+    }
+
+    #[test]
+    fn only_a_long_hold_quits() {
+        let mut quit = QuitGesture::default();
+        // Presses alone, even together, don't count
+        assert!(!quit.update(&buttons(true, false), 0));
+        assert!(!quit.update(&buttons(false, true), 50));
+        assert!(!quit.update(&buttons(true, true), 100));
+        // Letting go starts over
+        assert!(!quit.update(&hold_ab(), 150));
+        assert!(!quit.update(&hold_ab(), 150 + QUIT_HOLD_MILLIS - 50));
+        assert!(!quit.update(&InputSnapshot::default(), 150 + QUIT_HOLD_MILLIS));
+        assert!(!quit.update(&hold_ab(), 200 + QUIT_HOLD_MILLIS));
+        assert!(quit.update(&hold_ab(), 200 + 2 * QUIT_HOLD_MILLIS));
+    }
+}
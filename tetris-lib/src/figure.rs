@@ -10,6 +10,8 @@ type Painter = fn(&mut [RGB8], u8, u8, RGB8) -> bool;
 pub struct Figure {
     pub data: u16,
     pub wh: u8,
+    /// Quarter turns clockwise from the way the figure was defined, 0 to 3
+    pub rotation: u8,
 }
 
 impl Figure {
@@ -53,9 +55,14 @@ impl Figure {
         Self {
             data: rotated,
             wh: height << 4 | width, // flip
+            rotation: (self.rotation + 1) % 4,
         }
     }
 
+    pub fn rotate_ccw(&self) -> Self {
+        self.rotate().rotate().rotate()
+    }
+
     pub fn str(&self) -> str32 {
         let mut repr = str32::new();
         let mut cursor: u16 = 1;
@@ -133,48 +140,56 @@ impl Tetramino {
     }
 }
 
-// Standard Tetris tetraminoes (I, O, T, S, Z, J, L)
+// Standard Tetris tetraminoes (I, O, T, S, Z, J, L) as they spawn. The rows
+// of `data` run from the top, the most significant bit is the top left cell.
 pub const TETRAMINO: Tetramino = Tetramino::new([
     // I: ####
     Figure {
         data: 0b1111,
         wh: 4 << 4 | 1,
+        rotation: 0,
     },
     // O: ##
     //    ##
     Figure {
         data: 0b11_11,
         wh: 2 << 4 | 2,
+        rotation: 0,
     },
-    // T:  #
+    // T: .#.
     //    ###
     Figure {
-        data: 0b111_010,
+        data: 0b010_111,
         wh: 3 << 4 | 2,
+        rotation: 0,
     },
-    // Z:  ##
-    //      ##
+    // S: .##
+    //    ##.
     Figure {
         data: 0b011_110,
         wh: 3 << 4 | 2,
+        rotation: 0,
     },
-    // S:  ##
-    //    ##
+    // Z: ##.
+    //    .##
     Figure {
         data: 0b110_011,
         wh: 3 << 4 | 2,
+        rotation: 0,
     },
-    // L: #
+    // J: #..
     //    ###
     Figure {
         data: 0b100_111,
         wh: 3 << 4 | 2,
+        rotation: 0,
     },
-    // J:    #
-    //     ###
+    // L: ..#
+    //    ###
     Figure {
         data: 0b001_111,
         wh: 3 << 4 | 2,
+        rotation: 0,
     },
 ]);
 
@@ -184,11 +199,41 @@ pub const TETRAMINO: Tetramino = Tetramino::new([
 pub const TANK: Figure = Figure {
     data: 0b_110_011_110,
     wh: 3 << 4 | 3,
+    rotation: 0,
 };
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use alloc::string::String;
+
+    /// Rows of `figure` as drawn on the screen, `#` for set cells
+    fn picture(figure: Figure) -> String {
+        let mut out = String::new();
+        for row in 0..figure.height() {
+            for col in 0..figure.width() {
+                out.push(if figure.get_bit(col, row) { '#' } else { '.' });
+            }
+            out.push('\n');
+        }
+        out
+    }
+
+    #[test]
+    fn tetraminoes_in_theme_order() {
+        let pictures = [
+            "####\n",
+            "##\n##\n",
+            ".#.\n###\n",
+            ".##\n##.\n",
+            "##.\n.##\n",
+            "#..\n###\n",
+            "..#\n###\n",
+        ];
+        for (idx, expected) in pictures.into_iter().enumerate() {
+            assert_eq!(picture(TETRAMINO.wrapping_at(idx as u8)), expected);
+        }
+    }
 
     #[test]
     fn rotated() {
@@ -201,6 +246,7 @@ mod tests {
         let four = Figure {
             data: 0b101_101_111_001_001,
             wh: 3 << 4 | 5,
+            rotation: 0,
         };
 
         // ROTATED_FOUR:
@@ -210,6 +256,7 @@ mod tests {
         let rotated_four = Figure {
             data: 0b00111_00100_11111,
             wh: 5 << 4 | 3,
+            rotation: 0,
         };
 
         let rotated = four.rotate();
@@ -222,5 +269,8 @@ mod tests {
             rotated.str(),
         );
         assert_eq!(rotated.wh, rotated_four.wh);
+        assert_eq!(rotated.rotation, 1);
+        assert_eq!(rotated.rotate_ccw(), four);
+        assert_eq!(four.rotate_ccw().rotation, 3);
     }
 }
@@ -0,0 +1,245 @@
+// Bitmap font and scrolling text
+//
+// Glyphs are 3x5 pixels like the digits in `digits`, which the font reuses.
+// Lowercase letters are drawn as capitals and characters without a glyph as
+// `?`. A panel only fits a couple of characters side by side, so longer texts
+// go through a `Marquee` that scrolls them past.
+
+use crate::common::FrameBuffer;
+use crate::digits::DIGITS;
+use crate::figure::Figure;
+use crate::scheduler::Cadence;
+
+pub const GLYPH_WIDTH: usize = 3;
+pub const GLYPH_HEIGHT: usize = 5;
+/// Horizontal distance between two characters, the glyph and a 1 pixel gap
+pub const ADVANCE: usize = GLYPH_WIDTH + 1;
+/// Vertical distance between two lines of text
+pub const LINE_HEIGHT: usize = GLYPH_HEIGHT + 1;
+
+// Rows of 3 bits from top to bottom, like `digits`
+const LETTERS: [u16; 26] = [
+    0b010_101_111_101_101, // A
+    0b110_101_110_101_110, // B
+    0b011_100_100_100_011, // C
+    0b110_101_101_101_110, // D
+    0b111_100_110_100_111, // E
+    0b111_100_110_100_100, // F
+    0b011_100_101_101_011, // G
+    0b101_101_111_101_101, // H
+    0b111_010_010_010_111, // I
+    0b001_001_001_101_010, // J
+    0b101_101_110_101_101, // K
+    0b100_100_100_100_111, // L
+    0b101_111_111_101_101, // M
+    0b110_101_101_101_101, // N
+    0b010_101_101_101_010, // O
+    0b110_101_110_100_100, // P
+    0b010_101_101_110_011, // Q
+    0b110_101_110_101_101, // R
+    0b011_100_010_001_110, // S
+    0b111_010_010_010_010, // T
+    0b101_101_101_101_111, // U
+    0b101_101_101_101_010, // V
+    0b101_101_111_111_101, // W
+    0b101_101_010_101_101, // X
+    0b101_101_010_010_010, // Y
+    0b111_001_010_100_111, // Z
+];
+
+const SYMBOLS: [(char, u16); 16] = [
+    (' ', 0b000_000_000_000_000),
+    ('!', 0b010_010_010_000_010),
+    ('?', 0b110_001_010_000_010),
+    ('.', 0b000_000_000_000_010),
+    (',', 0b000_000_000_010_100),
+    (':', 0b000_010_000_010_000),
+    ('-', 0b000_000_111_000_000),
+    ('+', 0b000_010_111_010_000),
+    ('=', 0b000_111_000_111_000),
+    ('/', 0b001_001_010_100_100),
+    ('\'', 0b010_010_000_000_000),
+    ('<', 0b001_010_100_010_001),
+    ('>', 0b100_010_001_010_100),
+    ('(', 0b010_100_100_100_010),
+    (')', 0b010_001_001_001_010),
+    ('*', 0b101_010_101_000_000),
+];
+
+const fn figure(data: u16) -> Figure {
+    Figure {
+        data,
+        wh: (GLYPH_WIDTH as u8) << 4 | GLYPH_HEIGHT as u8,
+        rotation: 0,
+    }
+}
+
+/// Glyph of `c`
+pub fn glyph(c: char) -> Figure {
+    match c.to_ascii_uppercase() {
+        c @ 'A'..='Z' => figure(LETTERS[(c as u8 - b'A') as usize]),
+        c @ '0'..='9' => *DIGITS.wrapping_at(c as u8 - b'0'),
+        c => SYMBOLS
+            .iter()
+            .find(|(symbol, _)| *symbol == c)
+            .map_or(figure(0b110_001_010_000_010), |&(_, data)| figure(data)),
+    }
+}
+
+/// Width of `text` on a single line
+pub fn text_width(text: &str) -> usize {
+    (text.chars().count() * ADVANCE).saturating_sub(1)
+}
+
+/// Direction a `Marquee` scrolls in
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Scroll {
+    /// A single line entering on the right and leaving on the left
+    Left,
+    /// Lines as wide as the screen entering at the bottom and leaving at the
+    /// top
+    Up,
+}
+
+/// Text scrolling over the screen by one pixel every few ticks, over and over
+pub struct Marquee<'a, const W: usize, const H: usize> {
+    text: &'a str,
+    scroll: Scroll,
+    /// Row of a line scrolling left, column of lines scrolling up
+    at: i16,
+    pace: Cadence,
+    offset: u32,
+}
+
+impl<'a, const W: usize, const H: usize> Marquee<'a, W, H> {
+    /// `text` moving by a pixel every tick, centered on the screen
+    pub fn new(text: &'a str, scroll: Scroll) -> Self {
+        let at = match scroll {
+            Scroll::Left => H.saturating_sub(GLYPH_HEIGHT) / 2,
+            Scroll::Up => W.saturating_sub(Self::line_width()) / 2,
+        };
+        Self {
+            text,
+            scroll,
+            at: at as i16,
+            pace: Cadence::new(1),
+            offset: 0,
+        }
+    }
+
+    /// Moves by a pixel every `ticks_per_pixel` ticks
+    pub fn with_speed(mut self, ticks_per_pixel: u16) -> Self {
+        self.pace = Cadence::new(ticks_per_pixel.max(1));
+        self
+    }
+
+    /// Puts a line scrolling left on row `y`, or lines scrolling up at
+    /// column `x`
+    pub fn at(mut self, pos: i16) -> Self {
+        self.at = pos;
+        self
+    }
+
+    /// Characters on a line scrolling up
+    fn line_len() -> usize {
+        (W + 1) / ADVANCE
+    }
+
+    fn line_width() -> usize {
+        (Self::line_len() * ADVANCE).saturating_sub(1)
+    }
+
+    /// Pixels the text moves from appearing to being gone
+    fn travel(&self) -> u32 {
+        let len = self.text.chars().count();
+        let travel = match self.scroll {
+            Scroll::Left => W + text_width(self.text),
+            Scroll::Up => H + len.div_ceil(Self::line_len().max(1)) * LINE_HEIGHT,
+        };
+        travel.max(1) as u32
+    }
+
+    pub fn tick(&mut self) {
+        if self.pace.advance(1) {
+            self.offset += 1;
+        }
+    }
+
+    /// How many times the text went all the way past
+    pub fn passes(&self) -> u32 {
+        self.offset / self.travel()
+    }
+
+    pub fn draw(&self, screen: &mut FrameBuffer<W, H>, color: u8) {
+        let shift = (self.offset % self.travel()) as i16;
+        match self.scroll {
+            Scroll::Left => {
+                screen.draw_text(W as i16 - shift, self.at, self.text, color);
+            }
+            Scroll::Up => {
+                let line_len = Self::line_len().max(1);
+                for (i, c) in self.text.chars().enumerate() {
+                    let x = self.at + ((i % line_len) * ADVANCE) as i16;
+                    let y = H as i16 - shift + ((i / line_len) * LINE_HEIGHT) as i16;
+                    screen.draw_glyph(x, y, c, color);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::ascii;
+    use crate::theme::HUD;
+
+    #[test]
+    fn text_is_drawn_with_glyphs() {
+        let mut screen = FrameBuffer::<8, 5>::new();
+        assert_eq!(screen.draw_text(0, 0, "hi!", HUD), 11);
+        assert_eq!(
+            ascii(&screen),
+            "9.9.999.\n9.9..9..\n999..9..\n9.9..9..\n9.9.999.\n"
+        );
+        // Unknown characters are question marks
+        assert_eq!(glyph('~'), glyph('?'));
+        assert_eq!(glyph('7'), *DIGITS.wrapping_at(7));
+    }
+
+    #[test]
+    fn marquee_scrolls_left() {
+        let mut marquee = Marquee::<8, 7>::new("AB", Scroll::Left).with_speed(2);
+        let mut frames = 0;
+        while marquee.passes() == 0 {
+            marquee.tick();
+            frames += 1;
+        }
+        // 8 pixels to enter, 7 to leave, 2 ticks each
+        assert_eq!(frames, 2 * (8 + 7));
+
+        let mut marquee = Marquee::<8, 7>::new("AB", Scroll::Left);
+        for _ in 0..8 {
+            marquee.tick();
+        }
+        let mut screen = FrameBuffer::new();
+        marquee.draw(&mut screen, HUD);
+        // Fully in view, on the middle rows
+        assert_eq!(ascii(&screen).lines().nth(1), Some(".9..99.."));
+    }
+
+    #[test]
+    fn marquee_scrolls_up_in_lines() {
+        let mut marquee = Marquee::<8, 16>::new("ABC", Scroll::Up);
+        for _ in 0..16 {
+            marquee.tick();
+        }
+        let mut screen = FrameBuffer::new();
+        marquee.draw(&mut screen, HUD);
+        let picture = ascii(&screen);
+        let rows: alloc::vec::Vec<&str> = picture.lines().collect();
+        // Two characters on the first line, the third on the next one
+        assert_eq!(rows[0], ".9..99..");
+        assert_eq!(rows[6], ".99.....");
+    }
+}
@@ -1,11 +1,8 @@
-use smart_leds::RGB8;
-
 use crate::{
-    common::{
-        get_pixel, set_pixel, FrameBuffer, Game, GameController, LedDisplay, Prng, Timer,
-        BLACK_IDX, BRICK_IDX, GREEN_IDX, PINK_IDX, SCREEN_HEIGHT, SCREEN_WIDTH, YELLOW_IDX,
-    },
+    common::{EndReason, FrameBuffer, Game, GameOutcome, InputSnapshot, Prng, BLACK_IDX},
     log::{debug, info},
+    scheduler::Cadence,
+    theme::{ALERT, DELIMITER, HUD, HUD_ALT, PLAYER},
 };
 
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -15,12 +12,14 @@ enum GameState {
     DrawMode,
 }
 
-pub struct LifeGame<'a, D, C, T> {
-    screen: FrameBuffer,
-    next_screen: FrameBuffer,
-    display: &'a mut D,
-    controller: &'a mut C,
-    timer: &'a T,
+/// Ticks between two generations at speed 1
+const ROUND: u16 = 20;
+/// Brightness of the cells that died in the last generation
+const TRAIL_LEVEL: u8 = 48;
+
+pub struct LifeGame<const W: usize, const H: usize> {
+    cells: FrameBuffer<W, H>,
+    next_cells: FrameBuffer<W, H>,
     prng: Prng,
     generation: u32,
     state: GameState,
@@ -28,23 +27,30 @@ pub struct LifeGame<'a, D, C, T> {
     cursor_x: usize,
     cursor_y: usize,
     blink_counter: u8,
+    generations: Cadence,
+    speed: u8,
+    last_x_input: i8,
+    last_y_input: i8,
+    cursor_move_counter: u8,
 }
 
-impl<'a, D: LedDisplay, C: GameController, T: Timer> LifeGame<'a, D, C, T> {
-    pub fn new(prng: Prng, display: &'a mut D, controller: &'a mut C, timer: &'a T) -> Self {
+impl<const W: usize, const H: usize> LifeGame<W, H> {
+    pub fn new(prng: Prng) -> Self {
         let mut game = Self {
-            screen: FrameBuffer::new(),
-            next_screen: FrameBuffer::new(),
-            display,
-            controller,
-            timer,
+            cells: FrameBuffer::new(),
+            next_cells: FrameBuffer::new(),
             prng,
             generation: 0,
             state: GameState::Running,
             pattern_index: 0,
-            cursor_x: SCREEN_WIDTH / 2,
-            cursor_y: (SCREEN_HEIGHT + 6) / 2, // Start cursor in middle of game area
+            cursor_x: W / 2,
+            cursor_y: (H + 6) / 2, // Start cursor in middle of game area
             blink_counter: 0,
+            generations: Cadence::new(ROUND),
+            speed: 1,
+            last_x_input: 0,
+            last_y_input: 0,
+            cursor_move_counter: 0,
         };
 
         game.set_pattern();
@@ -52,7 +58,8 @@ impl<'a, D: LedDisplay, C: GameController, T: Timer> LifeGame<'a, D, C, T> {
     }
 
     fn set_pattern(&mut self) {
-        self.screen.clear();
+        self.cells.clear();
+        self.next_cells.clear();
         self.generation = 0;
 
         let current_pattern = PATTERNS[self.pattern_index];
@@ -60,19 +67,19 @@ impl<'a, D: LedDisplay, C: GameController, T: Timer> LifeGame<'a, D, C, T> {
             // Predefined pattern
             info!("Setting predefined pattern {}", self.pattern_index);
             for &(x, y) in pattern {
-                if x >= 0 && x < SCREEN_WIDTH as i8 && y >= 6 && y < SCREEN_HEIGHT as i8 {
-                    self.screen.set(x as usize, y as usize, GREEN_IDX);
+                if x >= 0 && x < W as i8 && y >= 6 && y < H as i8 {
+                    self.cells.set(x as usize, y as usize, PLAYER);
                 }
             }
         } else {
             // Random pattern
             info!("Setting random pattern");
-            for x in 0..SCREEN_WIDTH {
-                for y in 6..SCREEN_HEIGHT {
+            for x in 0..W {
+                for y in 6..H {
                     // Skip top area for UI
                     if self.prng.next_range(4) == 0 {
                         // 25% chance of being alive
-                        self.screen.set(x, y, GREEN_IDX);
+                        self.cells.set(x, y, PLAYER);
                     }
                 }
             }
@@ -98,8 +105,8 @@ impl<'a, D: LedDisplay, C: GameController, T: Timer> LifeGame<'a, D, C, T> {
 
                 // Handle wrapping at screen boundaries
                 let nx = if nx < 0 {
-                    SCREEN_WIDTH as i8 - 1
-                } else if nx >= SCREEN_WIDTH as i8 {
+                    W as i8 - 1
+                } else if nx >= W as i8 {
                     0
                 } else {
                     nx
@@ -108,13 +115,13 @@ impl<'a, D: LedDisplay, C: GameController, T: Timer> LifeGame<'a, D, C, T> {
                 let ny = if ny < 6 {
                     // Don't wrap vertically into UI area
                     continue;
-                } else if ny >= SCREEN_HEIGHT as i8 {
+                } else if ny >= H as i8 {
                     6
                 } else {
                     ny
                 };
 
-                if self.screen.get(nx as usize, ny as usize) != BLACK_IDX {
+                if self.cells.get(nx as usize, ny as usize) != BLACK_IDX {
                     count += 1;
                 }
             }
@@ -123,22 +130,14 @@ impl<'a, D: LedDisplay, C: GameController, T: Timer> LifeGame<'a, D, C, T> {
     }
 
     fn next_generation(&mut self) {
-        self.next_screen.clear();
-
-        // Copy UI area
-        for x in 0..SCREEN_WIDTH {
-            for y in 0..6 {
-                let color = self.screen.get(x, y);
-                self.next_screen.set(x, y, color);
-            }
-        }
+        self.next_cells.clear();
 
         // Apply Conway's rules to game area
         let mut _alive_count = 0;
-        for x in 0..SCREEN_WIDTH {
-            for y in 6..SCREEN_HEIGHT {
+        for x in 0..W {
+            for y in 6..H {
                 let neighbors = self.count_neighbors(x, y);
-                let is_alive = self.screen.get(x, y) != BLACK_IDX;
+                let is_alive = self.cells.get(x, y) != BLACK_IDX;
 
                 // Conway's Game of Life rules:
                 // 1. Live cell with 2-3 neighbors survives
@@ -147,17 +146,17 @@ impl<'a, D: LedDisplay, C: GameController, T: Timer> LifeGame<'a, D, C, T> {
                 let reborns = !is_alive && neighbors == 3;
                 // 3. All other cells die or stay dead
                 if stays_alive || reborns {
-                    self.next_screen.set(x, y, GREEN_IDX);
+                    self.next_cells.set(x, y, PLAYER);
                     _alive_count += 1;
                 } // else: cell dies or stays dead (already cleared)
             }
         }
 
-        // Swap buffers
-        core::mem::swap(&mut self.screen, &mut self.next_screen);
+        // Swap buffers, the previous generation is kept for the trail
+        core::mem::swap(&mut self.cells, &mut self.next_cells);
         self.generation += 1;
 
-        if self.generation % 50 == 0 {
+        if self.generation.is_multiple_of(50) {
             debug!(
                 "Generation {}, alive cells: {}",
                 self.generation, _alive_count
@@ -165,186 +164,178 @@ impl<'a, D: LedDisplay, C: GameController, T: Timer> LifeGame<'a, D, C, T> {
         }
     }
 
-    fn draw_ui(&mut self, speed: u8) {
-        // Clear score area
-        for x in 0..SCREEN_WIDTH {
-            for y in 0..5 {
-                self.screen.set(x, y, BLACK_IDX);
-            }
-        }
-
+    fn draw_ui(&self, screen: &mut FrameBuffer<W, H>) {
         // Show pause indicator or draw mode indicator
         if self.state == GameState::Paused {
             // Draw pause symbol (two vertical lines)
             for y in 1..=3 {
-                self.screen.set(2, y, YELLOW_IDX);
-                self.screen.set(4, y, YELLOW_IDX);
+                screen.set(2, y, HUD_ALT);
+                screen.set(4, y, HUD_ALT);
             }
         } else if self.state == GameState::DrawMode {
             // Draw pencil icon (simple representation)
-            self.screen.set(1, 1, PINK_IDX);
-            self.screen.set(2, 2, PINK_IDX);
-            self.screen.set(3, 3, PINK_IDX);
-            self.screen.set(4, 4, PINK_IDX);
+            screen.set(1, 1, ALERT);
+            screen.set(2, 2, ALERT);
+            screen.set(3, 3, ALERT);
+            screen.set(4, 4, ALERT);
         } else {
             // Display pattern index as individual pixels (one pixel per pattern)
             for i in 0..self.pattern_index {
-                self.screen
-                    .set(i % SCREEN_WIDTH, i / SCREEN_WIDTH, GREEN_IDX);
+                screen.set(i % W, i / W, HUD);
             }
 
-            let mut available_row = self.pattern_index / SCREEN_WIDTH;
-            if self.pattern_index % SCREEN_WIDTH > 0 {
+            let mut available_row = self.pattern_index / W;
+            if !self.pattern_index.is_multiple_of(W) {
                 available_row += 1;
             }
             if available_row < 5 {
                 // Show generation progress as pixels on available space
-                let gen_progress = ((self.generation / 10) % SCREEN_WIDTH as u32) as usize;
-                for i in 0..gen_progress.min(SCREEN_WIDTH) {
-                    self.screen.set(i, available_row, YELLOW_IDX);
+                let gen_progress = ((self.generation / 10) % W as u32) as usize;
+                for i in 0..gen_progress.min(W) {
+                    screen.set(i, available_row, HUD_ALT);
                 }
             }
         }
 
         // Draw horizontal line
-        for x in 0..SCREEN_WIDTH {
-            self.screen.set(x, 5, PINK_IDX);
+        for x in 0..W {
+            screen.set(x, 5, DELIMITER);
         }
-        for x in 0..(speed * 2) {
+        for x in 0..(self.speed * 2) {
             if x & 1 == 1 {
                 continue;
             }
-            self.screen.set(x.into(), 5, BRICK_IDX);
+            screen.set(x.into(), 5, HUD_ALT);
         }
     }
 
-    fn draw_cursor(&mut self, leds: &mut [RGB8; 256]) {
-        let color = if self.blink_counter >> 1 > 5 {
-            PINK_IDX
-        } else {
-            get_pixel(leds, self.cursor_x, self.cursor_y)
-        };
-        set_pixel(leds, self.cursor_x, self.cursor_y, color);
+    fn draw_cursor(&self, screen: &mut FrameBuffer<W, H>) {
+        if self.blink_counter >> 1 > 5 {
+            screen.set(self.cursor_x, self.cursor_y, ALERT);
+        }
     }
-}
 
-impl<'a, D: LedDisplay, C: GameController, T: Timer> Game for LifeGame<'a, D, C, T> {
-    async fn run(&mut self) {
-        let mut step = 0;
-        let round: u8 = 20;
-        let mut speed: u8 = 1;
-        let delay = 50;
-
-        let mut leds = [RGB8::new(0, 0, 0); 256];
-        let mut last_x_input = 0;
-        let mut last_y_input = 0;
-        let mut cursor_move_counter: u8 = 0;
-
-        loop {
-            // Handle input based on current state
-            match self.state {
-                GameState::Running => {
-                    if self.controller.joystick_was_pressed() {
-                        self.state = GameState::Paused;
-                    }
+    fn handle_draw_mode(&mut self, input: InputSnapshot) {
+        // Update cursor movement counter
+        self.cursor_move_counter = self.cursor_move_counter.wrapping_add(1);
 
-                    // Enter draw mode with B button in running state
-                    if self.controller.b_was_pressed() {
-                        self.state = GameState::DrawMode;
-                        info!("Entered draw mode");
-                    }
+        // Check if input direction changed (immediate response)
+        let input_changed = input.x != self.last_x_input || input.y != self.last_y_input;
 
-                    // Cycle through patterns with A button
-                    if self.controller.a_was_pressed() {
-                        self.next_pattern();
-                    }
-                }
-                GameState::Paused => {
-                    if self.controller.joystick_was_pressed() {
-                        self.state = GameState::Running;
-                    }
+        // Allow movement on input change OR every 8 frames for held input
+        let should_move = input_changed || self.cursor_move_counter.is_multiple_of(8);
 
-                    // Speed control with A and B buttons
-                    if self.controller.a_was_pressed() {
-                        speed = speed.saturating_sub(1).clamp(1, 4);
-                        info!("Speed has increased to {}", speed);
-                    } else if self.controller.b_was_pressed() {
-                        speed = speed.saturating_add(1).clamp(1, 4);
-                        info!("Speed has dropped to {}", speed);
-                    }
+        if should_move {
+            if input.x != 0 {
+                let new_x = (self.cursor_x as i8 + input.x).clamp(0, W as i8 - 1);
+                self.cursor_x = new_x as usize;
+            }
+
+            if input.y != 0 {
+                let new_y = (self.cursor_y as i8 + input.y).clamp(6, H as i8 - 1);
+                self.cursor_y = new_y as usize;
+            }
+        }
+
+        // Update last input state
+        self.last_x_input = input.x;
+        self.last_y_input = input.y;
+
+        // Toggle cell with joystick press
+        if input.joystick {
+            let current_color = self.cells.get(self.cursor_x, self.cursor_y);
+            if current_color == BLACK_IDX {
+                self.cells.set(self.cursor_x, self.cursor_y, PLAYER);
+            } else {
+                self.cells.set(self.cursor_x, self.cursor_y, BLACK_IDX);
+            }
+        }
+
+        // Exit draw mode with A or B button
+        if input.a || input.b {
+            self.state = GameState::Running;
+            info!("Exited draw mode");
+        }
+    }
+}
+
+impl<const W: usize, const H: usize> Game<W, H> for LifeGame<W, H> {
+    const MIN_WIDTH: usize = 8;
+    const MIN_HEIGHT: usize = 8;
+    const TICK_MILLIS: u64 = 50;
+
+    fn update(&mut self, input: InputSnapshot) -> Option<GameOutcome> {
+        // Handle input based on current state
+        match self.state {
+            GameState::Running => {
+                if input.joystick {
+                    self.state = GameState::Paused;
                 }
-                GameState::DrawMode => {
-                    // Handle cursor movement with joystick
-                    let x_delta = self.controller.read_x().await;
-                    let y_delta = self.controller.read_y().await;
-
-                    // Update cursor movement counter
-                    cursor_move_counter = cursor_move_counter.wrapping_add(1);
-
-                    // Check if input direction changed (immediate response)
-                    let input_changed = x_delta != last_x_input || y_delta != last_y_input;
-
-                    // Allow movement on input change OR every 8 frames for held input
-                    let should_move = input_changed || (cursor_move_counter % 8 == 0);
-
-                    if should_move {
-                        if x_delta != 0 {
-                            let new_x =
-                                (self.cursor_x as i8 + x_delta).clamp(0, SCREEN_WIDTH as i8 - 1);
-                            self.cursor_x = new_x as usize;
-                        }
-
-                        if y_delta != 0 {
-                            let new_y =
-                                (self.cursor_y as i8 + y_delta).clamp(6, SCREEN_HEIGHT as i8 - 1);
-                            self.cursor_y = new_y as usize;
-                        }
-                    }
 
-                    // Update last input state
-                    last_x_input = x_delta;
-                    last_y_input = y_delta;
-
-                    // Toggle cell with joystick press
-                    if self.controller.joystick_was_pressed() {
-                        let current_color = self.screen.get(self.cursor_x, self.cursor_y);
-                        if current_color == BLACK_IDX {
-                            self.screen.set(self.cursor_x, self.cursor_y, GREEN_IDX);
-                        } else {
-                            self.screen.set(self.cursor_x, self.cursor_y, BLACK_IDX);
-                        }
-                    }
+                // Enter draw mode with B button in running state
+                if input.b {
+                    self.state = GameState::DrawMode;
+                    info!("Entered draw mode");
+                }
 
-                    // Exit draw mode with A or B button
-                    if self.controller.a_was_pressed() || self.controller.b_was_pressed() {
-                        self.state = GameState::Running;
-                        info!("Exited draw mode");
-                    }
+                // Cycle through patterns with A button
+                if input.a {
+                    self.next_pattern();
                 }
             }
+            GameState::Paused => {
+                if input.joystick {
+                    self.state = GameState::Running;
+                }
 
-            // Update generation only when running
-            if self.state == GameState::Running && step >= round / speed {
-                self.next_generation();
-                step = 0;
+                // Speed control with A and B buttons
+                if input.a {
+                    self.speed = self.speed.saturating_sub(1).clamp(1, 4);
+                    info!("Speed has increased to {}", self.speed);
+                } else if input.b {
+                    self.speed = self.speed.saturating_add(1).clamp(1, 4);
+                    info!("Speed has dropped to {}", self.speed);
+                }
             }
+            GameState::DrawMode => self.handle_draw_mode(input),
+        }
 
-            // Update blink counter for cursor
-            self.blink_counter = (self.blink_counter + 1) % 20; // Blink every 20 frames
+        // Update generation only when running
+        if self.state == GameState::Running && self.generations.advance(self.speed as u16) {
+            self.next_generation();
+        }
 
-            // Draw everything
-            self.draw_ui(speed);
+        // Update blink counter for cursor
+        self.blink_counter = (self.blink_counter + 1) % 20; // Blink every 20 frames
 
-            self.screen.render(&mut leds);
-            // Draw cursor in draw mode
-            if self.state == GameState::DrawMode {
-                self.draw_cursor(&mut leds);
-            }
+        // The simulation runs until the player leaves it
+        None
+    }
 
-            self.display.write(&leds).await;
+    fn outcome(&self, reason: EndReason) -> GameOutcome {
+        GameOutcome {
+            reason,
+            score: 0,
+            count: self.generation,
+            ..Default::default()
+        }
+    }
+
+    fn draw(&self, screen: &mut FrameBuffer<W, H>) {
+        screen.copy_from(&self.cells);
+        // Cells that just died leave a dim trail
+        for x in 0..W {
+            for y in 6..H {
+                if self.cells.get(x, y) == BLACK_IDX && self.next_cells.get(x, y) != BLACK_IDX {
+                    screen.set_shade(x, y, PLAYER, TRAIL_LEVEL);
+                }
+            }
+        }
+        self.draw_ui(screen);
 
-            step += 1;
-            self.timer.sleep_millis(delay as u64).await;
+        // Draw cursor in draw mode
+        if self.state == GameState::DrawMode {
+            self.draw_cursor(screen);
         }
     }
 }
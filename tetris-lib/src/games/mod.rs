@@ -4,14 +4,27 @@ pub mod snake;
 pub mod tanks;
 pub mod tetris;
 
-use crate::common::{FrameBuffer, Game, GameController, LedDisplay, Prng, Timer, GREEN_IDX};
+use core::cell::RefCell;
+
+use crate::audio::{Audio, Mute, NoAudio, Sfx};
+use crate::bot::{Bot, BotController, DemoController, IdleBot, SharedGame};
+use crate::color::DEFAULT_BRIGHTNESS;
+use crate::common::{
+    EndReason, FrameBuffer, Game, GameController, GameOutcome, LedDisplay, Prng, Timer, BLACK_IDX,
+};
+use crate::driver::{run_game, Presenter};
+use crate::font::{self, Marquee, Scroll, GLYPH_HEIGHT, LINE_HEIGHT};
+use crate::highscores::{HighScores, Table};
+use crate::hud::ScoreWidget;
 use crate::log::info;
+use crate::settings::{Item, Settings, SettingsController};
+use crate::storage::Storage;
+use crate::theme::{self, ALERT, DELIMITER, HIGHLIGHT, HUD, HUD_ALT, ROLE_COUNT};
 use life::LifeGame;
-use races::RacesGame;
-use smart_leds::RGB8;
-use snake::SnakeGame;
-use tanks::TanksGame;
-use tetris::TetrisGame;
+use races::{RacesBot, RacesGame};
+use snake::{SnakeBot, SnakeGame};
+use tanks::{TanksBot, TanksGame};
+use tetris::{TetrisBot, TetrisGame};
 
 //  Coordinates
 //        x
@@ -104,70 +117,780 @@ pub const GAME_TITLES: [&[u32; 8]; 6] = [
     &LIFE_TITLE,
 ];
 
-/// Run a game menu loop that allows selecting and starting games
-pub async fn run_game_menu<D, C, T, F>(display: &mut D, controller: &mut C, timer: &T, seed_fn: F)
+/// Names of the games, in menu order
+pub const GAME_NAMES: [&str; GAME_TITLES.len()] =
+    ["tetris", "snake", "tanks", "u-tanks", "races", "life"];
+
+/// Which games, in menu order, fit a `W`x`H` canvas
+fn available_games<const W: usize, const H: usize>() -> [bool; GAME_TITLES.len()] {
+    let tanks_fit = TanksGame::<W, H>::fits(W, H);
+    [
+        TetrisGame::<W, H>::fits(W, H),
+        SnakeGame::<W, H>::fits(W, H),
+        tanks_fit,
+        tanks_fit,
+        RacesGame::<W, H>::fits(W, H),
+        LifeGame::<W, H>::fits(W, H),
+    ]
+}
+
+/// Plays game number `game_idx` (in menu order) seeded with `seed` with
+/// `settings`, returns `None` if there is no such game or it doesn't fit the
+/// canvas
+pub async fn play<D, A, C, T, const W: usize, const H: usize>(
+    game_idx: u8,
+    seed: u32,
+    settings: &Settings,
+    display: &mut D,
+    audio: &mut A,
+    controller: &mut C,
+    timer: &T,
+) -> Option<GameOutcome>
 where
-    D: LedDisplay,
+    D: LedDisplay<W, H>,
+    A: Audio,
+    C: GameController,
+    T: Timer,
+{
+    if !*available_games::<W, H>().get(game_idx as usize)? {
+        return None;
+    }
+    let prng = Prng::new(seed);
+    let controller = &mut SettingsController::new(controller, settings);
+    let outcome = match game_idx {
+        0 => {
+            run_game(
+                &mut TetrisGame::new(prng, settings),
+                display,
+                audio,
+                controller,
+                timer,
+            )
+            .await
+        }
+        1 => {
+            run_game(
+                &mut SnakeGame::new(prng, settings),
+                display,
+                audio,
+                controller,
+                timer,
+            )
+            .await
+        }
+        2 => {
+            run_game(
+                &mut TanksGame::new(prng, settings, false),
+                display,
+                audio,
+                controller,
+                timer,
+            )
+            .await
+        }
+        3 => {
+            run_game(
+                &mut TanksGame::new(prng, settings, true),
+                display,
+                audio,
+                controller,
+                timer,
+            )
+            .await
+        }
+        4 => {
+            run_game(
+                &mut RacesGame::new(prng, settings),
+                display,
+                audio,
+                controller,
+                timer,
+            )
+            .await
+        }
+        5 => run_game(&mut LifeGame::new(prng), display, audio, controller, timer).await,
+        _ => return None,
+    };
+    Some(outcome)
+}
+
+/// Longest time a game plays itself in the demo
+const DEMO_MILLIS: u64 = 30_000;
+
+/// Lets `bot` play `game` without sound for up to `DEMO_MILLIS`, returns
+/// whether the player interrupted it
+async fn run_demo<G, B, D, C, T, const W: usize, const H: usize>(
+    game: G,
+    bot: B,
+    display: &mut D,
+    controller: &mut C,
+    timer: &T,
+) -> bool
+where
+    G: Game<W, H>,
+    B: Bot<G>,
+    D: LedDisplay<W, H>,
+    C: GameController,
+    T: Timer,
+{
+    let game = RefCell::new(game);
+    let ticks = (DEMO_MILLIS / G::TICK_MILLIS) as u32;
+    let bot = BotController::new(&game, bot);
+    let mut demo = DemoController::new(controller, bot, ticks);
+    run_game(
+        &mut SharedGame(&game),
+        display,
+        &mut NoAudio,
+        &mut demo,
+        timer,
+    )
+    .await;
+    demo.interrupted()
+}
+
+/// Game number `game_idx` (in menu order) playing itself, like `play`. Any
+/// input of `controller` ends the demo; returns whether it did, `None` if
+/// there is no such game.
+pub async fn demo<D, C, T, const W: usize, const H: usize>(
+    game_idx: u8,
+    seed: u32,
+    settings: &Settings,
+    display: &mut D,
+    controller: &mut C,
+    timer: &T,
+) -> Option<bool>
+where
+    D: LedDisplay<W, H>,
+    C: GameController,
+    T: Timer,
+{
+    let prng = Prng::new(seed);
+    let interrupted = match game_idx {
+        0 => {
+            let game = TetrisGame::new(prng, settings);
+            run_demo(game, TetrisBot::default(), display, controller, timer).await
+        }
+        1 => {
+            let game = SnakeGame::new(prng, settings);
+            run_demo(game, SnakeBot, display, controller, timer).await
+        }
+        2 | 3 => {
+            let game = TanksGame::new(prng, settings, game_idx == 3);
+            run_demo(game, TanksBot, display, controller, timer).await
+        }
+        4 => {
+            let game = RacesGame::new(prng, settings);
+            run_demo(game, RacesBot, display, controller, timer).await
+        }
+        5 => run_demo(LifeGame::new(prng), IdleBot, display, controller, timer).await,
+        _ => return None,
+    };
+    Some(interrupted)
+}
+
+/// How long the result of a game stays on screen before the menu comes back
+const OUTCOME_MILLIS: u64 = 2000;
+/// A new record blinks this many times while it's on screen
+const RECORD_BLINKS: u64 = 5;
+
+/// Time between two frames of the menu
+const MENU_STEP_MILLIS: u64 = 200;
+/// Menu ticks without input before the games start playing themselves
+const DEMO_IDLE_TICKS: u32 = (20_000 / MENU_STEP_MILLIS) as u32;
+/// Menu ticks the title is shown for before the best score of the game
+const TITLE_TICKS: u32 = 15;
+/// Menu ticks the best score is shown for, at least
+const BEST_SCORE_TICKS: u32 = 5;
+/// Time a score too wide for the screen takes to move by a pixel on the
+/// outcome screen
+const SCORE_STEP_MILLIS: u64 = 100;
+
+/// Widget for scores in the middle of the screen
+fn score_widget<const W: usize, const H: usize>() -> ScoreWidget {
+    ScoreWidget::new(0, (H.saturating_sub(GLYPH_HEIGHT) / 2) as i16, W)
+}
+
+/// `score` centered on the screen in `color`, scrolled by `widget` if it's
+/// too wide
+fn score_screen<const W: usize, const H: usize>(
+    score: u32,
+    color: u8,
+    widget: &ScoreWidget,
+) -> FrameBuffer<W, H> {
+    let mut screen = FrameBuffer::new();
+    widget.draw(&mut screen, score, color);
+    screen
+}
+
+/// Score of a finished game, in the alert color for a new record
+fn outcome_screen<const W: usize, const H: usize>(
+    outcome: &GameOutcome,
+    new_record: bool,
+    widget: &ScoreWidget,
+) -> FrameBuffer<W, H> {
+    let color = match outcome.reason {
+        _ if new_record => ALERT,
+        EndReason::GameOver => HUD,
+        EndReason::Quit => HUD_ALT,
+        EndReason::Completed => HIGHLIGHT,
+    };
+    score_screen(result(outcome), color, widget)
+}
+
+/// Enters `outcome` into `table` of `high_scores`, returns its rank like
+/// `HighScores::record`. Quit games and unfinished sprints aren't entered.
+fn record_outcome(
+    high_scores: &mut HighScores,
+    table: Table,
+    outcome: &GameOutcome,
+) -> Option<usize> {
+    if outcome.reason == EndReason::Quit || table.timed() != outcome.timed {
+        return None;
+    }
+    if outcome.timed {
+        high_scores.record(
+            table,
+            outcome.duration_millis.try_into().unwrap_or(u32::MAX),
+        )
+    } else {
+        high_scores.record(table, outcome.score)
+    }
+}
+
+/// What the outcome screen shows of `outcome`: its score, or the seconds it
+/// took if it's timed
+fn result(outcome: &GameOutcome) -> u32 {
+    if outcome.timed {
+        (outcome.duration_millis / 1000) as u32
+    } else {
+        outcome.score
+    }
+}
+
+/// Shows the result of a game, blinking it if it's a new record
+async fn show_outcome<D, T, const W: usize, const H: usize>(
+    display: &mut D,
+    presenter: &mut Presenter<W, H>,
+    timer: &T,
+    outcome: &GameOutcome,
+    new_record: bool,
+) where
+    D: LedDisplay<W, H>,
+    T: Timer,
+{
+    let mut widget = score_widget::<W, H>();
+    // Long enough to scroll through a wide score once
+    let millis = OUTCOME_MILLIS.max(widget.cycle_ticks(result(outcome)) as u64 * SCORE_STEP_MILLIS);
+    let half_blink = OUTCOME_MILLIS / RECORD_BLINKS / 2;
+    let mut shown = 0;
+    while shown < millis {
+        if new_record && !(shown / half_blink).is_multiple_of(2) {
+            presenter.present(display, &FrameBuffer::new()).await;
+        } else {
+            let screen = outcome_screen::<W, H>(outcome, new_record, &widget);
+            presenter.present(display, &screen).await;
+        }
+        timer.sleep_millis(SCORE_STEP_MILLIS).await;
+        widget.tick();
+        shown += SCORE_STEP_MILLIS;
+    }
+}
+
+/// How long the sound setting is shown after it changed
+const SOUND_SETTING_MILLIS: u64 = 1000;
+
+/// A speaker, with sound waves when the sound is on and crossed out when muted
+fn sound_screen<const W: usize, const H: usize>(muted: bool) -> FrameBuffer<W, H> {
+    const SPEAKER: [(usize, usize); 14] = [
+        (3, 0),
+        (2, 1),
+        (3, 1),
+        (0, 2),
+        (1, 2),
+        (2, 2),
+        (3, 2),
+        (0, 3),
+        (1, 3),
+        (2, 3),
+        (3, 3),
+        (2, 4),
+        (3, 4),
+        (3, 5),
+    ];
+    const WAVES: [(usize, usize); 6] = [(5, 1), (5, 2), (5, 3), (5, 4), (6, 0), (6, 5)];
+    const CROSS: [(usize, usize); 5] = [(5, 1), (7, 1), (6, 2), (5, 3), (7, 3)];
+
+    let mut screen = FrameBuffer::new();
+    let left = W.saturating_sub(8) / 2;
+    let top = H.saturating_sub(6) / 2;
+    let (marks, color): (&[_], _) = if muted {
+        (&CROSS, ALERT)
+    } else {
+        (&WAVES, HIGHLIGHT)
+    };
+    for &(x, y) in SPEAKER.iter() {
+        screen.set(left + x, top + y, HUD);
+    }
+    for &(x, y) in marks {
+        screen.set(left + x, top + y + 1, color);
+    }
+    screen
+}
+
+/// Time between two frames of the settings page, values too wide for the
+/// panel scroll by a pixel per frame
+const SETTINGS_STEP_MILLIS: u64 = 40;
+
+/// Where the value of a setting goes: (left, top). Tall panels show it below
+/// the label, flat ones next to it.
+fn value_area<const W: usize, const H: usize>(item: Item) -> (usize, usize) {
+    if H >= 2 * LINE_HEIGHT {
+        (0, LINE_HEIGHT + 1)
+    } else {
+        (
+            font::text_width(item.label()) + 2,
+            H.saturating_sub(GLYPH_HEIGHT) / 2,
+        )
+    }
+}
+
+/// Scrolls the value of `item` if it doesn't fit next to or below the label
+fn value_marquee<const W: usize, const H: usize>(
+    settings: &Settings,
+    item: Item,
+) -> Marquee<'static, W, H> {
+    let (_, top) = value_area::<W, H>(item);
+    Marquee::new(settings.value(item), Scroll::Left).at(top as i16)
+}
+
+/// The label of `item` in the highlight color, its value and a delimiter in
+/// between. On the theme page the colors of the theme fill the rest of the
+/// panel.
+fn settings_screen<const W: usize, const H: usize>(
+    settings: &Settings,
+    item: Item,
+    marquee: &Marquee<W, H>,
+) -> FrameBuffer<W, H> {
+    let mut screen = FrameBuffer::new();
+    let (left, top) = value_area::<W, H>(item);
+    let value = settings.value(item);
+    let width = font::text_width(value);
+    if left + width <= W {
+        let x = left + (W - left - width) / 2;
+        screen.draw_text(x as i16, top as i16, value, HUD);
+    } else {
+        marquee.draw(&mut screen, HUD);
+    }
+
+    if item == Item::Theme {
+        // Stripes in all the colors below the value
+        for y in top + LINE_HEIGHT + 1..H {
+            for x in left..W {
+                let stripe = (y - top) / 2;
+                screen.set(x, y, 1 + (stripe % (ROLE_COUNT - 1)) as u8);
+            }
+        }
+    }
+
+    // The label covers whatever scrolled by
+    for y in 0..H {
+        for x in 0..left.min(W) {
+            screen.set(x, y, BLACK_IDX);
+        }
+    }
+    let label_top = if left == 0 { 0 } else { top };
+    screen.draw_text(0, label_top as i16, item.label(), HIGHLIGHT);
+    if left == 0 {
+        for x in 0..W {
+            screen.set(x, GLYPH_HEIGHT, DELIMITER);
+        }
+    } else {
+        for y in 0..H {
+            screen.set(left - 1, y, DELIMITER);
+        }
+    }
+    screen
+}
+
+/// Hands the settings the menu is in charge of to the display, the audio and
+/// the theme
+fn apply_settings<D, A, const W: usize, const H: usize>(
+    settings: &Settings,
+    display: &mut D,
+    audio: &mut Mute<A>,
+) where
+    D: LedDisplay<W, H>,
+    A: Audio,
+{
+    display.set_brightness(settings.brightness);
+    audio.set_muted(settings.muted);
+    if let Some(theme) = theme::by_index(settings.theme) {
+        display.set_theme(theme);
+    }
+}
+
+/// Settings page: the joystick moves up and down through the items and changes
+/// the selected one left and right, B or the joystick button go back to the
+/// menu. Changes apply right away.
+async fn run_settings<D, A, C, T, const W: usize, const H: usize>(
+    display: &mut D,
+    presenter: &mut Presenter<W, H>,
+    audio: &mut Mute<A>,
+    controller: &mut C,
+    timer: &T,
+    settings: &mut Settings,
+) where
+    D: LedDisplay<W, H>,
+    A: Audio,
     C: GameController,
     T: Timer,
-    F: Fn() -> u32,
 {
-    let mut leds: [RGB8; 256] = [RGB8::default(); 256];
-    let mut game_idx: u8 = 0;
+    let mut idx = 0;
+    let mut marquee = value_marquee(settings, Item::ALL[idx]);
+    // Joystick position of the last frame, holding it only counts once
+    let mut held = (0, 0);
+    loop {
+        let (x, y) = (controller.read_x().await, controller.read_y().await);
+        if controller.b_was_pressed() || controller.joystick_was_pressed() {
+            return;
+        }
+
+        if y != 0 && y != held.1 {
+            let count = Item::ALL.len() as i8;
+            idx = (idx as i8 + y).rem_euclid(count) as usize;
+            marquee = value_marquee(settings, Item::ALL[idx]);
+        }
+        if x != 0 && x != held.0 && settings.change(Item::ALL[idx], x) {
+            info!("Settings {:?}", settings);
+            apply_settings(settings, display, audio);
+            // The theme may have changed, the same frame looks different
+            presenter.invalidate();
+            audio.play_sfx(Sfx::Lock);
+            marquee = value_marquee(settings, Item::ALL[idx]);
+        }
+        held = (x, y);
+
+        let screen = settings_screen(settings, Item::ALL[idx], &marquee);
+        presenter.present(display, &screen).await;
+        timer.sleep_millis(SETTINGS_STEP_MILLIS).await;
+        marquee.tick();
+    }
+}
+
+/// Mode page shown as a Tetris game starts: the joystick steps through the
+/// modes left and right and its button starts the game, B goes back to the
+/// menu. Returns whether to start.
+async fn run_mode_select<D, C, T, const W: usize, const H: usize>(
+    display: &mut D,
+    presenter: &mut Presenter<W, H>,
+    controller: &mut C,
+    timer: &T,
+    settings: &mut Settings,
+) -> bool
+where
+    D: LedDisplay<W, H>,
+    C: GameController,
+    T: Timer,
+{
+    let item = Item::TetrisMode;
+    let mut marquee = value_marquee(settings, item);
+    // Joystick position of the last frame, holding it only counts once
+    let mut held = 0;
+    loop {
+        let x = controller.read_x().await;
+        if controller.joystick_was_pressed() {
+            return true;
+        }
+        if controller.b_was_pressed() {
+            return false;
+        }
+        if x != 0 && x != held && settings.change(item, x) {
+            marquee = value_marquee(settings, item);
+        }
+        held = x;
+
+        let screen = settings_screen(settings, item, &marquee);
+        presenter.present(display, &screen).await;
+        timer.sleep_millis(SETTINGS_STEP_MILLIS).await;
+        marquee.tick();
+    }
+}
+
+/// Steps through the game list in the direction of `delta`, skipping games that
+/// don't fit the panel
+fn next_game(game_idx: u8, delta: i8, available: &[bool; GAME_TITLES.len()]) -> u8 {
     let num_games = GAME_TITLES.len() as u8;
+    let mut idx = game_idx;
+    for _ in 0..num_games {
+        idx = match delta {
+            -1 => (idx + num_games - 1) % num_games,
+            1 => (idx + 1) % num_games,
+            _ => idx,
+        };
+        if available[idx as usize] {
+            return idx;
+        }
+    }
+    game_idx
+}
+
+/// Run a game menu loop that allows selecting and starting games.
+///
+/// The best score of the selected game is shown in between its title. Button A
+/// mutes or unmutes the sound, button B opens the settings page. Tetris asks
+/// for its mode before it starts. The high
+/// scores and the settings are kept in `storage`. When nobody touches the
+/// controller for a while, the games take turns playing themselves until
+/// somebody does.
+pub async fn run_game_menu<D, A, C, T, S, F, const W: usize, const H: usize>(
+    display: &mut D,
+    audio: &mut A,
+    controller: &mut C,
+    timer: &T,
+    storage: &mut S,
+    seed_fn: F,
+) where
+    D: LedDisplay<W, H>,
+    A: Audio,
+    C: GameController,
+    T: Timer,
+    S: Storage,
+    F: Fn() -> u32,
+{
+    let mut presenter = Presenter::new();
+    let available = available_games::<W, H>();
+    let mut game_idx: u8 = next_game(GAME_TITLES.len() as u8 - 1, 1, &available);
+    let mut high_scores = HighScores::load(storage).await;
+    let mut settings = match Settings::load(storage).await {
+        Some(settings) => settings,
+        // Keep the brightness the platform started with
+        None => Settings {
+            brightness: display.brightness().unwrap_or(DEFAULT_BRIGHTNESS),
+            ..Settings::default()
+        },
+    };
+    let mut audio = Mute::new(audio);
+    apply_settings(&settings, display, &mut audio);
+    audio.play_sfx(Sfx::Jingle);
+    let mut menu_ticks: u32 = 0;
+    let mut idle_ticks: u32 = 0;
+    let mut best_widget = score_widget::<W, H>();
+    info!("Menu for {}x{} panel", W, H);
 
     loop {
         let delta = controller.read_x().await;
+        let y = controller.read_y().await;
+        // Reading a press clears it, read each button once
+        let a = controller.a_was_pressed();
+        let b = controller.b_was_pressed();
+        let joystick = controller.joystick_was_pressed();
+        let touched = delta != 0 || y != 0 || a || b || joystick;
         if delta != 0 {
-            game_idx = match delta {
-                -1 => (game_idx + num_games - 1) % num_games,
-                1 => (game_idx + 1) % num_games,
-                _ => game_idx,
-            };
+            game_idx = next_game(game_idx, delta, &available);
+            menu_ticks = 0;
             info!(
                 "Menu navigation: delta={}, selected_game={}",
                 delta, game_idx
             );
         }
 
-        if controller.joystick_was_pressed() {
-            let seed = seed_fn();
-            let prng = Prng::new(seed);
-            match game_idx {
-                0 => {
-                    let mut tetris = TetrisGame::new(prng, display, controller, timer);
-                    tetris.run().await;
-                }
-                1 => {
-                    let mut snake = SnakeGame::new(prng, display, controller, timer);
-                    snake.run().await;
-                }
-                2 => {
-                    let mut tanks = TanksGame::new(prng, display, controller, timer, false);
-                    tanks.run().await;
-                }
-                3 => {
-                    let mut tanks = TanksGame::new(prng, display, controller, timer, true);
-                    tanks.run().await;
-                }
-                4 => {
-                    let mut races = RacesGame::new(prng, display, controller, timer);
-                    races.run().await;
-                }
-                5 => {
-                    let mut life = LifeGame::new(prng, display, controller, timer);
-                    life.run().await;
+        if a {
+            settings.muted = !settings.muted;
+            audio.set_muted(settings.muted);
+            settings.save(storage).await;
+            info!("Muted {}", audio.is_muted());
+            presenter
+                .present(display, &sound_screen(audio.is_muted()))
+                .await;
+            timer.sleep_millis(SOUND_SETTING_MILLIS).await;
+            menu_ticks = 0;
+        }
+
+        if b {
+            let before = settings;
+            run_settings(
+                display,
+                &mut presenter,
+                &mut audio,
+                controller,
+                timer,
+                &mut settings,
+            )
+            .await;
+            if settings != before {
+                settings.save(storage).await;
+            }
+            menu_ticks = 0;
+        }
+
+        let mut start = joystick && available[game_idx as usize];
+        if start && game_idx == 0 {
+            let before = settings;
+            start =
+                run_mode_select(display, &mut presenter, controller, timer, &mut settings).await;
+            if settings != before {
+                settings.save(storage).await;
+            }
+            menu_ticks = 0;
+        }
+
+        if start {
+            let outcome = play(
+                game_idx,
+                seed_fn(),
+                &settings,
+                display,
+                &mut audio,
+                controller,
+                timer,
+            )
+            .await;
+            // The game drew on the panel
+            presenter.invalidate();
+            if let Some(outcome) = outcome {
+                info!(
+                    "Game {} over, score {}, count {}, {} ms",
+                    game_idx, outcome.score, outcome.count, outcome.duration_millis
+                );
+                let table = Table::new(game_idx, settings.tetris_mode);
+                let rank = record_outcome(&mut high_scores, table, &outcome);
+                if rank.is_some() {
+                    high_scores.save(storage).await;
                 }
-                _ => {}
+                show_outcome(display, &mut presenter, timer, &outcome, rank == Some(0)).await;
+                audio.play_sfx(Sfx::Jingle);
+                menu_ticks = 0;
             }
         }
 
-        // Display menu - show game index
-        let title = GAME_TITLES[game_idx as usize];
-        let screen = FrameBuffer::from_rows(title, GREEN_IDX);
-        screen.render(&mut leds);
-        display.write(&leds).await;
+        idle_ticks = if touched { 0 } else { idle_ticks + 1 };
+        if idle_ticks >= DEMO_IDLE_TICKS {
+            info!("Demo from game {}", game_idx);
+            // The menu follows the demo, the game playing when the player
+            // steps in is selected
+            while let Some(false) =
+                demo(game_idx, seed_fn(), &settings, display, controller, timer).await
+            {
+                game_idx = next_game(game_idx, 1, &available);
+            }
+            presenter.invalidate();
+            idle_ticks = 0;
+            menu_ticks = 0;
+        }
+
+        // Display menu - the title of the selected game, now and then its
+        // best score, or for Sprint its best time in seconds
+        let table = Table::new(game_idx, settings.tetris_mode);
+        let best = match high_scores.best(table) {
+            millis if table.timed() => millis / 1000,
+            score => score,
+        };
+        let best_ticks = BEST_SCORE_TICKS.max(best_widget.cycle_ticks(best));
+        let screen = if best > 0 && menu_ticks % (TITLE_TICKS + best_ticks) >= TITLE_TICKS {
+            best_widget.tick();
+            score_screen::<W, H>(best, HIGHLIGHT, &best_widget)
+        } else {
+            best_widget.rewind();
+            FrameBuffer::<W, H>::from_rows(GAME_TITLES[game_idx as usize], HUD)
+        };
+        menu_ticks = menu_ticks.wrapping_add(1);
+        presenter.present(display, &screen).await;
+
+        timer.sleep_millis(MENU_STEP_MILLIS).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::InputSnapshot;
+    use crate::settings::TetrisMode;
+    use crate::testing::{
+        block_on, hold_ab, press, run_until_stalled, MemoryStorage, RecordingAudio,
+        RecordingDisplay, ScriptedController, VirtualTimer,
+    };
+
+    #[test]
+    fn menu_buttons_mute_open_settings_and_start() {
+        let idle = InputSnapshot::default();
+        let a = InputSnapshot { a: true, ..idle };
+        let b = InputSnapshot { b: true, ..idle };
+        // Mute, open and close the settings, pick the Tetris mode, start and
+        // quit the game
+        let script = [a, b, idle, b, press(), press()]
+            .into_iter()
+            .chain([hold_ab(); 21]);
+        let mut controller = ScriptedController::new(script).then_stall();
+        let mut display = RecordingDisplay::<8, 16>::new();
+        let mut storage = MemoryStorage::new();
+        let menu = run_until_stalled(run_game_menu(
+            &mut display,
+            &mut RecordingAudio::new(),
+            &mut controller,
+            &VirtualTimer::with_limit(60 * 1000),
+            &mut storage,
+            || 1,
+        ));
+        assert!(menu.is_none());
+        assert!(controller.is_exhausted());
+
+        let settings = block_on(Settings::load(&mut storage)).unwrap();
+        assert!(settings.muted);
+        let frames = display.frames();
+        assert!(frames.contains(&display.colors(&sound_screen(true))));
+        let item = Item::ALL[0];
+        let marquee = value_marquee(&settings, item);
+        assert!(frames.contains(&display.colors(&settings_screen(&settings, item, &marquee))));
+        // The game was quit without a score
+        assert!(frames.contains(&display.colors(&score_screen(
+            0,
+            HUD_ALT,
+            &score_widget::<8, 16>()
+        ))));
+    }
+
+    #[test]
+    fn only_finished_games_make_the_table() {
+        let mut high_scores = HighScores::default();
+        let snake = Table::new(1, TetrisMode::Marathon);
+        let outcome = GameOutcome {
+            reason: EndReason::Quit,
+            score: 12,
+            ..GameOutcome::default()
+        };
+        assert_eq!(record_outcome(&mut high_scores, snake, &outcome), None);
+        let game_over = GameOutcome {
+            reason: EndReason::GameOver,
+            ..outcome
+        };
+        assert_eq!(record_outcome(&mut high_scores, snake, &game_over), Some(0));
+
+        // Sprints count with their time once finished
+        let sprint = Table::new(0, TetrisMode::Sprint);
+        assert_eq!(record_outcome(&mut high_scores, sprint, &game_over), None);
+        let finished = GameOutcome {
+            reason: EndReason::Completed,
+            duration_millis: 75_000,
+            timed: true,
+            ..outcome
+        };
+        assert_eq!(record_outcome(&mut high_scores, sprint, &finished), Some(0));
+        assert_eq!(high_scores.best(sprint), 75_000);
+    }
 
-        timer.sleep_millis(200).await;
+    #[test]
+    fn play_refuses_games_that_dont_fit() {
+        let mut display = RecordingDisplay::<32, 8>::new();
+        let outcome = block_on(play(
+            0,
+            1,
+            &Settings::default(),
+            &mut display,
+            &mut RecordingAudio::new(),
+            &mut ScriptedController::new([]),
+            &VirtualTimer::new(),
+        ));
+        assert_eq!(outcome, None);
+        assert!(display.frames().is_empty());
     }
 }
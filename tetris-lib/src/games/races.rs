@@ -1,27 +1,38 @@
 use core::option::Option;
-use smart_leds::RGB8;
 
 use crate::{
+    audio::{raise, Sfx},
+    bot::Bot,
     common::{Dot, FrameBuffer, Prng},
-    common::{
-        Game, GameController, LedDisplay, Timer, BLACK_IDX, BLUE_IDX, BRICK_IDX, DARK_GREEN_IDX,
-        GREEN_IDX, PINK_IDX, RED_IDX, SCREEN_HEIGHT, SCREEN_WIDTH, YELLOW_IDX,
-    },
+    common::{EndReason, Game, GameOutcome, InputSnapshot, BLACK_IDX},
     digits::DIGITS,
+    hud::ScoreWidget,
+    scheduler::Cadence,
+    settings::Settings,
+    theme::{ENEMY, HAZARD, HUD, HUD_ALT, OBSTACLE, PICKUP, PLAYER, WALL},
 };
 
-static ROAD_UPDATE_STEP_SIZE: u8 = 10;
-static UPDATE_STEP_SIZE: u8 = ROAD_UPDATE_STEP_SIZE * 2;
+/// Ticks between two moves of the road, the obstacles and the car forward or
+/// backward
+const ROAD_TICKS: u16 = 10;
+/// Ticks between two sideways moves of the car
+const STEER_TICKS: u16 = ROAD_TICKS / 4;
+/// Ticks between two moves of the other cars and the powerup
+const TRAFFIC_TICKS: u16 = ROAD_TICKS * 2;
+/// Ticks the score stays on (and off) while blinking after game over
+const BLINK_TICKS: u16 = 10;
+const BLINKS: u16 = 3;
 
 // Races game implementation
-pub struct RacesGame<'a, D, C, T> {
-    screen: FrameBuffer,
-    display: &'a mut D,
-    controller: &'a mut C,
-    timer: &'a T,
-
-    update_step: u8,
-    cars_destroyed: u8,
+pub struct RacesGame<const W: usize, const H: usize> {
+    steer_pace: Cadence,
+    road_pace: Cadence,
+    traffic_pace: Cadence,
+    /// Progress of the road and the traffic per tick, higher levels are faster
+    speed: u16,
+    cars_destroyed: u32,
+    /// Scrolls scores of three digits and more left of the lives
+    score_widget: ScoreWidget,
     car_pos: Dot,
     obstacles: [Dot; 2],
     obstacle_count: usize,
@@ -37,19 +48,21 @@ pub struct RacesGame<'a, D, C, T> {
     road_animation: u8,
     bullet_powerup: Option<Dot>,
     prng: Prng,
+    sound: Option<Sfx>,
+    /// Ticks since the game was lost
+    game_over: Option<u16>,
 }
 
-impl<'a, D: LedDisplay, C: GameController, T: Timer> RacesGame<'a, D, C, T> {
-    pub fn new(prng: Prng, display: &'a mut D, controller: &'a mut C, timer: &'a T) -> Self {
+impl<const W: usize, const H: usize> RacesGame<W, H> {
+    pub fn new(prng: Prng, settings: &Settings) -> Self {
         let mut game = Self {
-            screen: FrameBuffer::new(),
-            display,
-            controller,
-            timer,
-
-            update_step: 0,
+            steer_pace: Cadence::due(STEER_TICKS),
+            road_pace: Cadence::new(ROAD_TICKS),
+            traffic_pace: Cadence::new(TRAFFIC_TICKS),
+            speed: 1 + settings.start_level as u16 / 3,
             cars_destroyed: 0,
-            car_pos: Dot::new(3, 28),
+            score_widget: ScoreWidget::new(0, 0, 3).with_speed(8),
+            car_pos: Dot::new(W as i8 / 2 - 1, H as i8 - 4),
             obstacles: [Dot::new(0, 0); 2],
             obstacle_count: 0,
             bullets: [Dot::new(0, 0); 4],
@@ -64,6 +77,8 @@ impl<'a, D: LedDisplay, C: GameController, T: Timer> RacesGame<'a, D, C, T> {
             road_animation: 0,
             bullet_powerup: None,
             prng,
+            sound: None,
+            game_over: None,
         };
 
         // Initialize racing car at the top
@@ -75,7 +90,7 @@ impl<'a, D: LedDisplay, C: GameController, T: Timer> RacesGame<'a, D, C, T> {
     fn spawn_obstacles(&mut self) {
         if self.obstacle_count < self.obstacles.len() && self.prng.next_range(30) == 0 {
             // Reduced spawn rate
-            let x = self.prng.next_range(6) as i8;
+            let x = self.prng.next_range(W as u8 - 2) as i8;
             self.obstacles[self.obstacle_count] = Dot::new(x, 0);
             self.obstacle_count += 1;
         }
@@ -83,7 +98,7 @@ impl<'a, D: LedDisplay, C: GameController, T: Timer> RacesGame<'a, D, C, T> {
 
     fn spawn_bullet_powerup(&mut self) {
         if self.bullet_powerup.is_none() && self.prng.next_range(50) == 0 {
-            let x = self.prng.next_range(5) as i8 + 1;
+            let x = self.prng.next_range(W as u8 - 3) as i8 + 1;
             self.bullet_powerup = Some(Dot::new(x, 0));
         }
     }
@@ -101,21 +116,20 @@ impl<'a, D: LedDisplay, C: GameController, T: Timer> RacesGame<'a, D, C, T> {
                 if self.max_bullets < 5 {
                     self.max_bullets += 1;
                 }
-            } else if powerup.y < SCREEN_HEIGHT as i8 {
+                raise(&mut self.sound, Sfx::Powerup);
+            } else if powerup.y < H as i8 {
                 // Only keep powerup if not collected and still on screen
                 self.bullet_powerup.replace(powerup);
             }
         }
     }
 
-    fn draw_bullet_powerup(&mut self) {
+    fn draw_bullet_powerup(&self, screen: &mut FrameBuffer<W, H>) {
         if let Some(powerup) = self.bullet_powerup {
-            if powerup.y >= 0 && powerup.y < SCREEN_HEIGHT as i8 {
+            if powerup.y >= 0 && powerup.y < H as i8 {
                 // Draw two vertical dots in pink
-                self.screen
-                    .set(powerup.x as usize, powerup.y as usize, PINK_IDX);
-                self.screen
-                    .set(powerup.x as usize, (powerup.y + 1) as usize, PINK_IDX);
+                screen.set(powerup.x as usize, powerup.y as usize, PICKUP);
+                screen.set(powerup.x as usize, (powerup.y + 1) as usize, PICKUP);
             }
         }
     }
@@ -145,11 +159,11 @@ impl<'a, D: LedDisplay, C: GameController, T: Timer> RacesGame<'a, D, C, T> {
             }
 
             // If car goes off screen at bottom, reset it to top with random x position
-            if self.racing_cars[i].y >= SCREEN_HEIGHT as i8 {
+            if self.racing_cars[i].y >= H as i8 {
                 self.racing_cars[i].y = 0;
                 // Try to find a position that doesn't overlap with obstacles
                 loop {
-                    let new_x = self.prng.next_range(5) as i8 + 1;
+                    let new_x = self.prng.next_range(W as u8 - 3) as i8 + 1;
                     let mut valid_position = true;
 
                     for j in 0..self.obstacle_count {
@@ -169,37 +183,37 @@ impl<'a, D: LedDisplay, C: GameController, T: Timer> RacesGame<'a, D, C, T> {
         }
     }
 
-    fn draw_racing_cars(&mut self) {
+    fn draw_racing_cars(&self, screen: &mut FrameBuffer<W, H>) {
         for i in 0..self.racing_cars.len() {
             let car = self.racing_cars[i];
-            if car.y >= 0 && car.y < SCREEN_HEIGHT as i8 && self.racing_car_health > 0 {
+            if car.y >= 0 && car.y < H as i8 && self.racing_car_health > 0 {
                 // Draw racing car (same shape as player car but in blue)
                 let x = car.x as usize;
                 let y = car.y as usize;
 
                 // Draw car body (check bounds to prevent underflow)
-                if x > 0 && x < SCREEN_WIDTH - 1 && y < SCREEN_HEIGHT {
-                    self.screen.set(x - 1, y, BLUE_IDX);
-                    self.screen.set(x, y, BLUE_IDX);
-                    self.screen.set(x + 1, y, BLUE_IDX);
+                if x > 0 && x < W - 1 && y < H {
+                    screen.set(x - 1, y, ENEMY);
+                    screen.set(x, y, ENEMY);
+                    screen.set(x + 1, y, ENEMY);
                 }
-                if y > 0 && x < SCREEN_WIDTH {
-                    self.screen.set(x, y - 1, BLUE_IDX);
+                if y > 0 && x < W {
+                    screen.set(x, y - 1, ENEMY);
                 }
-                if y > 1 && x > 0 && x < SCREEN_WIDTH - 1 {
-                    self.screen.set(x, y - 2, BLUE_IDX);
-                    self.screen.set(x - 1, y - 2, BLUE_IDX);
-                    self.screen.set(x + 1, y - 2, BLUE_IDX);
+                if y > 1 && x > 0 && x < W - 1 {
+                    screen.set(x, y - 2, ENEMY);
+                    screen.set(x - 1, y - 2, ENEMY);
+                    screen.set(x + 1, y - 2, ENEMY);
                 }
-                if y > 2 && x < SCREEN_WIDTH {
-                    self.screen.set(x, y - 3, BLUE_IDX);
+                if y > 2 && x < W {
+                    screen.set(x, y - 3, ENEMY);
                 }
             }
         }
     }
     fn update_road(&mut self) {
         self.update_road = (self.update_road + 1) % 4;
-        self.road_animation = (self.road_animation + 1) % SCREEN_HEIGHT as u8;
+        self.road_animation = (self.road_animation + 1) % H as u8;
     }
 
     fn update_obstacles(&mut self) {
@@ -208,7 +222,7 @@ impl<'a, D: LedDisplay, C: GameController, T: Timer> RacesGame<'a, D, C, T> {
             self.obstacles[i].y += 1;
 
             // Remove obstacles that are off screen
-            if self.obstacles[i].y >= SCREEN_HEIGHT as i8 {
+            if self.obstacles[i].y >= H as i8 {
                 // Remove by swapping with last obstacle
                 self.obstacle_count -= 1;
                 if i < self.obstacle_count {
@@ -288,6 +302,7 @@ impl<'a, D: LedDisplay, C: GameController, T: Timer> RacesGame<'a, D, C, T> {
             let obs = self.obstacles[i];
             if self.check_car_obstacle_collision(&obs) {
                 self.lives -= 1;
+                raise(&mut self.sound, Sfx::Crash);
                 self.invulnerable_time = 20; // 1 second of invulnerability
                 return;
             }
@@ -326,13 +341,14 @@ impl<'a, D: LedDisplay, C: GameController, T: Timer> RacesGame<'a, D, C, T> {
                     if self.racing_car_health == 0 {
                         self.cars_destroyed += 1;
                         self.racing_cars[0].y = 0;
-                        self.racing_cars[0].x = self.prng.next_range(5) as i8 + 1;
+                        self.racing_cars[0].x = self.prng.next_range(W as u8 - 3) as i8 + 1;
                         self.racing_car_health = 3;
                     }
                 }
             }
 
             if hit {
+                raise(&mut self.sound, Sfx::Hit);
                 // Remove bullet
                 self.bullet_count -= 1;
                 if i < self.bullet_count {
@@ -344,216 +360,261 @@ impl<'a, D: LedDisplay, C: GameController, T: Timer> RacesGame<'a, D, C, T> {
         }
     }
 
-    fn draw_road(&mut self) {
+    fn draw_road(&self, screen: &mut FrameBuffer<W, H>) {
         // Draw intermittent road edges with animation
         let mut bricks = 0i8;
         let mut part = true;
-        for y in 0..SCREEN_HEIGHT {
+        for y in 0..H {
             if bricks == 4 {
                 bricks = 0;
                 part = !part;
             };
             bricks += 1;
 
-            let color = if part { BRICK_IDX } else { BLACK_IDX };
+            let color = if part { WALL } else { BLACK_IDX };
 
             // Left edge
-            self.screen
-                .set(0, (y + self.road_animation as usize) % SCREEN_HEIGHT, color);
+            screen.set(0, (y + self.road_animation as usize) % H, color);
             // Right edge
-            self.screen
-                .set(7, (y + self.road_animation as usize) % SCREEN_HEIGHT, color);
+            screen.set(W - 1, (y + self.road_animation as usize) % H, color);
         }
     }
 
-    fn draw_car(&mut self) {
+    fn draw_car(&self, screen: &mut FrameBuffer<W, H>) {
         let x = self.car_pos.x as usize;
         let y = self.car_pos.y as usize;
 
-        if self.invulnerable_time > 0 && (self.invulnerable_time / 4) % 2 == 0 {
+        if self.invulnerable_time > 0 && (self.invulnerable_time / 4).is_multiple_of(2) {
             // Blink car when invulnerable
             return;
         }
 
         // Draw car body (check bounds to prevent underflow)
-        if x > 0 && x < SCREEN_WIDTH - 1 && y < SCREEN_HEIGHT {
-            self.screen.set(x - 1, y, GREEN_IDX);
-            self.screen.set(x, y, GREEN_IDX);
-            self.screen.set(x + 1, y, GREEN_IDX);
+        if x > 0 && x < W - 1 && y < H {
+            screen.set(x - 1, y, PLAYER);
+            screen.set(x, y, PLAYER);
+            screen.set(x + 1, y, PLAYER);
         }
-        if y > 0 && x < SCREEN_WIDTH {
-            self.screen.set(x, y - 1, GREEN_IDX);
+        if y > 0 && x < W {
+            screen.set(x, y - 1, PLAYER);
         }
-        if y > 1 && x > 0 && x < SCREEN_WIDTH - 1 {
-            self.screen.set(x, y - 2, GREEN_IDX);
-            self.screen.set(x - 1, y - 2, GREEN_IDX);
-            self.screen.set(x + 1, y - 2, GREEN_IDX);
+        if y > 1 && x > 0 && x < W - 1 {
+            screen.set(x, y - 2, PLAYER);
+            screen.set(x - 1, y - 2, PLAYER);
+            screen.set(x + 1, y - 2, PLAYER);
         }
-        if y > 2 && x < SCREEN_WIDTH {
-            self.screen.set(x, y - 3, GREEN_IDX);
+        if y > 2 && x < W {
+            screen.set(x, y - 3, PLAYER);
         }
     }
 
-    fn draw_obstacles(&mut self) {
+    fn draw_obstacles(&self, screen: &mut FrameBuffer<W, H>) {
         for i in 0..self.obstacle_count {
             let obs = self.obstacles[i];
-            if obs.y >= 0 && obs.y < SCREEN_HEIGHT as i8 {
+            if obs.y >= 0 && obs.y < H as i8 {
                 // Draw bigger obstacle (2x2) in dark green
-                self.screen
-                    .set(obs.x as usize, obs.y as usize, DARK_GREEN_IDX);
-                self.screen
-                    .set(obs.x as usize + 1, obs.y as usize, DARK_GREEN_IDX);
-                self.screen
-                    .set(obs.x as usize, obs.y as usize + 1, DARK_GREEN_IDX);
-                self.screen
-                    .set(obs.x as usize + 1, obs.y as usize + 1, DARK_GREEN_IDX);
+                screen.set(obs.x as usize, obs.y as usize, OBSTACLE);
+                screen.set(obs.x as usize + 1, obs.y as usize, OBSTACLE);
+                screen.set(obs.x as usize, obs.y as usize + 1, OBSTACLE);
+                screen.set(obs.x as usize + 1, obs.y as usize + 1, OBSTACLE);
             }
         }
     }
 
-    fn draw_bullets(&mut self) {
+    fn draw_bullets(&self, screen: &mut FrameBuffer<W, H>) {
         for i in 0..self.bullet_count {
             let bullet = self.bullets[i];
-            if bullet.y >= 0 && bullet.y < SCREEN_HEIGHT as i8 {
-                self.screen
-                    .set(bullet.x as usize, bullet.y as usize, RED_IDX);
+            if bullet.y >= 0 && bullet.y < H as i8 {
+                screen.set(bullet.x as usize, bullet.y as usize, HAZARD);
             }
         }
     }
 
-    fn draw_score(&mut self) {
+    fn draw_score(&self, screen: &mut FrameBuffer<W, H>) {
         let score = self.cars_destroyed;
 
-        // Draw left digit (tens)
-        let tens = score / 10;
-        let tens_figure = DIGITS.wrapping_at(tens);
-        // Add extra space for digit one
-        let tens_x = if tens == 1 { 1 } else { 0 };
-        self.screen.draw_figure(tens_x, 0, tens_figure, YELLOW_IDX);
-
-        // Draw right digit (ones)
-        let ones = score % 10;
-        let ones_figure = DIGITS.wrapping_at(ones);
-        // Add extra space for digit one
-        let ones_x = if ones == 1 { 6 } else { 5 };
-        self.screen.draw_figure(ones_x, 0, ones_figure, YELLOW_IDX);
+        if score < 100 {
+            // Draw left digit (tens)
+            let tens = (score / 10) as u8;
+            let tens_figure = DIGITS.wrapping_at(tens);
+            // Add extra space for digit one
+            let tens_x = if tens == 1 { 1 } else { 0 };
+            screen.draw_figure(tens_x, 0, tens_figure, HUD);
+
+            // Draw right digit (ones)
+            let ones = (score % 10) as u8;
+            let ones_figure = DIGITS.wrapping_at(ones);
+            // Add extra space for digit one
+            let ones_x = if ones == 1 { 6 } else { 5 };
+            screen.draw_figure(ones_x, 0, ones_figure, HUD);
+        } else {
+            self.score_widget.draw(screen, score, HUD);
+        }
 
         // Draw vertical line of lives in the middle
         for y in 0..self.lives {
-            self.screen.set(3, y as usize, GREEN_IDX);
+            screen.set(3, y as usize, HUD_ALT);
         }
 
-        // Draw bullet count to the right of lives in pink
+        // Draw bullet count to the right of lives
         for y in 0..self.max_bullets {
-            self.screen.set(4, y as usize, PINK_IDX);
+            screen.set(4, y as usize, HAZARD);
         }
     }
 
-    async fn game_over(&mut self, mut leds: [RGB8; 256]) {
-        for _ in 0..3 {
-            self.screen.clear();
-            self.screen.render(&mut leds);
-            self.display.write(&leds).await;
-            self.timer.sleep_millis(200).await;
+    fn step(&mut self, input: InputSnapshot) {
+        // Fire bullet on button press
+        if input.joystick && self.bullet_count < self.bullets.len() && self.max_bullets > 0 {
+            self.bullets[self.bullet_count] = Dot::new(self.car_pos.x, self.car_pos.y - 4);
+            self.bullet_count += 1;
+            self.max_bullets -= 1; // Decrement available bullets when firing
+            raise(&mut self.sound, Sfx::Fire);
+        }
+
+        self.spawn_obstacles();
+        self.spawn_bullet_powerup();
 
-            self.draw_score();
-            self.screen.render(&mut leds);
-            self.display.write(&leds).await;
-            self.timer.sleep_millis(200).await;
+        if self.steer_pace.advance(1) {
+            // Move car horizontally
+            if input.x != 0 {
+                let new_x = self.car_pos.x + input.x;
+                if new_x >= 1 && new_x <= W as i8 - 2 {
+                    self.car_pos.x = new_x;
+                }
+            }
         }
 
-        // Wait for button press
-        while !self.controller.joystick_was_pressed() {
-            self.timer.sleep_millis(50).await;
+        if self.road_pace.advance(self.speed) {
+            // Move car vertically
+            if input.y != 0 {
+                let new_y = self.car_pos.y + input.y;
+                if new_y >= 3 && new_y < H as i8 {
+                    self.car_pos.y = new_y;
+                }
+            }
+
+            self.update_obstacles();
+            self.update_road();
         }
-    }
 
-    fn road_should_update(&mut self) -> bool {
-        self.update_step = (self.update_step + 1) % UPDATE_STEP_SIZE;
-        self.update_step % ROAD_UPDATE_STEP_SIZE == 0
-    }
-    fn can_move_car_horizontally(&mut self) -> bool {
-        self.update_step % (ROAD_UPDATE_STEP_SIZE / 4) == 0
-    }
+        // Update game state
+        if self.traffic_pace.advance(self.speed) {
+            self.update_bullet_powerup();
+            self.update_racing_cars();
+        }
+        self.update_bullets();
+        self.check_collisions();
 
-    fn should_update(&mut self) -> bool {
-        self.update_step == 0
+        // Check game over
+        if self.lives == 0 {
+            self.game_over = Some(0);
+            raise(&mut self.sound, Sfx::GameOver);
+        }
     }
 }
 
-impl<'a, D: LedDisplay, C: GameController, T: Timer> Game for RacesGame<'a, D, C, T> {
-    async fn run(&mut self) {
-        let mut leds = [RGB8::new(0, 0, 0); 256];
-
-        loop {
-            // Fire bullet on button press
-            if self.controller.joystick_was_pressed()
-                && self.bullet_count < self.bullets.len()
-                && self.max_bullets > 0
-            {
-                self.bullets[self.bullet_count] = Dot::new(self.car_pos.x, self.car_pos.y - 4);
-                self.bullet_count += 1;
-                self.max_bullets -= 1; // Decrement available bullets when firing
+impl<const W: usize, const H: usize> Game<W, H> for RacesGame<W, H> {
+    const MIN_WIDTH: usize = 8;
+    const MIN_HEIGHT: usize = 16;
+    const TICK_MILLIS: u64 = 20;
+
+    fn update(&mut self, input: InputSnapshot) -> Option<GameOutcome> {
+        self.score_widget.tick();
+        match self.game_over {
+            // Wait for button press once the score stopped blinking
+            Some(ticks) if ticks >= 2 * BLINKS * BLINK_TICKS && input.joystick => {
+                Some(self.outcome(EndReason::GameOver))
             }
-
-            self.spawn_obstacles();
-            self.spawn_bullet_powerup();
-            // Handle joystick input
-            let x = self.controller.read_x().await;
-            let y = self.controller.read_y().await;
-
-            if self.can_move_car_horizontally() {
-                // Move car horizontally
-                if x != 0 {
-                    let new_x = self.car_pos.x + x;
-                    if new_x >= 1 && new_x <= SCREEN_WIDTH as i8 - 2 {
-                        self.car_pos.x = new_x;
-                    }
-                }
+            Some(ticks) => {
+                self.game_over = Some(ticks.saturating_add(1));
+                None
+            }
+            None => {
+                self.step(input);
+                None
             }
+        }
+    }
 
-            if self.road_should_update() {
-                // Move car vertically
-                if y != 0 {
-                    let new_y = self.car_pos.y + y;
-                    if new_y >= 3 && new_y < SCREEN_HEIGHT as i8 {
-                        self.car_pos.y = new_y;
-                    }
-                }
+    fn outcome(&self, reason: EndReason) -> GameOutcome {
+        GameOutcome {
+            reason,
+            score: self.cars_destroyed,
+            count: self.cars_destroyed,
+            ..Default::default()
+        }
+    }
 
-                self.update_obstacles();
-                self.update_road();
-            }
+    fn take_sound(&mut self) -> Option<Sfx> {
+        self.sound.take()
+    }
 
-            // Update game state
-            if self.should_update() {
-                self.update_bullet_powerup();
-                self.update_racing_cars();
+    fn draw(&self, screen: &mut FrameBuffer<W, H>) {
+        if let Some(ticks) = self.game_over {
+            if ticks >= 2 * BLINKS * BLINK_TICKS || !(ticks / BLINK_TICKS).is_multiple_of(2) {
+                self.draw_score(screen);
             }
-            self.update_bullets();
-            self.check_collisions();
+            return;
+        }
 
-            // Check game over
-            if self.lives == 0 {
-                self.game_over(leds).await;
-                break;
-            }
+        self.draw_road(screen);
+        self.draw_obstacles(screen);
+        self.draw_bullet_powerup(screen);
+        self.draw_bullets(screen);
+        self.draw_racing_cars(screen);
+        self.draw_car(screen);
+        self.draw_score(screen);
+    }
+}
+
+/// Rows between the car and the next obstacle in its lane the bot is happy
+/// with
+const BOT_CLEARANCE: i8 = 4;
+
+/// Computer player dodging obstacles, chasing the racing car while it has
+/// bullets left and the bullet powerup otherwise
+#[derive(Default, Copy, Clone, Debug)]
+pub struct RacesBot;
+
+impl RacesBot {
+    /// Rows from the car at column `x` to the first obstacle coming its way,
+    /// at most `BOT_CLEARANCE`
+    fn clearance<const W: usize, const H: usize>(game: &RacesGame<W, H>, x: i8) -> i8 {
+        let car_y = game.car_pos.y;
+        game.obstacles[..game.obstacle_count]
+            .iter()
+            .filter(|obs| obs.x + 1 >= x - 1 && obs.x <= x + 1 && obs.y <= car_y)
+            .map(|obs| car_y - 3 - (obs.y + 1))
+            .fold(BOT_CLEARANCE, i8::min)
+    }
+}
+
+impl<const W: usize, const H: usize> Bot<RacesGame<W, H>> for RacesBot {
+    fn input(&mut self, game: &RacesGame<W, H>) -> InputSnapshot {
+        if let Some(ticks) = game.game_over {
+            return InputSnapshot {
+                joystick: ticks >= 2 * BLINKS * BLINK_TICKS,
+                ..Default::default()
+            };
+        }
+        let car = game.car_pos;
+        let racing_car = game.racing_cars[0];
+        let chasing = game.racing_car_health > 0 && game.max_bullets > 0 && racing_car.y < car.y;
+        let goal = if chasing {
+            racing_car.x
+        } else {
+            game.bullet_powerup.map_or(car.x, |powerup| powerup.x)
+        };
 
-            // Draw everything
-            self.screen.clear();
-            self.draw_road();
-            self.draw_obstacles();
-            self.draw_bullet_powerup();
-            self.draw_bullets();
-            self.draw_racing_cars();
-            self.draw_car();
-            self.draw_score();
-
-            // Update display
-            self.screen.render(&mut leds);
-            self.display.write(&leds).await;
-
-            self.timer.sleep_millis(20).await;
+        // The clearest lane, the one closest to the goal among equally clear
+        // ones
+        let target = (1..=W as i8 - 2)
+            .max_by_key(|&x| (Self::clearance(game, x), -(x - goal).abs()))
+            .unwrap_or(car.x);
+        InputSnapshot {
+            x: (target - car.x).signum(),
+            joystick: chasing && car.x == racing_car.x && game.bullet_count == 0,
+            ..Default::default()
         }
     }
 }
@@ -1,35 +1,38 @@
-use smart_leds::RGB8;
-
 use crate::{
-    common::{
-        Dot, FrameBuffer, Game, GameController, LedDisplay, Prng, Timer, DARK_GREEN_IDX, GREEN_IDX,
-        LIGHT_GREEN_IDX, PINK_IDX, RED_IDX, SCREEN_HEIGHT, SCREEN_WIDTH,
-    },
-    digits::DIGITS,
+    audio::{raise, Sfx},
+    bot::Bot,
+    common::{Dot, EndReason, FrameBuffer, Game, GameOutcome, InputSnapshot, Prng},
+    hud::ScoreWidget,
+    scheduler::Cadence,
+    settings::Settings,
+    theme::{DELIMITER, HUD, PICKUP, PLAYER, PLAYER_HEAD, PLAYER_TAIL},
 };
 
-pub struct SnakeGame<'a, D, C, T> {
-    screen: FrameBuffer,
-    display: &'a mut D,
-    controller: &'a mut C,
-    timer: &'a T,
+/// Progress between two moves, the snake makes 1 per tick at normal speed
+const STEP: u16 = 30;
+/// Ticks the snake stays on (and off) while blinking after game over
+const BLINK_TICKS: u16 = 10;
+const BLINKS: u16 = 3;
 
+pub struct SnakeGame<const W: usize, const H: usize> {
     body: [Dot; 256],
     body_len: usize,
     direction: Dot,
     next_direction: Dot,
     apple: Dot,
     prng: Prng,
-    score: u8,
+    score: u32,
+    start_level: u8,
+    score_widget: ScoreWidget,
+    pace: Cadence,
+    sound: Option<Sfx>,
+    /// Ticks since the game was lost
+    game_over: Option<u16>,
 }
 
-impl<'a, D: LedDisplay, C: GameController, T: Timer> SnakeGame<'a, D, C, T> {
-    pub fn new(prng: Prng, display: &'a mut D, controller: &'a mut C, timer: &'a T) -> Self {
+impl<const W: usize, const H: usize> SnakeGame<W, H> {
+    pub fn new(prng: Prng, settings: &Settings) -> Self {
         let mut game = Self {
-            screen: FrameBuffer::new(),
-            display,
-            controller,
-            timer,
             body: [Dot::new(0, 0); 256],
             body_len: 3,
             direction: Dot::new(1, 0),
@@ -37,12 +40,18 @@ impl<'a, D: LedDisplay, C: GameController, T: Timer> SnakeGame<'a, D, C, T> {
             apple: Dot::new(0, 0),
             prng,
             score: 0,
+            start_level: settings.start_level,
+            score_widget: ScoreWidget::new(0, 0, W).with_speed(8).zero_padded(),
+            pace: Cadence::due(STEP),
+            sound: None,
+            game_over: None,
         };
 
         // Initialize snake body
-        game.body[0] = Dot::new(3, 15);
-        game.body[1] = Dot::new(2, 15);
-        game.body[2] = Dot::new(1, 15);
+        let row = H as i8 / 2 - 1;
+        game.body[0] = Dot::new(3, row);
+        game.body[1] = Dot::new(2, row);
+        game.body[2] = Dot::new(1, row);
 
         game.respawn_apple();
         game
@@ -50,11 +59,8 @@ impl<'a, D: LedDisplay, C: GameController, T: Timer> SnakeGame<'a, D, C, T> {
 
     fn respawn_apple(&mut self) {
         loop {
-            let x = self.prng.next_range(SCREEN_WIDTH as u8) as i8;
-            let y = self
-                .prng
-                .next_range(SCREEN_HEIGHT as u8)
-                .clamp(6, SCREEN_HEIGHT as u8) as i8;
+            let x = self.prng.next_range(W as u8) as i8;
+            let y = self.prng.next_range(H as u8).clamp(6, H as u8) as i8;
             let new_apple = Dot::new(x, y);
 
             // Check if apple spawns on snake body
@@ -78,7 +84,7 @@ impl<'a, D: LedDisplay, C: GameController, T: Timer> SnakeGame<'a, D, C, T> {
             self.direction = self.next_direction;
         }
         let head = self.body[0];
-        let new_head = head.move_wrap(self.direction);
+        let new_head = head.move_wrap::<W, H>(self.direction);
 
         // Check collision with self
         for i in 0..self.body_len {
@@ -101,103 +107,189 @@ impl<'a, D: LedDisplay, C: GameController, T: Timer> SnakeGame<'a, D, C, T> {
             }
             self.score += 1;
             self.respawn_apple();
+            raise(&mut self.sound, Sfx::Apple);
         }
 
         true
     }
 
-    fn draw_snake(&mut self) {
+    fn draw_snake(&self, screen: &mut FrameBuffer<W, H>) {
         for i in 0..self.body_len {
             let dot = self.body[i];
             let color = match i {
-                0 => LIGHT_GREEN_IDX,
-                i if i == self.body_len - 1 => DARK_GREEN_IDX,
-                _ => GREEN_IDX,
+                0 => PLAYER_HEAD,
+                i if i == self.body_len - 1 => PLAYER_TAIL,
+                _ => PLAYER,
             };
-            self.screen.set(dot.x as usize, dot.y as usize, color);
+            screen.set(dot.x as usize, dot.y as usize, color);
         }
     }
 
-    fn draw_score(&mut self) {
-        let score_display = (self.score % 100) as usize;
-        let tens = score_display / 10;
-        let ones = score_display % 10;
-
-        self.screen.draw_figure(0, 0, &DIGITS[tens], GREEN_IDX);
-        self.screen.draw_figure(4, 0, &DIGITS[ones], GREEN_IDX);
-        for x in 0..SCREEN_WIDTH {
-            self.screen.set(x, 5, PINK_IDX);
+    fn draw_score(&self, screen: &mut FrameBuffer<W, H>) {
+        self.score_widget.draw(screen, self.score, HUD);
+        for x in 0..W {
+            screen.set(x, 5, DELIMITER);
         }
     }
 
-    async fn game_over(&mut self, mut leds: [RGB8; 256]) {
-        for _ in 0..3 {
-            self.screen.clear();
-            self.screen.render(&mut leds);
-            self.display.write(&leds).await;
-            self.timer.sleep_millis(200).await;
-
-            self.draw_snake();
-            self.draw_score();
-            self.screen.render(&mut leds);
-            self.display.write(&leds).await;
-            self.timer.sleep_millis(200).await;
+    fn step(&mut self, input: InputSnapshot) {
+        let direction = Dot::new(input.x, input.y).to_direction();
+        if !direction.is_zero() {
+            self.next_direction = direction;
         }
 
-        // Wait for button press
-        while !self.controller.joystick_was_pressed() {
-            self.timer.sleep_millis(50).await;
+        let mut speedup = if self.direction == direction {
+            5
+        } else {
+            // Reset to normal speed when no direction is pressed
+            1
+        };
+        // Adjust the snake's speed based on the level, one more every 10
+        // apples, up to a move every tick
+        let level = self.start_level as u32 + self.score / 10;
+        speedup += level.min(STEP as u32) as u16;
+
+        if self.pace.advance(speedup) && !self.move_forward() {
+            self.game_over = Some(0);
+            raise(&mut self.sound, Sfx::GameOver);
         }
     }
 }
 
-impl<'a, D: LedDisplay, C: GameController, T: Timer> Game for SnakeGame<'a, D, C, T> {
-    async fn run(&mut self) {
-        let mut leds = [RGB8::new(0, 0, 0); 256];
-        let mut step = 30;
-        let mut speedup;
+impl<const W: usize, const H: usize> Game<W, H> for SnakeGame<W, H> {
+    const MIN_WIDTH: usize = 8;
+    const MIN_HEIGHT: usize = 16;
+    const TICK_MILLIS: u64 = 20;
 
-        loop {
-            // Handle joystick input
-            let x = self.controller.read_x().await;
-            let y = self.controller.read_y().await;
-            let direction = Dot::new(x, y).to_direction();
-            if !direction.is_zero() {
-                self.next_direction = direction;
+    fn update(&mut self, input: InputSnapshot) -> Option<GameOutcome> {
+        self.score_widget.tick();
+        match self.game_over {
+            // Wait for button press once the snake stopped blinking
+            Some(ticks) if ticks >= 2 * BLINKS * BLINK_TICKS && input.joystick => {
+                Some(self.outcome(EndReason::GameOver))
             }
-
-            if self.direction == direction {
-                speedup = 5;
-            } else {
-                // Reset to normal speed when no direction is pressed
-                speedup = 1;
+            Some(ticks) => {
+                self.game_over = Some(ticks.saturating_add(1));
+                None
             }
-            // Adjust the snake's speed based on the score.
-            speedup += self.score / 10;
-            if self.score > 99 {
-                self.score = 0;
+            None => {
+                self.step(input);
+                None
             }
+        }
+    }
 
-            if step >= 30 {
-                step = 0;
-                // Move snake
-                if !self.move_forward() {
-                    self.game_over(leds).await;
-                    break;
+    fn outcome(&self, reason: EndReason) -> GameOutcome {
+        GameOutcome {
+            reason,
+            score: self.score,
+            count: self.score,
+            ..Default::default()
+        }
+    }
+
+    fn take_sound(&mut self) -> Option<Sfx> {
+        self.sound.take()
+    }
+
+    fn draw(&self, screen: &mut FrameBuffer<W, H>) {
+        if let Some(ticks) = self.game_over {
+            if ticks >= 2 * BLINKS * BLINK_TICKS || !(ticks / BLINK_TICKS).is_multiple_of(2) {
+                self.draw_snake(screen);
+                self.draw_score(screen);
+            }
+            return;
+        }
+
+        self.draw_score(screen);
+        self.draw_snake(screen);
+        // Draw apple
+        screen.set(self.apple.x as usize, self.apple.y as usize, PICKUP);
+    }
+}
+
+const DIRECTIONS: [Dot; 4] = [
+    Dot { x: 1, y: 0 },
+    Dot { x: 0, y: 1 },
+    Dot { x: -1, y: 0 },
+    Dot { x: 0, y: -1 },
+];
+
+/// Computer player taking the shortest way to the apple around its body, or
+/// any way that doesn't bite if there is none
+#[derive(Default, Copy, Clone, Debug)]
+pub struct SnakeBot;
+
+impl SnakeBot {
+    /// Direction of the first move on the shortest way from the head to the
+    /// apple, a breadth-first search over the wrapping board
+    fn path<const W: usize, const H: usize>(game: &SnakeGame<W, H>) -> Option<Dot> {
+        let mut first = [[None::<Dot>; W]; H];
+        // The tail moves out of the way, the rest of the body is blocked
+        let mut blocked = [[false; W]; H];
+        for dot in &game.body[..game.body_len - 1] {
+            blocked[dot.y as usize][dot.x as usize] = true;
+        }
+
+        // Every free cell is queued at most once, the head is blocked
+        let mut queue = [[game.body[0]; W]; H];
+        let (mut head, mut tail) = (0, 1);
+        while head < tail {
+            let dot = queue[head / W][head % W];
+            head += 1;
+            for direction in DIRECTIONS {
+                if dot == game.body[0] && direction.is_opposite(&game.direction) {
+                    continue;
+                }
+                let next = dot.move_wrap::<W, H>(direction);
+                let (x, y) = (next.x as usize, next.y as usize);
+                if blocked[y][x] || first[y][x].is_some() {
+                    continue;
                 }
+                let step = if dot == game.body[0] {
+                    direction
+                } else {
+                    first[dot.y as usize][dot.x as usize]?
+                };
+                if next == game.apple {
+                    return Some(step);
+                }
+                first[y][x] = Some(step);
+                queue[tail / W][tail % W] = next;
+                tail += 1;
+            }
+        }
+        None
+    }
+}
 
-                // Draw and update display
-                self.screen.clear();
-                self.draw_score();
-                self.draw_snake();
-                // Draw apple
-                self.screen
-                    .set(self.apple.x as usize, self.apple.y as usize, RED_IDX);
-                self.screen.render(&mut leds);
-                self.display.write(&leds).await;
+impl<const W: usize, const H: usize> Bot<SnakeGame<W, H>> for SnakeBot {
+    fn input(&mut self, game: &SnakeGame<W, H>) -> InputSnapshot {
+        if let Some(ticks) = game.game_over {
+            return InputSnapshot {
+                joystick: ticks >= 2 * BLINKS * BLINK_TICKS,
+                ..Default::default()
+            };
+        }
+        let head = game.body[0];
+        let safe = |direction: &Dot| {
+            let next = head.move_wrap::<W, H>(*direction);
+            !direction.is_opposite(&game.direction)
+                && !game.body[..game.body_len - 1].contains(&next)
+        };
+        let direction = Self::path(game)
+            .or_else(|| Some(game.direction).filter(safe))
+            .or_else(|| DIRECTIONS.into_iter().find(safe))
+            .unwrap_or(game.direction);
+        // Holding the current direction would speed the snake up
+        if direction == game.direction {
+            InputSnapshot::default()
+        } else {
+            InputSnapshot {
+                x: direction.x,
+                y: direction.y,
+                ..Default::default()
             }
-            step += speedup;
-            self.timer.sleep_millis(20).await;
         }
     }
 }
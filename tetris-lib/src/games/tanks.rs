@@ -1,13 +1,12 @@
-use core::default::Default;
-use smart_leds::RGB8;
+use crate::audio::{raise, Sfx};
+use crate::bot::Bot;
+use crate::common::{Dot, EndReason, FrameBuffer, Game, GameOutcome, InputSnapshot, Prng};
 
-use crate::common::{
-    Dot, FrameBuffer, Game, GameController, LedDisplay, Prng, Timer, BRICK_IDX, COLORS, GREEN_IDX,
-    PINK_IDX, RED_IDX, SCREEN_HEIGHT, SCREEN_WIDTH,
-};
-
-use crate::digits::DIGITS;
 use crate::figure::{Figure, TANK};
+use crate::hud::ScoreWidget;
+use crate::scheduler::Cadence;
+use crate::settings::Settings;
+use crate::theme::{DELIMITER, ENEMY, HAZARD, HUD, HUD_ALT, PLAYER, ROLE_COUNT};
 
 #[derive(Clone, Copy)]
 struct Missile {
@@ -28,7 +27,11 @@ impl Missile {
     }
 
     fn visible(&self) -> bool {
-        self.x >= 0 && self.x < 8 && self.y >= 0 && self.y < 32
+        self.x >= 0 && self.y >= 0
+    }
+
+    fn inside<const W: usize, const H: usize>(&self) -> bool {
+        self.visible() && (self.x as usize) < W && (self.y as usize) < H
     }
 
     fn hide(&mut self) {
@@ -110,11 +113,11 @@ impl Tank {
         }
     }
 
-    fn move_missiles(&mut self) {
+    fn move_missiles<const W: usize, const H: usize>(&mut self) {
         for m in &mut self.missiles {
             if m.visible() {
                 m.move_();
-                if !m.visible() {
+                if !m.inside::<W, H>() {
                     m.hide();
                 }
             }
@@ -173,42 +176,48 @@ impl Tank {
     }
 }
 
+/// Enemies act once every `ROUND` ticks, sooner as the score grows
+const ROUND: u16 = 10;
+/// Ticks between two confetti pixels after game over
+const CONFETTI_TICKS: u16 = 2;
+
 // Tanks game implementation
-pub struct TanksGame<'a, D, C, T> {
-    screen: FrameBuffer,
-    display: &'a mut D,
-    controller: &'a mut C,
-    timer: &'a T,
+pub struct TanksGame<const W: usize, const H: usize> {
+    /// Score, lives and the delimiter; tanks can't drive over it
+    hud: FrameBuffer<W, H>,
 
     tank: Tank,
     enemies: [Tank; 4],
     enemy_count: usize,
-    score: u8,
+    score: u32,
+    start_level: u8,
+    score_widget: ScoreWidget,
     prng: Prng,
 
     test_mode: bool,
+    ai_pace: Cadence,
+    sound: Option<Sfx>,
+    /// Ticks since the game was lost
+    game_over: Option<u16>,
 }
 
-impl<'a, D: LedDisplay, C: GameController, T: Timer> TanksGame<'a, D, C, T> {
-    pub fn new(
-        prng: Prng,
-        display: &'a mut D,
-        controller: &'a mut C,
-        timer: &'a T,
-        test_mode: bool,
-    ) -> Self {
+impl<const W: usize, const H: usize> TanksGame<W, H> {
+    pub fn new(prng: Prng, settings: &Settings, test_mode: bool) -> Self {
         Self {
-            screen: FrameBuffer::new(),
-            display,
-            controller,
-            timer,
+            hud: FrameBuffer::new(),
 
-            tank: Tank::new(Dot::new(3, 16), -1, 3),
+            tank: Tank::new(Dot::new(W as i8 / 2 - 1, H as i8 / 2), -1, 3),
             enemies: [Tank::new(Dot::new(0, 0), 0, 1); 4],
             enemy_count: 0,
             score: 0,
+            start_level: settings.start_level,
+            // Next to the lives in the last column
+            score_widget: ScoreWidget::new(0, 0, W - 1).with_speed(2).zero_padded(),
             prng,
             test_mode,
+            ai_pace: Cadence::due(ROUND),
+            sound: None,
+            game_over: None,
         }
     }
 
@@ -220,7 +229,7 @@ impl<'a, D: LedDisplay, C: GameController, T: Timer> TanksGame<'a, D, C, T> {
         exclude_player: bool,
         exclude_enemy_idx: Option<usize>,
     ) -> bool {
-        self.screen.collides(x, y, &tank.figure)
+        self.hud.collides(x, y, &tank.figure)
             || (!exclude_player
                 && !self.tank.is_dead()
                 && self.tank.overlaps_figure(x, y, &tank.figure))
@@ -245,11 +254,12 @@ impl<'a, D: LedDisplay, C: GameController, T: Timer> TanksGame<'a, D, C, T> {
             return;
         }
 
+        let (right, bottom) = Self::max_tank_pos();
         let spawns = [
-            Dot::new(0, 6),  // top-left (moved down to avoid delimiter)
-            Dot::new(5, 6),  // top-right (moved down to avoid delimiter)
-            Dot::new(0, 29), // bottom-left
-            Dot::new(5, 29), // bottom-right
+            Dot::new(0, 6),          // top-left (moved down to avoid delimiter)
+            Dot::new(right, 6),      // top-right (moved down to avoid delimiter)
+            Dot::new(0, bottom),     // bottom-left
+            Dot::new(right, bottom), // bottom-right
         ];
 
         // Find available spawn indices and randomly select one
@@ -330,7 +340,8 @@ impl<'a, D: LedDisplay, C: GameController, T: Timer> TanksGame<'a, D, C, T> {
         let new_pos = enemy.pos.move_by(direction);
 
         // Check screen boundaries first
-        if new_pos.x < 0 || new_pos.x > 5 || new_pos.y < 6 || new_pos.y > 29 {
+        let (right, bottom) = Self::max_tank_pos();
+        if new_pos.x < 0 || new_pos.x > right || new_pos.y < 6 || new_pos.y > bottom {
             enemy.rotate(&Dot::new(0, 0));
             return;
         }
@@ -396,60 +407,65 @@ impl<'a, D: LedDisplay, C: GameController, T: Timer> TanksGame<'a, D, C, T> {
         }
     }
 
+    /// Bottom-right position a tank can take without leaving the screen
+    fn max_tank_pos() -> (i8, i8) {
+        let size = TANK.width().max(TANK.height()) as i8;
+        (W as i8 - size, H as i8 - size)
+    }
+
     fn move_missiles(&mut self) {
-        self.tank.move_missiles();
-        self.enemies.iter_mut().for_each(|e| e.move_missiles());
+        self.tank.move_missiles::<W, H>();
+        self.enemies
+            .iter_mut()
+            .for_each(|e| e.move_missiles::<W, H>());
     }
 
-    fn draw_player(&mut self) {
-        self.screen.draw_figure(
-            self.tank.pos.x,
-            self.tank.pos.y,
-            &self.tank.figure,
-            GREEN_IDX,
-        );
+    fn draw_player(&self, screen: &mut FrameBuffer<W, H>) {
+        screen.draw_figure(self.tank.pos.x, self.tank.pos.y, &self.tank.figure, PLAYER);
     }
 
-    fn draw_enemy(&mut self, idx: usize) {
+    fn draw_enemy(&self, screen: &mut FrameBuffer<W, H>, idx: usize) {
         let enemy = &self.enemies[idx];
-        self.screen
-            .draw_figure(enemy.pos.x, enemy.pos.y, &enemy.figure, BRICK_IDX);
+        screen.draw_figure(enemy.pos.x, enemy.pos.y, &enemy.figure, ENEMY);
         for m in &enemy.missiles {
             if m.visible() {
-                self.screen.set(m.x as usize, m.y as usize, RED_IDX);
+                screen.set(m.x as usize, m.y as usize, HAZARD);
             }
         }
     }
 
-    fn draw_player_missiles(&mut self) {
+    fn draw_player_missiles(&self, screen: &mut FrameBuffer<W, H>) {
         for m in &self.tank.missiles {
             if m.visible() {
-                self.screen.set(m.x as usize, m.y as usize, RED_IDX);
+                screen.set(m.x as usize, m.y as usize, HAZARD);
             }
         }
     }
 
-    fn draw_score_delimiter(&mut self) {
-        for x in 0..SCREEN_WIDTH {
-            self.screen.set(x, 5, PINK_IDX);
+    fn draw_score_delimiter(screen: &mut FrameBuffer<W, H>) {
+        for x in 0..W {
+            screen.set(x, 5, DELIMITER);
         }
     }
 
-    fn draw_score(&mut self) {
-        let score_display = (self.score % 100) as usize;
-        let tens = score_display / 10;
-        let ones = score_display % 10;
-
-        self.screen.draw_figure(0, 0, &DIGITS[tens], GREEN_IDX);
-        self.screen.draw_figure(4, 0, &DIGITS[ones], GREEN_IDX);
+    fn draw_score(&self, screen: &mut FrameBuffer<W, H>) {
+        self.score_widget.draw(screen, self.score, HUD);
     }
 
-    fn draw_lives(&mut self) {
+    fn draw_lives(&self, screen: &mut FrameBuffer<W, H>) {
         for i in 0..self.tank.lives {
-            self.screen.set(7, i as usize, PINK_IDX);
+            screen.set(W - 1, i as usize, HUD_ALT);
         }
     }
 
+    fn update_hud(&mut self) {
+        let mut hud = FrameBuffer::new();
+        self.draw_score(&mut hud);
+        self.draw_lives(&mut hud);
+        Self::draw_score_delimiter(&mut hud);
+        self.hud = hud;
+    }
+
     fn check_collisions(&mut self) {
         for i in 0..self.enemy_count {
             let enemy = &mut self.enemies[i];
@@ -457,6 +473,7 @@ impl<'a, D: LedDisplay, C: GameController, T: Timer> TanksGame<'a, D, C, T> {
                 if m.visible() && self.tank.collides(Dot::new(m.x, m.y)) {
                     self.tank.hit();
                     m.hide();
+                    raise(&mut self.sound, Sfx::Crash);
                 }
             }
         }
@@ -468,6 +485,7 @@ impl<'a, D: LedDisplay, C: GameController, T: Timer> TanksGame<'a, D, C, T> {
                     if enemy.collides(Dot::new(m.x, m.y)) {
                         enemy.hit();
                         m.hide();
+                        raise(&mut self.sound, Sfx::Hit);
                         if enemy.is_dead() {
                             self.score += 1;
                         }
@@ -477,69 +495,141 @@ impl<'a, D: LedDisplay, C: GameController, T: Timer> TanksGame<'a, D, C, T> {
         }
     }
 
-    async fn game_over(&mut self, mut leds: [RGB8; 256]) {
-        while !self.controller.joystick_was_pressed() {
-            let x = self.prng.next_range(SCREEN_WIDTH as u8);
-            let y = self.prng.next_range(SCREEN_HEIGHT as u8);
-            let color = self.prng.next_range(COLORS.len() as u8);
-            self.screen.set(x as usize, y as usize, color);
-            self.screen.render(&mut leds);
-            self.display.write(&leds).await;
-            self.timer.sleep_millis(200).await;
+    fn throw_confetti(&mut self) {
+        let x = self.prng.next_range(W as u8);
+        let y = self.prng.next_range(H as u8);
+        let color = self.prng.next_range(ROLE_COUNT as u8);
+        self.hud.set(x as usize, y as usize, color);
+    }
+
+    fn step(&mut self, input: InputSnapshot) {
+        self.score_widget.tick();
+        self.update_hud();
+
+        if self.tank.is_dead() {
+            self.game_over = Some(0);
+            raise(&mut self.sound, Sfx::GameOver);
+            return;
+        }
+
+        if input.joystick {
+            self.tank.fire();
+            raise(&mut self.sound, Sfx::Fire);
+        }
+
+        let direction = Dot::new(input.x, input.y).to_direction();
+
+        self.move_player(direction);
+        self.move_missiles();
+        self.check_collisions();
+
+        let level = self.start_level as u32 + self.score / 10;
+        let speedup = level.min(ROUND as u32) as u16;
+        if self.ai_pace.advance(1 + speedup) {
+            self.ai();
         }
     }
 }
 
-impl<'a, D: LedDisplay, C: GameController, T: Timer> Game for TanksGame<'a, D, C, T> {
-    async fn run(&mut self)
-    where
-        D: LedDisplay,
-        C: GameController,
-        T: Timer,
-    {
-        let mut leds: [RGB8; 256] = [RGB8::default(); 256];
-        let mut step = 10;
-        let round = 10;
-
-        loop {
-            self.screen.clear();
-            self.draw_score();
-            self.draw_lives();
-            self.draw_score_delimiter();
-
-            if self.tank.is_dead() {
-                self.game_over(leds).await;
-                return;
+impl<const W: usize, const H: usize> Game<W, H> for TanksGame<W, H> {
+    const MIN_WIDTH: usize = 8;
+    const MIN_HEIGHT: usize = 16;
+    const TICK_MILLIS: u64 = 100;
+
+    fn update(&mut self, input: InputSnapshot) -> Option<GameOutcome> {
+        match self.game_over {
+            Some(_) if input.joystick => Some(self.outcome(EndReason::GameOver)),
+            Some(ticks) => {
+                if ticks.is_multiple_of(CONFETTI_TICKS) {
+                    self.throw_confetti();
+                }
+                self.game_over = Some(ticks.wrapping_add(1));
+                None
             }
-
-            if self.controller.joystick_was_pressed() {
-                self.tank.fire();
+            None => {
+                self.step(input);
+                None
             }
+        }
+    }
 
-            let x_input = self.controller.read_x().await;
-            let y_input = self.controller.read_y().await;
-            let direction = Dot::new(x_input, y_input).to_direction();
+    fn outcome(&self, reason: EndReason) -> GameOutcome {
+        GameOutcome {
+            reason,
+            score: self.score,
+            count: self.score,
+            ..Default::default()
+        }
+    }
 
-            self.move_player(direction);
-            self.move_missiles();
-            self.check_collisions();
+    fn take_sound(&mut self) -> Option<Sfx> {
+        self.sound.take()
+    }
 
-            self.draw_player();
-            for i in 0..self.enemy_count {
-                self.draw_enemy(i);
-            }
-            self.draw_player_missiles();
+    fn draw(&self, screen: &mut FrameBuffer<W, H>) {
+        screen.copy_from(&self.hud);
+        if self.game_over.is_some() {
+            return;
+        }
 
-            let speedup = self.score / 10;
-            if step >= round {
-                self.ai();
-                step = 0;
-            }
-            step += 1 + speedup;
+        self.draw_player(screen);
+        for i in 0..self.enemy_count {
+            self.draw_enemy(screen, i);
+        }
+        self.draw_player_missiles(screen);
+    }
+}
+
+/// Ticks of confetti the bot watches before confirming game over
+const BOT_GAME_OVER_TICKS: u16 = 30;
+
+/// Computer player lining up with the nearest enemy and firing at it
+#[derive(Default, Copy, Clone, Debug)]
+pub struct TanksBot;
 
-            self.screen.render(&mut leds);
-            self.display.write(&leds).await;
-            self.timer.sleep_millis(100).await;
+impl<const W: usize, const H: usize> Bot<TanksGame<W, H>> for TanksBot {
+    fn input(&mut self, game: &TanksGame<W, H>) -> InputSnapshot {
+        if let Some(ticks) = game.game_over {
+            return InputSnapshot {
+                joystick: ticks >= BOT_GAME_OVER_TICKS,
+                ..Default::default()
+            };
+        }
+        let pos = game.tank.pos;
+        let Some(enemy) = game.enemies[..game.enemy_count]
+            .iter()
+            .filter(|e| !e.is_dead())
+            .min_by_key(|e| (e.pos.x - pos.x).abs() + (e.pos.y - pos.y).abs())
+        else {
+            return InputSnapshot::default();
+        };
+
+        // Missiles leave from the middle of the tank, they hit tanks up to a
+        // pixel off that line
+        let (dx, dy) = (enemy.pos.x - pos.x, enemy.pos.y - pos.y);
+        let (direction, aligned) = if dx.abs() <= 1 {
+            (Dot::new(0, dy.signum()), true)
+        } else if dy.abs() <= 1 {
+            (Dot::new(dx.signum(), 0), true)
+        } else if dx.abs() < dy.abs() {
+            (Dot::new(dx.signum(), 0), false)
+        } else {
+            (Dot::new(0, dy.signum()), false)
+        };
+
+        let facing = game.tank.direction() == direction;
+        let loaded = game.tank.missiles.iter().any(|m| !m.visible());
+        if aligned && facing {
+            InputSnapshot {
+                joystick: loaded,
+                ..Default::default()
+            }
+        } else {
+            InputSnapshot {
+                x: direction.x,
+                y: direction.y,
+                ..Default::default()
+            }
         }
     }
 }
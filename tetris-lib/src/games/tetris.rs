@@ -1,244 +1,1317 @@
-use smart_leds::RGB8;
-
+use crate::audio::{raise, Sfx};
+use crate::bot::Bot;
 use crate::common::{
-    Dot, FrameBuffer, Game, GameController, LedDisplay, Prng, Timer, BLACK_IDX, BLUE_IDX,
-    BRICK_IDX, GREEN_IDX, LIGHT_BLUE_IDX, PINK_IDX, RED_IDX, SCREEN_HEIGHT, SCREEN_WIDTH,
-    YELLOW_IDX,
+    EndReason, FrameBuffer, Game, GameOutcome, InputSnapshot, Pixel, Prng, BLACK_IDX,
 };
 use crate::figure::{Figure, TETRAMINO};
+use crate::hud::ScoreWidget;
+use crate::scheduler::Cadence;
+use crate::settings::{Randomizer, Settings, TetrisMode};
+use crate::theme::{DELIMITER, HUD, HUD_ALT, PIECES, WALL};
+
+/// Rows at the top taken by the score and the delimiter
+const HUD_ROWS: usize = 6;
+const INIT_Y: i8 = 6;
+/// Ticks between two rows of fall at the slowest speed
+const FALL_TICKS: u16 = 11;
+/// Ticks the last tetramino stays on (and off) while blinking after game over
+const BLINK_TICKS: u16 = 10;
+/// Brightness of the ghost piece, out of 255 of its tetramino's color
+const GHOST_LEVEL: u8 = 64;
+/// Ticks full rows flash before they are cleared
+const CLEAR_TICKS: u16 = 12;
+/// Ticks full rows stay on (and off) while flashing
+const FLASH_TICKS: u16 = 3;
+/// Points for clearing 1 to 4 rows at once at level 0, the guideline's over a
+/// hundred. Every level multiplies them by one more.
+const LINE_POINTS: [u32; 5] = [0, 1, 3, 5, 8];
+/// Level that ends a marathon
+const MARATHON_LEVEL: u32 = 15;
+/// Lines a sprint takes
+const SPRINT_LINES: u32 = 40;
+/// Length of an ultra game
+const ULTRA_MILLIS: u32 = 120_000;
+/// Time between two garbage rows in survival
+const GARBAGE_MILLIS: u32 = 8_000;
+/// Moves and turns restarting the lock delay of a tetramino on a row, so that
+/// it can't be kept from locking forever
+const MAX_LOCK_RESETS: u8 = 15;
+
+/// Tetraminos known in advance, the most the HUD can show
+const QUEUE_LEN: usize = 6;
+/// Room for a tetramino in the queue in the HUD: the widest one and a gap
+/// on the left
+const SLOT_WIDTH: usize = 5;
+/// Room for a tetramino in the queue in the HUD: the highest one and a gap
+/// below
+const SLOT_HEIGHT: usize = 3;
+/// Score area the queue leaves, two digits
+const SCORE_WIDTH: usize = 7;
+
+/// Super Rotation System: tetraminos turn around the center of a square box,
+/// given as its size and where each tetramino sits in it as it spawns
+const SRS_BOXES: [(i8, i8, i8); 7] = [
+    (4, 0, 1),
+    (2, 0, 0),
+    (3, 0, 0),
+    (3, 0, 0),
+    (3, 0, 0),
+    (3, 0, 0),
+    (3, 0, 0),
+];
+/// SRS wall kicks of a clockwise turn from each rotation state, tried in
+/// order, x to the right and y up. Turning back tries them the other way.
+const SRS_KICKS: [[(i8, i8); 5]; 4] = [
+    [(0, 0), (-1, 0), (-1, 1), (0, -2), (-1, -2)],
+    [(0, 0), (1, 0), (1, -1), (0, 2), (1, 2)],
+    [(0, 0), (1, 0), (1, 1), (0, -2), (1, -2)],
+    [(0, 0), (-1, 0), (-1, -1), (0, 2), (-1, 2)],
+];
+/// SRS wall kicks of the I tetramino
+const SRS_I_KICKS: [[(i8, i8); 5]; 4] = [
+    [(0, 0), (-2, 0), (1, 0), (-2, -1), (1, 2)],
+    [(0, 0), (-1, 0), (2, 0), (-1, 2), (2, -1)],
+    [(0, 0), (2, 0), (-1, 0), (2, 1), (-1, -2)],
+    [(0, 0), (1, 0), (-2, 0), (1, -2), (-2, 1)],
+];
+
+/// Where tetramino `idx` sits in its SRS box after `rotation` clockwise
+/// turns from its spawn state
+fn srs_offset(idx: u8, rotation: u8) -> (i8, i8) {
+    let (size, mut x, mut y) = SRS_BOXES[idx as usize % SRS_BOXES.len()];
+    let figure = TETRAMINO.wrapping_at(idx);
+    let (mut width, mut height) = (figure.width() as i8, figure.height() as i8);
+    for _ in 0..rotation {
+        (x, y) = (size - y - height, x);
+        (width, height) = (height, width);
+    }
+    (x, y)
+}
+
+/// Wall kicks of tetramino `idx` turning from `rotation`, screen y down
+fn srs_kicks(idx: u8, rotation: u8, clockwise: bool) -> impl Iterator<Item = (i8, i8)> {
+    let table = if idx == 0 { &SRS_I_KICKS } else { &SRS_KICKS };
+    let (kicks, sign) = if clockwise {
+        (table[rotation as usize], 1)
+    } else {
+        (table[(rotation as usize + 3) % 4], -1)
+    };
+    kicks.into_iter().map(move |(x, y)| (sign * x, -sign * y))
+}
+
+/// Tetraminos to come, drawn by a `Randomizer`
+struct PieceQueue {
+    randomizer: Randomizer,
+    pieces: [u8; QUEUE_LEN],
+    /// Tetraminos of the current bag, the first `bag_left` are still to come
+    bag: [u8; 7],
+    bag_left: usize,
+}
+
+impl PieceQueue {
+    fn new(randomizer: Randomizer, prng: &mut Prng) -> Self {
+        let mut queue = Self {
+            randomizer,
+            pieces: [0; QUEUE_LEN],
+            bag: [0, 1, 2, 3, 4, 5, 6],
+            bag_left: 0,
+        };
+        for i in 0..QUEUE_LEN {
+            queue.pieces[i] = queue.draw(prng);
+        }
+        queue
+    }
+
+    fn draw(&mut self, prng: &mut Prng) -> u8 {
+        match self.randomizer {
+            Randomizer::Classic => prng.next_range(7),
+            Randomizer::Bag => {
+                if self.bag_left == 0 {
+                    // Fisher-Yates shuffle
+                    for i in (1..self.bag.len()).rev() {
+                        let j = prng.next_range(i as u8 + 1) as usize;
+                        self.bag.swap(i, j);
+                    }
+                    self.bag_left = self.bag.len();
+                }
+                self.bag_left -= 1;
+                self.bag[self.bag_left]
+            }
+        }
+    }
 
-use crate::digits::DIGITS;
+    /// Takes the next tetramino and draws a new one for the end of the queue
+    fn pop(&mut self, prng: &mut Prng) -> u8 {
+        let next = self.pieces[0];
+        self.pieces.rotate_left(1);
+        self.pieces[QUEUE_LEN - 1] = self.draw(prng);
+        next
+    }
+
+    /// Tetramino coming after `ahead` others
+    fn peek(&self, ahead: usize) -> u8 {
+        self.pieces[ahead % QUEUE_LEN]
+    }
+}
 
-pub struct TetrisGame<'a, D, C, T> {
-    screen: FrameBuffer,
-    concrete: FrameBuffer,
-    display: &'a mut D,
-    controller: &'a mut C,
-    timer: &'a T,
-    score: u8,
+pub struct TetrisGame<const W: usize, const H: usize> {
+    concrete: FrameBuffer<W, H>,
+    score: u32,
+    /// Rows cleared so far, every 10 make a level
+    lines: u32,
+    mode: TetrisMode,
+    /// Whether to show where the falling tetramino lands
+    ghost: bool,
+    /// Ticks played, line clears included
+    ticks: u32,
+    /// Ticks since the last garbage row in survival
+    garbage_ticks: u32,
+    /// The goal of the mode is reached
+    completed: bool,
+    start_level: u8,
+    score_widget: ScoreWidget,
     prng: Prng,
+
+    x: i8,
+    y: i8,
+    fall: Cadence,
+    sideways: Cadence,
+    curr_idx: u8,
+    curr: Figure,
+    queue: PieceQueue,
+    /// Tetraminos dropped so far, the falling one included
+    pieces: u32,
+    held: Option<u8>,
+    /// Whether the falling tetramino can still be swapped with the held one
+    can_hold: bool,
+    /// Up was pushed on the last tick, a hard drop takes a new push
+    up_held: bool,
+    /// Ticks a landed tetramino stays movable
+    lock_delay: u16,
+    /// Ticks since the falling tetramino landed, `None` while it falls
+    landed: Option<u16>,
+    /// Moves and turns since the falling tetramino reached its lowest row
+    lock_resets: u8,
+    lowest: i8,
+    /// Ticks since the full rows started flashing, the next tetramino comes
+    /// once they are cleared
+    clearing: Option<u16>,
+    sound: Option<Sfx>,
+    /// Ticks since the game was lost
+    game_over: Option<u16>,
 }
 
-impl<'a, D: LedDisplay, C: GameController, T: Timer> TetrisGame<'a, D, C, T> {
-    pub fn new(prng: Prng, display: &'a mut D, controller: &'a mut C, timer: &'a T) -> Self {
+impl<const W: usize, const H: usize> TetrisGame<W, H> {
+    pub fn new(mut prng: Prng, settings: &Settings) -> Self {
+        let mut queue = PieceQueue::new(settings.randomizer, &mut prng);
+        let curr_idx = queue.pop(&mut prng);
         Self {
-            screen: FrameBuffer::new(),
             concrete: FrameBuffer::new(),
-            display,
-            controller,
-            timer,
             score: 0,
+            lines: 0,
+            mode: settings.tetris_mode,
+            ghost: settings.ghost,
+            ticks: 0,
+            garbage_ticks: 0,
+            completed: false,
+            start_level: settings.start_level,
+            score_widget: ScoreWidget::new(0, 0, Self::score_width())
+                .with_speed(3)
+                .zero_padded(),
             prng,
+            x: Self::init_x(),
+            y: INIT_Y,
+            fall: Cadence::new(FALL_TICKS),
+            sideways: Cadence::new(2),
+            curr_idx,
+            curr: TETRAMINO.wrapping_at(curr_idx),
+            queue,
+            pieces: 1,
+            held: None,
+            can_hold: true,
+            up_held: false,
+            lock_delay: (settings.lock_delay_millis as u64 / Self::TICK_MILLIS) as u16,
+            landed: None,
+            lock_resets: 0,
+            lowest: INIT_Y,
+            clearing: None,
+            sound: None,
+            game_over: None,
         }
     }
 
+    fn init_x() -> i8 {
+        W as i8 / 2 - 1
+    }
+
     fn get_tetramino_color(&self, tetramino_idx: u8) -> u8 {
-        match tetramino_idx {
-            0 => LIGHT_BLUE_IDX, // I piece
-            1 => YELLOW_IDX,     // O piece
-            2 => PINK_IDX,       // T piece
-            3 => GREEN_IDX,      // S piece
-            4 => RED_IDX,        // Z piece
-            5 => BLUE_IDX,       // J piece
-            6 => BRICK_IDX,      // L piece
-            _ => RED_IDX,        // Default
+        PIECES[tetramino_idx as usize % PIECES.len()]
+    }
+
+    /// Dim shade of the color of a tetramino
+    fn ghost_pixel(&self, tetramino_idx: u8) -> Pixel {
+        Pixel::Shade(self.get_tetramino_color(tetramino_idx), GHOST_LEVEL)
+    }
+
+    /// Row the falling tetramino would land on if dropped now
+    fn landing_y(&self) -> i8 {
+        let mut y = self.y;
+        while !self.grounded_at(y) {
+            y += 1;
         }
+        y
     }
 
-    fn draw_score(&mut self) {
-        self.score %= 100;
-        let speed = self.score / 10;
-        let score_digit = self.score % 10;
+    /// Columns of the queue in the HUD, as many as leave two digits of the
+    /// score. None fit next to them on narrow panels, where the queue shrinks
+    /// to a column of dots.
+    fn queue_columns() -> usize {
+        (W.saturating_sub(SCORE_WIDTH) / SLOT_WIDTH).min(QUEUE_LEN / 2)
+    }
 
-        let speed_fig = DIGITS.wrapping_at(speed);
-        let score_fig = DIGITS.wrapping_at(score_digit);
+    /// Width of the score area left of the queue
+    fn score_width() -> usize {
+        match Self::queue_columns() {
+            0 => W.saturating_sub(1),
+            columns => W - columns * SLOT_WIDTH,
+        }
+    }
 
-        self.screen.draw_figure(0, 0, speed_fig, GREEN_IDX);
-        self.screen.draw_figure(5, 0, score_fig, GREEN_IDX);
+    /// The queue on panels too narrow for its slots: the tetraminos to come
+    /// as dots of their colors down the last column of the HUD, and the held
+    /// one below them, in the delimiter color once it was swapped in this drop
+    fn draw_queue_dots(&self, screen: &mut FrameBuffer<W, H>) {
+        let x = W - 1;
+        let held_row = HUD_ROWS - 2;
+        for row in 0..held_row {
+            screen.set(x, row, self.get_tetramino_color(self.queue.peek(row)));
+        }
+        if let Some(idx) = self.held {
+            let color = if self.can_hold {
+                self.get_tetramino_color(idx)
+            } else {
+                DELIMITER
+            };
+            screen.set(x, held_row, color);
+        }
+    }
 
-        // Draw horizontal line
-        for x in 0..SCREEN_WIDTH {
-            self.screen.set(x, 5, PINK_IDX);
+    /// The tetraminos to come, top to bottom and left to right after the
+    /// score, and the held one in the last slot: in the alternate HUD color,
+    /// or the delimiter color once it was swapped in this drop
+    fn draw_queue(&self, screen: &mut FrameBuffer<W, H>) {
+        if Self::queue_columns() == 0 {
+            return self.draw_queue_dots(screen);
+        }
+        let rows = HUD_ROWS / SLOT_HEIGHT;
+        let slots = Self::queue_columns() * rows;
+        let draw_slot = |screen: &mut FrameBuffer<W, H>, slot: usize, idx: u8, color: u8| {
+            let figure = TETRAMINO.wrapping_at(idx);
+            let left = Self::score_width() + 1 + slot / rows * SLOT_WIDTH;
+            // Centered in the slot
+            let x = left + (SLOT_WIDTH - 1 - figure.width() as usize) / 2;
+            let y = slot % rows * SLOT_HEIGHT;
+            screen.draw_figure(x as i8, y as i8, &figure, color);
+        };
+        for slot in 0..slots - 1 {
+            let idx = self.queue.peek(slot);
+            draw_slot(screen, slot, idx, self.get_tetramino_color(idx));
         }
+        if let Some(idx) = self.held {
+            let color = if self.can_hold { HUD_ALT } else { DELIMITER };
+            draw_slot(screen, slots - 1, idx, color);
+        }
+    }
+
+    /// Ticks in `millis`
+    fn ticks_in(millis: u32) -> u32 {
+        millis / Self::TICK_MILLIS as u32
     }
 
-    fn reduce_concrete(&mut self) -> Option<usize> {
-        for row in (6..SCREEN_HEIGHT).rev() {
-            if self.concrete.row_is_full(row) {
-                self.concrete
-                    .clear_range(row * SCREEN_WIDTH, (row + 1) * SCREEN_WIDTH);
-                return Some(row);
+    /// What the HUD shows of the mode: the number in place of the score, and
+    /// how far the gauge along the delimiter is filled, as a part of `total`
+    fn readout(&self) -> (u32, u32, u32) {
+        match self.mode {
+            // Lines to the next level
+            TetrisMode::Marathon => (self.score, self.lines % 10, 10),
+            // Seconds run and lines cleared
+            TetrisMode::Sprint => {
+                let seconds = self.ticks / Self::ticks_in(1000);
+                (seconds, self.lines, SPRINT_LINES)
+            }
+            // Time left
+            TetrisMode::Ultra => {
+                let total = Self::ticks_in(ULTRA_MILLIS);
+                (self.score, total.saturating_sub(self.ticks), total)
             }
+            // Time until the next garbage row
+            TetrisMode::Survival => {
+                let total = Self::ticks_in(GARBAGE_MILLIS);
+                (self.score, self.garbage_ticks, total)
+            }
+        }
+    }
+
+    fn draw_score(&self, screen: &mut FrameBuffer<W, H>) {
+        let (value, part, total) = self.readout();
+        self.score_widget.draw(screen, value, HUD);
+        self.draw_queue(screen);
+
+        // Draw horizontal line, the gauge filling it from the left
+        let filled = (part.min(total) as usize * W).div_ceil(total as usize);
+        for x in 0..W {
+            let color = if x < filled { HUD_ALT } else { DELIMITER };
+            screen.set(x, HUD_ROWS - 1, color);
+        }
+    }
+
+    fn level(&self) -> u32 {
+        self.start_level as u32 + self.lines / 10
+    }
+
+    /// Whether the falling tetramino rests on the concrete or the floor
+    fn grounded(&self) -> bool {
+        self.grounded_at(self.y)
+    }
+
+    /// Whether the falling tetramino would rest on the concrete or the floor
+    /// on row `y`
+    fn grounded_at(&self, y: i8) -> bool {
+        self.concrete.collides(self.x, y + 1, &self.curr)
+    }
+
+    /// Brings on tetramino `idx` at the top, as it spawns
+    fn spawn(&mut self, idx: u8) {
+        self.curr_idx = idx;
+        self.curr = TETRAMINO.wrapping_at(idx);
+        self.x = Self::init_x();
+        self.y = INIT_Y + 1;
+        self.landed = None;
+        self.lock_resets = 0;
+        self.lowest = self.y;
+    }
+
+    /// Swaps the falling tetramino with the held one, or puts it aside for
+    /// the next one if none is held yet. Nothing happens if there is no room
+    /// for the swapped in one.
+    fn hold(&mut self) {
+        let idx = self.held.unwrap_or(self.queue.peek(0));
+        let figure = TETRAMINO.wrapping_at(idx);
+        if self.concrete.collides(Self::init_x(), INIT_Y + 1, &figure) {
+            return;
+        }
+        if self.held.is_none() {
+            self.queue.pop(&mut self.prng);
         }
-        None
+        self.held = Some(self.curr_idx);
+        self.can_hold = false;
+        self.spawn(idx);
     }
 
-    fn shift_concrete(&mut self, cleared_row: usize) {
-        let mut to_idx = cleared_row;
-        let mut from_idx = cleared_row - 1;
+    /// Drops the falling tetramino and turns it into concrete, then flashes
+    /// the rows it fills or brings on the next one
+    fn lock(&mut self) {
+        self.y = self.landing_y();
+        let curr_color = self.get_tetramino_color(self.curr_idx);
+        self.concrete
+            .draw_figure(self.x, self.y, &self.curr, curr_color);
+        raise(&mut self.sound, Sfx::Lock);
+        self.can_hold = true;
 
-        while from_idx > 5 {
-            if self.concrete.row_is_empty(from_idx) {
-                if from_idx == 0 {
-                    break;
+        if (HUD_ROWS..H).any(|row| self.concrete.row_is_full(row)) {
+            self.clearing = Some(0);
+            raise(&mut self.sound, Sfx::LineClear);
+        } else {
+            self.spawn_next();
+        }
+    }
+
+    /// Clears the full rows at once and scores them by how many they are
+    fn clear(&mut self) {
+        let lines = clear_lines(&mut self.concrete);
+        self.score += LINE_POINTS[lines as usize] * (self.level() + 1);
+        self.lines += lines;
+        self.completed |= match self.mode {
+            TetrisMode::Marathon => self.level() >= MARATHON_LEVEL,
+            TetrisMode::Sprint => self.lines >= SPRINT_LINES,
+            TetrisMode::Ultra | TetrisMode::Survival => false,
+        };
+    }
+
+    /// Pushes the stack up by a row of garbage with a hole at a random
+    /// column, and the falling tetramino too if it's in the way
+    fn add_garbage(&mut self) {
+        if !self.concrete.row_is_empty(HUD_ROWS) {
+            self.top_out();
+            return;
+        }
+        for y in HUD_ROWS..H - 1 {
+            for x in 0..W {
+                self.concrete.set(x, y, self.concrete.get(x, y + 1));
+            }
+        }
+        let hole = self.prng.next_range(W as u8) as usize;
+        for x in 0..W {
+            let color = if x == hole { BLACK_IDX } else { WALL };
+            self.concrete.set(x, H - 1, color);
+        }
+        if self.concrete.collides(self.x, self.y, &self.curr) {
+            self.y -= 1;
+            if self.y < INIT_Y || self.concrete.collides(self.x, self.y, &self.curr) {
+                self.top_out();
+            }
+        }
+    }
+
+    fn top_out(&mut self) {
+        self.game_over = Some(0);
+        raise(&mut self.sound, Sfx::GameOver);
+    }
+
+    /// Brings on the next tetramino, unless the last one is in the way
+    fn spawn_next(&mut self) {
+        self.x = Self::init_x();
+        self.y = INIT_Y + 1;
+        if self.concrete.collides(self.x, self.y, &self.curr) {
+            // Blinks above the stack
+            self.y = INIT_Y;
+            self.top_out();
+            return;
+        }
+
+        let idx = self.queue.pop(&mut self.prng);
+        self.spawn(idx);
+        self.pieces += 1;
+    }
+
+    /// Turns the falling tetramino around the center of its SRS box, trying
+    /// the wall kicks in turn, returns whether it turned
+    fn turn(&mut self, clockwise: bool) -> bool {
+        let turned = if clockwise {
+            self.curr.rotate()
+        } else {
+            self.curr.rotate_ccw()
+        };
+        let (from_x, from_y) = srs_offset(self.curr_idx, self.curr.rotation);
+        let (to_x, to_y) = srs_offset(self.curr_idx, turned.rotation);
+        for (kick_x, kick_y) in srs_kicks(self.curr_idx, self.curr.rotation, clockwise) {
+            let x = self.x - from_x + to_x + kick_x;
+            let y = self.y - from_y + to_y + kick_y;
+            // Never into the HUD
+            if y >= INIT_Y && !self.concrete.collides(x, y, &turned) {
+                self.curr = turned;
+                (self.x, self.y) = (x, y);
+                return true;
+            }
+        }
+        false
+    }
+
+    fn step(&mut self, input: InputSnapshot) {
+        // Hard drop on up, once per push
+        let up = input.y < 0;
+        let hard_drop = up && !self.up_held;
+        self.up_held = up;
+
+        self.ticks += 1;
+        if self.mode == TetrisMode::Ultra && self.ticks >= Self::ticks_in(ULTRA_MILLIS) {
+            self.completed = true;
+            return;
+        }
+
+        if let Some(ticks) = self.clearing {
+            if ticks + 1 < CLEAR_TICKS {
+                self.clearing = Some(ticks + 1);
+            } else {
+                self.clearing = None;
+                self.clear();
+                self.spawn_next();
+            }
+            return;
+        }
+
+        if self.mode == TetrisMode::Survival {
+            self.garbage_ticks += 1;
+            if self.garbage_ticks >= Self::ticks_in(GARBAGE_MILLIS) {
+                self.garbage_ticks = 0;
+                self.add_garbage();
+                if self.game_over.is_some() {
+                    return;
                 }
-                from_idx -= 1;
-                continue;
             }
+        }
 
-            // Copy row
-            for x in 0..SCREEN_WIDTH {
-                let color = self.concrete.get(x, from_idx);
-                self.concrete.set(x, to_idx, color);
+        if input.a && self.can_hold {
+            self.hold();
+        }
+
+        // Faster every other level up to falling every tick
+        let speed_bonus = (self.level() / 2).clamp(1, FALL_TICKS as u32) as u16;
+        let down_bonus = if input.y > 0 { 10 } else { 0 };
+        if self.fall.advance(speed_bonus + down_bonus) {
+            if self.grounded() {
+                self.landed.get_or_insert(0);
+            } else {
+                self.y += 1;
             }
+        }
 
-            if from_idx == 0 {
-                break;
+        // Move sideways every other tick
+        let mut moved = false;
+        let new_x = self.x + input.x;
+        if self.sideways.advance(1)
+            && new_x != self.x
+            && new_x >= 0
+            && new_x < W as i8
+            && !self.concrete.collides(new_x, self.y, &self.curr)
+        {
+            self.x = new_x;
+            moved = true;
+        }
+
+        // The joystick button turns clockwise, B counter-clockwise
+        if input.joystick != input.b && self.turn(input.joystick) {
+            moved = true;
+        }
+
+        // Moving a landed tetramino gives it time again, or lets it fall on,
+        // for a limited number of moves per row it gets down to. Out of
+        // moves, it locks once the time is up after it touches down.
+        if self.y > self.lowest {
+            self.lowest = self.y;
+            self.lock_resets = 0;
+        }
+        if moved && self.lock_resets < MAX_LOCK_RESETS {
+            self.lock_resets += 1;
+            if self.landed.is_some() {
+                self.landed = if self.grounded() { Some(0) } else { None };
             }
-            from_idx -= 1;
-            if to_idx == 0 {
-                break;
+        } else if self.lock_resets == MAX_LOCK_RESETS && self.grounded() {
+            self.landed.get_or_insert(0);
+        }
+
+        if hard_drop {
+            self.lock();
+        } else if let Some(ticks) = self.landed {
+            if ticks >= self.lock_delay {
+                self.lock();
+            } else {
+                self.landed = Some(ticks + 1);
+            }
+        }
+    }
+}
+
+impl<const W: usize, const H: usize> Game<W, H> for TetrisGame<W, H> {
+    const MIN_WIDTH: usize = 8;
+    const MIN_HEIGHT: usize = 16;
+    const TICK_MILLIS: u64 = 50;
+
+    fn update(&mut self, input: InputSnapshot) -> Option<GameOutcome> {
+        self.score_widget.tick();
+        match self.game_over {
+            Some(_) if input.joystick => Some(self.outcome(EndReason::GameOver)),
+            Some(ticks) => {
+                self.game_over = Some(ticks.wrapping_add(1));
+                None
+            }
+            None => {
+                self.step(input);
+                self.completed.then(|| self.outcome(EndReason::Completed))
             }
-            to_idx -= 1;
         }
+    }
 
-        // Clear the row that was just shifted
-        for x in 0..SCREEN_WIDTH {
-            self.concrete.set(x, to_idx, BLACK_IDX);
+    fn outcome(&self, reason: EndReason) -> GameOutcome {
+        GameOutcome {
+            reason,
+            score: self.score,
+            count: self.lines,
+            // A finished sprint counts by its time
+            timed: reason == EndReason::Completed && self.mode == TetrisMode::Sprint,
+            ..Default::default()
         }
     }
 
-    async fn game_over(
-        &mut self,
-        mut leds: [RGB8; 256],
-        last_pos: Dot,
-        last_figure: &Figure,
-        last_color: u8,
-    ) {
-        while !self.controller.joystick_was_pressed() {
-            // Preserve the concrete blocks and score
-            self.screen.copy_from(&self.concrete);
-            self.draw_score();
+    fn take_sound(&mut self) -> Option<Sfx> {
+        self.sound.take()
+    }
 
-            // Blink the last tetramino
-            self.screen
-                .draw_figure(last_pos.x, last_pos.y - 1, last_figure, last_color);
-            self.screen.render(&mut leds);
-            self.display.write(&leds).await;
-            self.timer.sleep_millis(500).await;
-
-            // Clear only the last tetramino
-            self.screen
-                .draw_figure(last_pos.x, last_pos.y - 1, last_figure, BLACK_IDX);
-            self.screen.render(&mut leds);
-            self.display.write(&leds).await;
-            self.timer.sleep_millis(500).await;
-        }
-    }
-}
-
-impl<'a, D: LedDisplay, C: GameController, T: Timer> Game for TetrisGame<'a, D, C, T> {
-    async fn run(&mut self) {
-        const INIT_X: i8 = 3;
-        const INIT_Y: i8 = 6;
-        const RESPAWN_THRESHOLD: i8 = 11;
-
-        let mut x = INIT_X;
-        let mut y = INIT_Y;
-        let mut ipass: i8 = 0;
-        let mut mpass: u8 = 0;
-
-        let mut curr_idx = self.prng.next_range(7);
-        let mut next_idx = self.prng.next_range(7);
-        let mut curr = TETRAMINO.wrapping_at(curr_idx);
-        let mut next = TETRAMINO.wrapping_at(next_idx);
-        let mut leds: [RGB8; 256] = [RGB8::default(); 256];
-
-        loop {
-            if ipass > 10 {
-                ipass = 0;
-                y += 1;
-            }
-
-            // Read joystick
-            let x_diff = self.controller.read_x().await;
-            let mut new_x = x + x_diff;
-            if mpass % 2 == 0 {
-                new_x = x;
-            }
-            mpass = mpass.wrapping_add(1);
-
-            if new_x >= 0 && new_x < SCREEN_WIDTH as i8 && !self.concrete.collides(new_x, y, &curr)
-            {
-                x = new_x;
-            }
-
-            if self.controller.joystick_was_pressed() {
-                let rotated = curr.rotate();
-                let shift = if rotated.height() > rotated.width()
-                    && x + rotated.width() as i8 >= SCREEN_WIDTH as i8
-                {
-                    (rotated.height() - rotated.width()) as i8
-                } else {
-                    0
-                };
-
-                if !self.concrete.collides(x - shift, y, &rotated) {
-                    curr = rotated;
-                    x -= shift;
+    fn draw(&self, screen: &mut FrameBuffer<W, H>) {
+        // Preserve the concrete blocks and score
+        screen.copy_from(&self.concrete);
+        self.draw_score(screen);
+
+        if let Some(ticks) = self.clearing {
+            // Flash the full rows, the last tetramino is part of them
+            if !(ticks / FLASH_TICKS).is_multiple_of(2) {
+                for row in (HUD_ROWS..H).filter(|&row| self.concrete.row_is_full(row)) {
+                    for x in 0..W {
+                        screen.set(x, row, BLACK_IDX);
+                    }
                 }
             }
+            return;
+        }
+
+        let curr_color = self.get_tetramino_color(self.curr_idx);
+        if let Some(ticks) = self.game_over {
+            // Blink the last tetramino
+            if (ticks / BLINK_TICKS).is_multiple_of(2) {
+                screen.draw_figure(self.x, self.y, &self.curr, curr_color);
+            }
+            return;
+        }
+
+        if self.ghost {
+            let ghost = self.ghost_pixel(self.curr_idx);
+            screen.draw_figure_pixel(self.x, self.landing_y(), &self.curr, ghost);
+        }
+        screen.draw_figure(self.x, self.y, &self.curr, curr_color);
+    }
+}
 
-            self.screen.copy_from(&self.concrete);
-            self.draw_score();
+/// Ticks the bot lets the last tetramino blink before confirming game over
+const BOT_GAME_OVER_TICKS: u16 = 4 * BLINK_TICKS;
 
-            let curr_color = self.get_tetramino_color(curr_idx);
-            let next_color = self.get_tetramino_color(next_idx);
+/// Weights of the placement heuristic, in hundredths. The default ones are
+/// the well known weights tuned by a genetic algorithm for a 10x20 board.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct Weights {
+    /// Per row of the sum of the column heights
+    pub height: i32,
+    /// Per line cleared
+    pub lines: i32,
+    /// Per empty cell with a block above it
+    pub holes: i32,
+    /// Per row of difference between neighboring columns
+    pub bumpiness: i32,
+}
 
-            if y > RESPAWN_THRESHOLD {
-                self.screen.draw_figure(INIT_X, INIT_Y, &next, next_color);
+impl Default for Weights {
+    fn default() -> Self {
+        Self {
+            height: -51,
+            lines: 76,
+            holes: -36,
+            bumpiness: -18,
+        }
+    }
+}
+
+impl Weights {
+    /// Score of the stack on `board` after clearing `lines` lines
+    fn evaluate<const W: usize, const H: usize>(
+        &self,
+        board: &FrameBuffer<W, H>,
+        lines: u32,
+    ) -> i32 {
+        let (mut height, mut holes, mut bumpiness) = (0, 0, 0);
+        let mut prev_column: Option<i32> = None;
+        for x in 0..W {
+            let top = (HUD_ROWS..H).find(|&y| board.get(x, y) != BLACK_IDX);
+            let column = top.map_or(0, |top| (H - top) as i32);
+            let blocks = (HUD_ROWS..H)
+                .filter(|&y| board.get(x, y) != BLACK_IDX)
+                .count();
+            height += column;
+            holes += column - blocks as i32;
+            if let Some(prev) = prev_column {
+                bumpiness += (column - prev).abs();
             }
+            prev_column = Some(column);
+        }
+        self.height * height
+            + self.lines * lines as i32
+            + self.holes * holes
+            + self.bumpiness * bumpiness
+    }
+}
 
-            if !self.concrete.collides(x, y, &curr) {
-                self.screen.draw_figure(x, y, &curr, curr_color);
-            } else {
-                self.screen.draw_figure(x, y - 1, &curr, curr_color);
-                self.concrete.draw_figure(x, y - 1, &curr, curr_color);
+/// Where a tetramino goes: turned into `figure` and dropped in column `x`
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Placement {
+    pub x: i8,
+    pub figure: Figure,
+}
 
-                x = INIT_X;
-                y = INIT_Y + 1;
+/// Removes the full rows of `board` at once, dropping the rows above them,
+/// returns how many
+fn clear_lines<const W: usize, const H: usize>(board: &mut FrameBuffer<W, H>) -> u32 {
+    let mut lines = 0;
+    let mut to = H;
+    for from in (HUD_ROWS..H).rev() {
+        if board.row_is_full(from) {
+            lines += 1;
+            continue;
+        }
+        to -= 1;
+        if to != from {
+            for x in 0..W {
+                board.set(x, to, board.get(x, from));
+            }
+        }
+    }
+    for y in HUD_ROWS..to {
+        for x in 0..W {
+            board.set(x, y, BLACK_IDX);
+        }
+    }
+    lines
+}
 
-                if self.concrete.collides(x, y, &curr) {
-                    self.game_over(leds, Dot::new(x, y), &curr, curr_color)
-                        .await;
-                    return;
-                }
+/// Calls `visit` with every placement `figure` at (`x`, `y`) on `board` can
+/// reach by turning and moving sideways before dropping straight down, the
+/// board it leaves and the lines it clears
+fn for_each_placement<const W: usize, const H: usize>(
+    board: &FrameBuffer<W, H>,
+    figure: Figure,
+    x: i8,
+    y: i8,
+    mut visit: impl FnMut(Placement, &FrameBuffer<W, H>, u32),
+) {
+    let mut turned = [figure; 4];
+    for rotations in 1..turned.len() {
+        turned[rotations] = turned[rotations - 1].rotate();
+    }
+    for (rotations, figure) in turned.iter().enumerate() {
+        // Symmetric tetraminos look the same after some turns
+        if turned[..rotations]
+            .iter()
+            .any(|other| (other.data, other.wh) == (figure.data, figure.wh))
+        {
+            continue;
+        }
+        for target in 0..W as i8 {
+            let (from, to) = (x.min(target), x.max(target));
+            if (from..=to).any(|column| board.collides(column, y, figure)) {
+                continue;
+            }
+            let mut landing = y;
+            while !board.collides(target, landing + 1, figure) {
+                landing += 1;
+            }
+            let mut after = *board;
+            after.draw_figure(target, landing, figure, HUD);
+            let lines = clear_lines(&mut after);
+            let placement = Placement {
+                x: target,
+                figure: *figure,
+            };
+            visit(placement, &after, lines);
+        }
+    }
+}
 
-                curr_idx = next_idx;
-                next_idx = self.prng.next_range(7);
-                curr = TETRAMINO.wrapping_at(curr_idx);
-                next = TETRAMINO.wrapping_at(next_idx);
+/// Best placement for `figure` falling at (`x`, `y`) on `board` by
+/// `weights`. With the `next` tetramino, placements are judged by the best
+/// stack they leave once that one is placed as well.
+pub fn best_placement<const W: usize, const H: usize>(
+    board: &FrameBuffer<W, H>,
+    figure: Figure,
+    x: i8,
+    y: i8,
+    next: Option<Figure>,
+    weights: &Weights,
+) -> Option<Placement> {
+    let mut best: Option<(i32, Placement)> = None;
+    for_each_placement(board, figure, x, y, |placement, after, lines| {
+        let score = match next {
+            Some(next) => {
+                let mut best_next = None;
+                let spawn_x = TetrisGame::<W, H>::init_x();
+                for_each_placement(after, next, spawn_x, INIT_Y + 1, |_, after, more| {
+                    let score = weights.evaluate(after, lines + more);
+                    best_next = best_next.max(Some(score));
+                });
+                // Nowhere to put the next one is game over
+                best_next.unwrap_or(i32::MIN)
             }
-            if mpass % 2 == 0 {
-                if let Some(row) = self.reduce_concrete() {
-                    self.score += 1;
-                    self.shift_concrete(row);
+            None => weights.evaluate(after, lines),
+        };
+        if best.is_none_or(|(best_score, _)| score > best_score) {
+            best = Some((score, placement));
+        }
+    });
+    best.map(|(_, placement)| placement)
+}
+
+impl<const W: usize, const H: usize> TetrisGame<W, H> {
+    /// Where the bot with `weights` would put the falling tetramino, `None`
+    /// once the game is over
+    pub fn hint(&self, weights: &Weights) -> Option<Placement> {
+        if self.game_over.is_some() {
+            return None;
+        }
+        best_placement(
+            &self.concrete,
+            self.curr,
+            self.x,
+            self.y,
+            Some(TETRAMINO.wrapping_at(self.queue.peek(0))),
+            weights,
+        )
+    }
+}
+
+/// Computer player placing every tetramino where it leaves the best stack
+/// by its `Weights`, looking ahead at the next tetramino. Drives a game as a
+/// `GameController` through `bot::BotController`.
+#[derive(Default, Copy, Clone, Debug)]
+pub struct TetrisBot {
+    weights: Weights,
+    /// Tetramino the plan is for, by its number, and where it goes
+    plan: Option<(u32, Option<Placement>)>,
+}
+
+impl TetrisBot {
+    pub fn new(weights: Weights) -> Self {
+        Self {
+            weights,
+            plan: None,
+        }
+    }
+
+    pub fn weights(&self) -> &Weights {
+        &self.weights
+    }
+}
+
+impl<const W: usize, const H: usize> Bot<TetrisGame<W, H>> for TetrisBot {
+    fn input(&mut self, game: &TetrisGame<W, H>) -> InputSnapshot {
+        if let Some(ticks) = game.game_over {
+            return InputSnapshot {
+                joystick: ticks >= BOT_GAME_OVER_TICKS,
+                ..Default::default()
+            };
+        }
+        // Planned once per tetramino, the search takes a while on wide panels
+        let placement = match self.plan {
+            Some((piece, placement)) if piece == game.pieces => placement,
+            _ => {
+                let placement = game.hint(&self.weights);
+                self.plan = Some((game.pieces, placement));
+                placement
+            }
+        };
+        match placement {
+            // Turn the shorter way while moving over, drop once there
+            Some(Placement { x, figure }) => {
+                let turned = game.curr == figure;
+                let back = figure.rotation == (game.curr.rotation + 3) % 4;
+                InputSnapshot {
+                    x: (x - game.x).signum(),
+                    y: (turned && x == game.x) as i8,
+                    joystick: !turned && !back,
+                    b: back,
+                    ..Default::default()
                 }
             }
+            None => InputSnapshot::default(),
+        }
+    }
+}
 
-            self.screen.render(&mut leds);
-            self.display.write(&leds).await;
+/// Lines `bot` clears in a game seeded with `seed`, played headless until
+/// it ends, for at most `max_ticks` ticks
+pub fn benchmark<const W: usize, const H: usize>(
+    seed: u32,
+    settings: &Settings,
+    mut bot: TetrisBot,
+    max_ticks: u32,
+) -> u32 {
+    let mut game = TetrisGame::<W, H>::new(Prng::new(seed), settings);
+    for _ in 0..max_ticks {
+        if game.game_over.is_some() {
+            break;
+        }
+        let input = bot.input(&game);
+        if game.update(input).is_some() {
+            break;
+        }
+    }
+    game.outcome(EndReason::GameOver).count
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::NoAudio;
+    use crate::driver::{run_game, QUIT_HOLD_MILLIS};
+    use crate::testing::{block_on, hold_ab, RecordingDisplay, ScriptedController, VirtualTimer};
+
+    #[test]
+    fn stacking_pieces_ends_the_game() {
+        let mut game = TetrisGame::<8, 32>::new(Prng::new(7), &Settings::default());
+        let drop = InputSnapshot {
+            y: 1,
+            ..Default::default()
+        };
+
+        let mut ticks = 0;
+        while game.game_over.is_none() {
+            assert_eq!(game.update(drop), None);
+            ticks += 1;
+            assert!(ticks < 10_000, "pieces never reached the top");
+        }
+
+        // The game waits for the joystick before handing back the result
+        assert_eq!(game.update(drop), None);
+        let press = InputSnapshot {
+            joystick: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            game.update(press),
+            Some(GameOutcome {
+                reason: EndReason::GameOver,
+                ..Default::default()
+            })
+        );
+    }
+
+    #[test]
+    fn hold_swaps_once_per_drop() {
+        let mut game = TetrisGame::<8, 16>::new(Prng::new(4), &Settings::default());
+        let first = game.curr_idx;
+        let next = game.queue.peek(0);
+        let hold = InputSnapshot {
+            a: true,
+            ..Default::default()
+        };
+        game.update(hold);
+        assert_eq!((game.held, game.curr_idx), (Some(first), next));
+        game.update(hold);
+        assert_eq!((game.held, game.curr_idx), (Some(first), next));
+
+        // A hard drop locks at once and allows holding again
+        let up = InputSnapshot {
+            y: -1,
+            ..Default::default()
+        };
+        game.update(up);
+        assert_eq!(game.pieces, 2);
+        assert!(!game.concrete.row_is_empty(15));
+        game.update(up);
+        assert_eq!(game.pieces, 2, "hard drops take a new push");
+        let curr = game.curr_idx;
+        game.update(hold);
+        assert_eq!((game.held, game.curr_idx), (Some(curr), first));
+    }
+
+    #[test]
+    fn hold_then_turn_doesnt_quit() {
+        let mut game = TetrisGame::<8, 16>::new(Prng::new(4), &Settings::default());
+        let first = game.curr_idx;
+        let hold = InputSnapshot {
+            a: true,
+            ..Default::default()
+        };
+        let back = InputSnapshot {
+            b: true,
+            ..Default::default()
+        };
+        let mut controller =
+            ScriptedController::new([hold, back, InputSnapshot::default()]).then_repeat(hold_ab());
+        let outcome = block_on(run_game(
+            &mut game,
+            &mut RecordingDisplay::new(),
+            &mut NoAudio,
+            &mut controller,
+            &VirtualTimer::with_limit(10_000),
+        ));
+        // Only holding A and B down quit
+        assert_eq!(outcome.reason, EndReason::Quit);
+        assert_eq!(outcome.duration_millis, 3 * 50 + QUIT_HOLD_MILLIS);
+        assert_eq!(game.held, Some(first));
+    }
+
+    #[test]
+    fn lock_delay_lets_landed_pieces_slide() {
+        let settings = Settings {
+            lock_delay_millis: 500,
+            ..Settings::default()
+        };
+        let mut game = TetrisGame::<8, 16>::new(Prng::new(4), &settings);
+        let drop = InputSnapshot {
+            y: 1,
+            ..Default::default()
+        };
+        while game.landed.is_none() {
+            game.update(drop);
+        }
+        let x = game.x;
+        let left = InputSnapshot {
+            x: -1,
+            ..Default::default()
+        };
+        for _ in 0..4 {
+            game.update(left);
+        }
+        assert_eq!((game.pieces, game.x), (1, x - 2));
+
+        // Without a delay, pieces lock as they land
+        let settings = Settings {
+            lock_delay_millis: 0,
+            ..Settings::default()
+        };
+        let mut game = TetrisGame::<8, 16>::new(Prng::new(4), &settings);
+        while game.pieces == 1 {
+            game.update(drop);
+            assert_eq!(game.landed, None);
+        }
+    }
+
+    #[test]
+    fn tetrises_flash_and_clear_at_once() {
+        let settings = Settings {
+            start_level: 2,
+            ..Settings::default()
+        };
+        let mut game = TetrisGame::<8, 16>::new(Prng::new(4), &settings);
+        // Four rows full but for the left column, one block above them
+        for y in 12..16 {
+            for x in 1..8 {
+                game.concrete.set(x, y, HUD);
+            }
+        }
+        game.concrete.set(3, 11, HUD);
+        game.spawn(0);
+        game.turn(true);
+        game.x = 0;
+        let up = InputSnapshot {
+            y: -1,
+            ..Default::default()
+        };
+        game.update(up);
+        assert_eq!(game.clearing, Some(0));
+
+        // The full rows flash while the next tetramino waits
+        let mut screen = FrameBuffer::new();
+        let mut flashed = false;
+        while game.clearing.is_some() {
+            game.draw(&mut screen);
+            flashed |= screen.row_is_empty(15);
+            assert_eq!(game.pieces, 1);
+            game.update(InputSnapshot::default());
+        }
+        assert!(flashed);
+        assert_eq!((game.lines, game.pieces), (4, 2));
+        assert_eq!(game.score, LINE_POINTS[4] * 3);
+        assert_eq!(game.concrete.get(3, 15), HUD);
+        assert!(game.concrete.row_is_empty(14));
+    }
+
+    #[test]
+    fn modes_end_on_their_goals() {
+        // A sprint ends with the 40th line, timed
+        let settings = Settings {
+            tetris_mode: TetrisMode::Sprint,
+            ..Settings::default()
+        };
+        let mut game = TetrisGame::<8, 16>::new(Prng::new(4), &settings);
+        game.lines = SPRINT_LINES - 1;
+        for x in 4..8 {
+            game.concrete.set(x, 15, HUD);
+        }
+        game.spawn(0);
+        game.x = 0;
+        let up = InputSnapshot {
+            y: -1,
+            ..Default::default()
+        };
+        let mut outcome = game.update(up);
+        while outcome.is_none() {
+            outcome = game.update(InputSnapshot::default());
+        }
+        let outcome = outcome.unwrap();
+        assert_eq!(outcome.reason, EndReason::Completed);
+        assert!(outcome.timed);
+        assert_eq!(outcome.count, SPRINT_LINES);
+
+        // Ultra ends after two minutes, whatever the score
+        let settings = Settings {
+            tetris_mode: TetrisMode::Ultra,
+            ..Settings::default()
+        };
+        let mut game = TetrisGame::<8, 32>::new(Prng::new(4), &settings);
+        game.ticks = TetrisGame::<8, 32>::ticks_in(ULTRA_MILLIS) - 10;
+        for _ in 1..10 {
+            assert_eq!(game.update(InputSnapshot::default()), None);
+        }
+        let outcome = game.update(InputSnapshot::default()).unwrap();
+        assert_eq!(outcome.reason, EndReason::Completed);
+        assert!(!outcome.timed);
+    }
+
+    #[test]
+    fn survival_pushes_garbage_up() {
+        let settings = Settings {
+            tetris_mode: TetrisMode::Survival,
+            ..Settings::default()
+        };
+        let mut game = TetrisGame::<8, 32>::new(Prng::new(4), &settings);
+        game.concrete.set(2, 31, HUD);
+        for _ in 0..TetrisGame::<8, 32>::ticks_in(GARBAGE_MILLIS) {
+            game.update(InputSnapshot::default());
+        }
+        assert_eq!(game.concrete.get(2, 30), HUD);
+        let garbage = (0..8).filter(|&x| game.concrete.get(x, 31) == WALL);
+        assert_eq!(garbage.count(), 7);
+        assert_eq!(game.game_over, None);
+    }
+
+    #[test]
+    fn ghost_shows_where_the_piece_lands() {
+        let mut game = TetrisGame::<8, 16>::new(Prng::new(4), &Settings::default());
+        game.spawn(1);
+        let mut screen = FrameBuffer::new();
+        game.draw(&mut screen);
+        let ghost = game.ghost_pixel(1);
+        assert_eq!(ghost, Pixel::Shade(PIECES[1], GHOST_LEVEL));
+        for (x, y) in [(3, 14), (4, 14), (3, 15), (4, 15)] {
+            assert_eq!(screen.pixel(x, y), ghost);
+        }
+
+        game.ghost = false;
+        screen.clear();
+        game.draw(&mut screen);
+        assert!(screen.row_is_empty(15));
+    }
 
-            if self.score > 99 {
-                self.score = 0;
+    #[test]
+    fn turns_kick_off_the_walls() {
+        let mut game = TetrisGame::<8, 16>::new(Prng::new(4), &Settings::default());
+        game.spawn(0);
+        let (x, y) = (game.x, game.y);
+        // The I tetramino turns around the center of its 4x4 box
+        assert!(game.turn(true));
+        assert_eq!((game.x, game.y, game.curr.rotation), (x + 2, y - 1, 1));
+        assert!(game.turn(false));
+        assert_eq!((game.x, game.y), (x, y));
+        assert_eq!(game.curr, TETRAMINO.wrapping_at(0));
+
+        // Upright against the right wall, it lies down one column over
+        assert!(game.turn(true));
+        game.x = 7;
+        assert!(game.turn(true));
+        assert_eq!((game.x, game.curr.rotation), (4, 2));
+
+        // B turns counter-clockwise
+        let back = InputSnapshot {
+            b: true,
+            ..Default::default()
+        };
+        game.update(back);
+        assert_eq!(game.curr.rotation, 1);
+    }
+
+    #[test]
+    fn bot_fills_the_gap() {
+        // The bottom row has a gap 4 wide on the left
+        let mut board = FrameBuffer::<8, 16>::new();
+        for x in 4..8 {
+            board.set(x, 15, HUD);
+        }
+        let i_piece = TETRAMINO.wrapping_at(0);
+        let upright = i_piece.rotate();
+        let placement = best_placement(&board, upright, 3, 7, None, &Weights::default());
+        assert_eq!(
+            placement,
+            Some(Placement {
+                x: 0,
+                figure: upright.rotate()
+            })
+        );
+
+        assert_eq!(clear_lines(&mut board), 0);
+        board.draw_figure(0, 15, &i_piece, HUD);
+        assert_eq!(clear_lines(&mut board), 1);
+        assert!(board.row_is_empty(15));
+    }
+
+    #[test]
+    fn bot_clears_lines() {
+        let lines = benchmark::<8, 16>(1, &Settings::default(), TetrisBot::default(), 50_000);
+        assert!(lines >= 10, "the bot cleared {lines} lines");
+
+        // A sprint is over at 40 lines, the last clear may take up to four
+        let settings = Settings {
+            tetris_mode: TetrisMode::Sprint,
+            ..Settings::default()
+        };
+        let lines = benchmark::<8, 32>(1, &settings, TetrisBot::default(), 100_000);
+        assert!(
+            (SPRINT_LINES..SPRINT_LINES + 4).contains(&lines),
+            "the sprint ended at {lines} lines"
+        );
+    }
+
+    #[test]
+    fn bags_hold_every_tetramino_once() {
+        let mut prng = Prng::new(9);
+        let mut queue = PieceQueue::new(Randomizer::Bag, &mut prng);
+        for _ in 0..10 {
+            let mut seen = [false; 7];
+            for _ in 0..7 {
+                seen[queue.pop(&mut prng) as usize] = true;
+            }
+            assert_eq!(seen, [true; 7]);
+        }
+
+        // The classic randomizer deals the tetraminos as the PRNG draws them
+        let mut queue = PieceQueue::new(Randomizer::Classic, &mut Prng::new(9));
+        let mut draws = Prng::new(9);
+        let mut prng = Prng::new(10);
+        for _ in 0..QUEUE_LEN {
+            assert_eq!(queue.pop(&mut prng), draws.next_range(7));
+        }
+    }
+
+    #[test]
+    fn queue_fills_the_hud_beside_the_score() {
+        let game = TetrisGame::<16, 16>::new(Prng::new(5), &Settings::default());
+        let mut screen = FrameBuffer::new();
+        game.draw(&mut screen);
+        // The next tetramino right of the score, above the held one, the
+        // playfield untouched
+        let next = TETRAMINO.wrapping_at(game.queue.peek(0));
+        assert_eq!(TetrisGame::<16, 16>::score_width(), 11);
+        assert_eq!(TetrisGame::<32, 16>::queue_columns(), QUEUE_LEN / 2);
+        let mut hud = FrameBuffer::<16, 16>::new();
+        let x = 12 + (SLOT_WIDTH - 1 - next.width() as usize) / 2;
+        hud.draw_figure(
+            x as i8,
+            0,
+            &next,
+            game.get_tetramino_color(game.queue.peek(0)),
+        );
+        for y in 0..next.height() as usize {
+            for x in 12..16 {
+                assert_eq!(screen.get(x, y), hud.get(x, y));
             }
+        }
+    }
 
-            let speed_bonus = (self.score / 2 / 10).max(1) as i8;
-            let y_input = self.controller.read_y().await;
-            let down_bonus: i8 = if y_input > 0 { 10 } else { 0 };
+    #[test]
+    fn narrow_hud_keeps_two_digits() {
+        let mut game = TetrisGame::<8, 32>::new(Prng::new(5), &Settings::default());
+        game.score = 42;
+        game.held = Some(2);
+        let mut screen = FrameBuffer::new();
+        game.draw(&mut screen);
 
-            ipass += speed_bonus + down_bonus;
-            self.timer.sleep_millis(50).await;
+        // Both digits, and the queue as dots in the last column
+        let mut hud = FrameBuffer::<8, 32>::new();
+        ScoreWidget::new(0, 0, 7).draw(&mut hud, 42, HUD);
+        for row in 0..4 {
+            hud.set(7, row, game.get_tetramino_color(game.queue.peek(row)));
+        }
+        hud.set(7, 4, game.get_tetramino_color(2));
+        for y in 0..HUD_ROWS - 1 {
+            for x in 0..8 {
+                assert_eq!(screen.get(x, y), hud.get(x, y), "({x}, {y})");
+            }
         }
     }
 }
@@ -0,0 +1,196 @@
+// High-score table
+//
+// The best results of every game, kept in `Storage` under the "highscores"
+// key as:
+//
+//   "HS" | version | number of tables | results (u32, little endian)...
+//
+// with `TABLE_SIZE` results per table, best first. There is a table per game
+// in menu order, followed by those of the Tetris modes other than Marathon,
+// which uses the one of Tetris. Results are scores, except in the Sprint
+// table where they are times in milliseconds. Data written for another
+// version or list of tables is ignored.
+
+use crate::games::GAME_TITLES;
+use crate::settings::TetrisMode;
+use crate::storage::Storage;
+
+const KEY: &str = "highscores";
+const MAGIC: &[u8; 2] = b"HS";
+const VERSION: u8 = 1;
+const GAMES: usize = GAME_TITLES.len();
+const TABLES: usize = GAMES + TetrisMode::ALL.len() - 1;
+
+/// Number of results kept per table
+pub const TABLE_SIZE: usize = 3;
+
+/// Which high-score table a game goes to: one per game, and one per mode for
+/// Tetris
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Table(u8);
+
+impl Table {
+    /// Table of game number `game` (in menu order), played in `mode` if it's
+    /// Tetris
+    pub fn new(game: u8, mode: TetrisMode) -> Self {
+        match mode {
+            _ if game != 0 => Self(game),
+            TetrisMode::Marathon => Self(0),
+            mode => Self(GAMES as u8 + mode as u8 - 1),
+        }
+    }
+
+    /// Whether the table ranks times, shortest first, rather than scores
+    pub fn timed(self) -> bool {
+        self == Self::new(0, TetrisMode::Sprint)
+    }
+}
+
+/// Best results per table
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct HighScores {
+    scores: [[u32; TABLE_SIZE]; TABLES],
+}
+
+impl HighScores {
+    pub const ENCODED_SIZE: usize = 4 + TABLES * TABLE_SIZE * 4;
+
+    /// Reads the table from `storage`, starting empty if there is none
+    pub async fn load<S: Storage>(storage: &mut S) -> Self {
+        let mut buf = [0; Self::ENCODED_SIZE];
+        match storage.load(KEY, &mut buf).await {
+            Some(len) => Self::decode(&buf[..len]).unwrap_or_default(),
+            None => Self::default(),
+        }
+    }
+
+    pub async fn save<S: Storage>(&self, storage: &mut S) {
+        storage.save(KEY, &self.encode()).await;
+    }
+
+    /// Results in `table`, best first (zero for unused entries)
+    pub fn table(&self, table: Table) -> &[u32; TABLE_SIZE] {
+        &self.scores[table.0 as usize]
+    }
+
+    pub fn best(&self, table: Table) -> u32 {
+        self.scores[table.0 as usize][0]
+    }
+
+    /// Enters `result` into `table`, returns its rank (0 for a new record) or
+    /// `None` if it didn't make it. Zero results are never entered.
+    pub fn record(&mut self, table: Table, result: u32) -> Option<usize> {
+        let timed = table.timed();
+        let entries = self.scores.get_mut(table.0 as usize)?;
+        if result == 0 {
+            return None;
+        }
+        let rank = entries.iter().position(|&entry| {
+            if timed {
+                entry == 0 || result < entry
+            } else {
+                result > entry
+            }
+        })?;
+        entries.copy_within(rank..TABLE_SIZE - 1, rank + 1);
+        entries[rank] = result;
+        Some(rank)
+    }
+
+    pub fn encode(&self) -> [u8; Self::ENCODED_SIZE] {
+        let mut buf = [0; Self::ENCODED_SIZE];
+        buf[..2].copy_from_slice(MAGIC);
+        buf[2] = VERSION;
+        buf[3] = TABLES as u8;
+        for (chunk, score) in buf[4..].chunks_exact_mut(4).zip(self.scores.as_flattened()) {
+            chunk.copy_from_slice(&score.to_le_bytes());
+        }
+        buf
+    }
+
+    pub fn decode(data: &[u8]) -> Option<Self> {
+        if data.len() != Self::ENCODED_SIZE
+            || &data[..2] != MAGIC
+            || data[2] != VERSION
+            || data[3] as usize != TABLES
+        {
+            return None;
+        }
+        let mut table = Self::default();
+        for (score, chunk) in table
+            .scores
+            .as_flattened_mut()
+            .iter_mut()
+            .zip(data[4..].chunks_exact(4))
+        {
+            *score = u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+        }
+        Some(table)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{block_on, MemoryStorage};
+
+    #[test]
+    fn scores_are_ranked() {
+        let tetris = Table::new(0, TetrisMode::Marathon);
+        let mut scores = HighScores::default();
+        assert_eq!(scores.record(tetris, 0), None);
+        assert_eq!(scores.record(tetris, 5), Some(0));
+        assert_eq!(scores.record(tetris, 9), Some(0));
+        assert_eq!(scores.record(tetris, 7), Some(1));
+        assert_eq!(scores.record(tetris, 6), Some(2));
+        assert_eq!(scores.record(tetris, 1), None);
+        assert_eq!(scores.table(tetris), &[9, 7, 6]);
+        assert_eq!(scores.best(Table::new(1, TetrisMode::Marathon)), 0);
+        assert_eq!(scores.record(Table(TABLES as u8), 10), None);
+    }
+
+    #[test]
+    fn tetris_modes_have_their_own_tables() {
+        let ultra = Table::new(0, TetrisMode::Ultra);
+        let sprint = Table::new(0, TetrisMode::Sprint);
+        let mut scores = HighScores::default();
+        assert_eq!(scores.record(ultra, 300), Some(0));
+        assert_eq!(scores.best(Table::new(0, TetrisMode::Marathon)), 0);
+        assert_eq!(scores.best(Table::new(0, TetrisMode::Survival)), 0);
+        // Other games don't have modes
+        assert_eq!(
+            Table::new(1, TetrisMode::Ultra),
+            Table::new(1, TetrisMode::Marathon)
+        );
+
+        // Sprint times rank shortest first
+        assert!(sprint.timed() && !ultra.timed());
+        assert_eq!(scores.record(sprint, 90_000), Some(0));
+        assert_eq!(scores.record(sprint, 70_000), Some(0));
+        assert_eq!(scores.record(sprint, 80_000), Some(1));
+        assert_eq!(scores.record(sprint, 95_000), None);
+        assert_eq!(scores.table(sprint), &[70_000, 80_000, 90_000]);
+        assert_eq!(scores.table(ultra), &[300, 0, 0]);
+    }
+
+    #[test]
+    fn table_survives_storage() {
+        let mut storage = MemoryStorage::new();
+        assert_eq!(
+            block_on(HighScores::load(&mut storage)),
+            HighScores::default()
+        );
+
+        let mut scores = HighScores::default();
+        scores.record(Table::new(2, TetrisMode::Marathon), 12);
+        scores.record(Table::new(5, TetrisMode::Marathon), 70_000);
+        scores.record(Table::new(0, TetrisMode::Sprint), 61_500);
+        block_on(scores.save(&mut storage));
+        assert_eq!(block_on(HighScores::load(&mut storage)), scores);
+
+        // Data of another version is dropped
+        let mut data = scores.encode();
+        data[2] = VERSION + 1;
+        assert_eq!(HighScores::decode(&data), None);
+    }
+}
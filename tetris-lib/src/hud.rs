@@ -0,0 +1,194 @@
+// Heads-up display
+//
+// The games keep their score in the 8x6 area at the top of the panel, above
+// the delimiter, which only fits two digits. Longer numbers are shown by the
+// `ScoreWidget`: it holds the leading digits, scrolls over to the last ones,
+// holds them and starts over.
+
+use crate::common::FrameBuffer;
+use crate::font::{self, ADVANCE, GLYPH_HEIGHT, GLYPH_WIDTH};
+
+/// Most decimal digits of a `u32`
+const MAX_DIGITS: usize = 10;
+
+/// Steps both ends of a long number are held for
+const HOLD_STEPS: u32 = 4;
+
+/// Digits of `value`, zero padded to at least `min_digits`
+pub fn format_number(value: u32, min_digits: usize, buf: &mut [u8; MAX_DIGITS]) -> &str {
+    let mut start = MAX_DIGITS;
+    let mut value = value;
+    while start > 0 && (start == MAX_DIGITS || value > 0 || MAX_DIGITS - start < min_digits) {
+        start -= 1;
+        buf[start] = b'0' + (value % 10) as u8;
+        value /= 10;
+    }
+    // Only ASCII digits were written
+    core::str::from_utf8(&buf[start..]).unwrap_or_default()
+}
+
+/// A number in an area of the screen `width` pixels wide and as high as a
+/// glyph, scrolling if it doesn't fit
+#[derive(Clone, Copy, Debug)]
+pub struct ScoreWidget {
+    left: i16,
+    top: i16,
+    width: usize,
+    padded: bool,
+    ticks_per_pixel: u16,
+    ticks: u32,
+}
+
+impl ScoreWidget {
+    /// Widget in the area starting at (`left`, `top`), moving by a pixel every
+    /// tick
+    pub const fn new(left: i16, top: i16, width: usize) -> Self {
+        Self {
+            left,
+            top,
+            width,
+            padded: false,
+            ticks_per_pixel: 1,
+            ticks: 0,
+        }
+    }
+
+    /// Moves by a pixel every `ticks_per_pixel` ticks
+    pub const fn with_speed(mut self, ticks_per_pixel: u16) -> Self {
+        self.ticks_per_pixel = if ticks_per_pixel > 0 {
+            ticks_per_pixel
+        } else {
+            1
+        };
+        self
+    }
+
+    /// Fills the area with leading zeros rather than centering short numbers
+    pub const fn zero_padded(mut self) -> Self {
+        self.padded = true;
+        self
+    }
+
+    /// Digits fitting in the area
+    fn capacity(&self) -> usize {
+        (self.width + 1) / ADVANCE
+    }
+
+    /// Pixels `value` is wider than the area
+    fn overflow(&self, value: u32) -> usize {
+        let mut buf = [0; MAX_DIGITS];
+        let text = format_number(value, 1, &mut buf);
+        font::text_width(text).saturating_sub(self.width)
+    }
+
+    /// Ticks it takes to show all of `value` once, 0 if it fits
+    pub fn cycle_ticks(&self, value: u32) -> u32 {
+        match self.overflow(value) as u32 {
+            0 => 0,
+            overflow => (2 * HOLD_STEPS + overflow) * self.ticks_per_pixel as u32,
+        }
+    }
+
+    pub fn tick(&mut self) {
+        self.ticks = self.ticks.wrapping_add(1);
+    }
+
+    /// Starts over with the leading digits
+    pub fn rewind(&mut self) {
+        self.ticks = 0;
+    }
+
+    pub fn draw<const W: usize, const H: usize>(
+        &self,
+        screen: &mut FrameBuffer<W, H>,
+        value: u32,
+        color: u8,
+    ) {
+        let min_digits = if self.padded { self.capacity() } else { 1 };
+        let mut buf = [0; MAX_DIGITS];
+        let text = format_number(value, min_digits, &mut buf);
+        let text_width = font::text_width(text);
+
+        let x = match self.overflow(value) as u32 {
+            0 => self.left + (self.width.saturating_sub(text_width) / 2) as i16,
+            overflow => {
+                let cycle = 2 * HOLD_STEPS + overflow;
+                let step = self.ticks / self.ticks_per_pixel as u32 % cycle;
+                self.left - step.saturating_sub(HOLD_STEPS).min(overflow) as i16
+            }
+        };
+
+        let right = self.left + self.width as i16;
+        for (i, c) in text.chars().enumerate() {
+            let glyph = font::glyph(c);
+            let glyph_x = x + (i * ADVANCE) as i16;
+            for dy in 0..GLYPH_HEIGHT {
+                for dx in 0..GLYPH_WIDTH {
+                    let (px, py) = (glyph_x + dx as i16, self.top + dy as i16);
+                    let inside = px >= self.left.max(0)
+                        && px < right.min(W as i16)
+                        && (0..H as i16).contains(&py);
+                    if inside && glyph.get_bit(dx as u8, dy as u8) {
+                        screen.set(px as usize, py as usize, color);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::ascii;
+    use crate::theme::HUD;
+
+    #[test]
+    fn numbers_are_formatted() {
+        let mut buf = [0; MAX_DIGITS];
+        assert_eq!(format_number(0, 1, &mut buf), "0");
+        assert_eq!(format_number(7, 2, &mut buf), "07");
+        assert_eq!(format_number(1234, 2, &mut buf), "1234");
+        assert_eq!(format_number(u32::MAX, 1, &mut buf), "4294967295");
+    }
+
+    #[test]
+    fn short_scores_stay_still() {
+        let mut widget = ScoreWidget::new(0, 0, 8).zero_padded();
+        let mut screen = FrameBuffer::<8, 6>::new();
+        widget.draw(&mut screen, 7, HUD);
+        let before = ascii(&screen);
+        for _ in 0..100 {
+            widget.tick();
+        }
+        assert_eq!(widget.cycle_ticks(7), 0);
+        let mut screen = FrameBuffer::<8, 6>::new();
+        widget.draw(&mut screen, 7, HUD);
+        assert_eq!(ascii(&screen), before);
+        assert_eq!(before.lines().next(), Some("999.999."));
+    }
+
+    #[test]
+    fn long_scores_scroll_within_their_area() {
+        let mut widget = ScoreWidget::new(0, 0, 7).with_speed(2);
+        // "123" is 11 pixels wide, 4 more than the area
+        assert_eq!(widget.cycle_ticks(123), 2 * (2 * HOLD_STEPS + 4));
+
+        let first_row = |widget: &ScoreWidget| {
+            let mut screen = FrameBuffer::<8, 6>::new();
+            widget.draw(&mut screen, 123, HUD);
+            ascii(&screen).lines().next().unwrap_or_default().into()
+        };
+        let start: alloc::string::String = first_row(&widget);
+        assert_eq!(start, ".9..999.");
+        for _ in 0..2 * (HOLD_STEPS + 4) {
+            widget.tick();
+        }
+        // Scrolled to "23", the last column is outside of the area
+        assert_eq!(first_row(&widget), "999.999.");
+        for _ in 0..2 * HOLD_STEPS {
+            widget.tick();
+        }
+        assert_eq!(first_row(&widget), start);
+    }
+}
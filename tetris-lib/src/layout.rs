@@ -0,0 +1,234 @@
+// Physical LED wiring layouts
+//
+// A layout maps a screen coordinate (x to the right, y down) onto the position
+// of the LED in the strip and back. The same layout object is used to fill the
+// LED buffer for the hardware and by the emulators to draw it on screen.
+
+use core::str::FromStr;
+
+/// Order in which the strip runs through the panel
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Wiring {
+    /// Every row runs in the same direction
+    ProgressiveRows,
+    /// Every column runs in the same direction
+    ProgressiveColumns,
+    /// Rows alternate direction (zig-zag)
+    SerpentineRows,
+    /// Columns alternate direction (zig-zag)
+    SerpentineColumns,
+}
+
+/// Panel corner where the first LED of the strip sits
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum StartCorner {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct LedLayout {
+    pub wiring: Wiring,
+    pub start: StartCorner,
+    /// Panel is mounted upside down (rotated by 180 degrees)
+    pub flipped: bool,
+}
+
+impl LedLayout {
+    /// The 8x32 panel the games were written for: rows zig-zag and the strip
+    /// starts in the top right corner
+    pub const SERPENTINE: LedLayout = LedLayout::new(Wiring::SerpentineRows, StartCorner::TopRight);
+
+    pub const fn new(wiring: Wiring, start: StartCorner) -> Self {
+        Self {
+            wiring,
+            start,
+            flipped: false,
+        }
+    }
+
+    pub const fn flipped(mut self) -> Self {
+        self.flipped = !self.flipped;
+        self
+    }
+
+    fn by_rows(&self) -> bool {
+        matches!(
+            self.wiring,
+            Wiring::ProgressiveRows | Wiring::SerpentineRows
+        )
+    }
+
+    fn serpentine(&self) -> bool {
+        matches!(
+            self.wiring,
+            Wiring::SerpentineRows | Wiring::SerpentineColumns
+        )
+    }
+
+    fn starts_right(&self) -> bool {
+        matches!(self.start, StartCorner::TopRight | StartCorner::BottomRight)
+    }
+
+    fn starts_bottom(&self) -> bool {
+        matches!(
+            self.start,
+            StartCorner::BottomLeft | StartCorner::BottomRight
+        )
+    }
+
+    /// Mirrors a screen coordinate so that the strip starts at (0, 0); the
+    /// transformation is its own inverse
+    fn normalize(&self, x: usize, y: usize, width: usize, height: usize) -> (usize, usize) {
+        let (mut x, mut y) = (x, y);
+        if self.flipped {
+            x = width - 1 - x;
+            y = height - 1 - y;
+        }
+        if self.starts_right() {
+            x = width - 1 - x;
+        }
+        if self.starts_bottom() {
+            y = height - 1 - y;
+        }
+        (x, y)
+    }
+
+    /// Index in the LED strip of the screen pixel (`x`, `y`)
+    pub fn index(&self, x: usize, y: usize, width: usize, height: usize) -> usize {
+        let (x, y) = self.normalize(x, y, width, height);
+        let (major, mut minor, minor_len) = if self.by_rows() {
+            (y, x, width)
+        } else {
+            (x, y, height)
+        };
+        if self.serpentine() && major % 2 == 1 {
+            minor = minor_len - 1 - minor;
+        }
+        major * minor_len + minor
+    }
+
+    /// Screen pixel lit by the LED at `index` in the strip
+    pub fn position(&self, index: usize, width: usize, height: usize) -> (usize, usize) {
+        let minor_len = if self.by_rows() { width } else { height };
+        let major = index / minor_len;
+        let mut minor = index % minor_len;
+        if self.serpentine() && major % 2 == 1 {
+            minor = minor_len - 1 - minor;
+        }
+        let (x, y) = if self.by_rows() {
+            (minor, major)
+        } else {
+            (major, minor)
+        };
+        self.normalize(x, y, width, height)
+    }
+}
+
+impl Default for LedLayout {
+    fn default() -> Self {
+        Self::SERPENTINE
+    }
+}
+
+/// Error returned when a layout description can't be parsed
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct ParseLayoutError;
+
+impl FromStr for LedLayout {
+    type Err = ParseLayoutError;
+
+    /// Parses a `wiring[:corner][:flipped]` description, for example
+    /// `serpentine-rows:top-right` or `progressive-columns:bottom-left:flipped`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.split(':');
+        let wiring = match parts.next().ok_or(ParseLayoutError)? {
+            "progressive" | "progressive-rows" => Wiring::ProgressiveRows,
+            "progressive-columns" => Wiring::ProgressiveColumns,
+            "serpentine" | "serpentine-rows" => Wiring::SerpentineRows,
+            "serpentine-columns" => Wiring::SerpentineColumns,
+            _ => return Err(ParseLayoutError),
+        };
+
+        let mut layout = LedLayout::new(wiring, StartCorner::TopLeft);
+        for part in parts {
+            match part {
+                "top-left" => layout.start = StartCorner::TopLeft,
+                "top-right" => layout.start = StartCorner::TopRight,
+                "bottom-left" => layout.start = StartCorner::BottomLeft,
+                "bottom-right" => layout.start = StartCorner::BottomRight,
+                "flipped" => layout.flipped = true,
+                _ => return Err(ParseLayoutError),
+            }
+        }
+        Ok(layout)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALL_WIRINGS: [Wiring; 4] = [
+        Wiring::ProgressiveRows,
+        Wiring::ProgressiveColumns,
+        Wiring::SerpentineRows,
+        Wiring::SerpentineColumns,
+    ];
+    const ALL_CORNERS: [StartCorner; 4] = [
+        StartCorner::TopLeft,
+        StartCorner::TopRight,
+        StartCorner::BottomLeft,
+        StartCorner::BottomRight,
+    ];
+
+    #[test]
+    fn serpentine_matches_original_panel() {
+        let layout = LedLayout::SERPENTINE;
+        // Even rows run right to left, odd rows left to right
+        assert_eq!(layout.index(0, 0, 8, 32), 7);
+        assert_eq!(layout.index(7, 0, 8, 32), 0);
+        assert_eq!(layout.index(0, 1, 8, 32), 8);
+        assert_eq!(layout.index(3, 2, 8, 32), 2 * 8 + 4);
+    }
+
+    #[test]
+    fn serpentine_columns() {
+        let layout = LedLayout::new(Wiring::SerpentineColumns, StartCorner::BottomLeft);
+        assert_eq!(layout.index(0, 15, 16, 16), 0);
+        assert_eq!(layout.index(0, 0, 16, 16), 15);
+        assert_eq!(layout.index(1, 0, 16, 16), 16);
+        assert_eq!(layout.index(1, 15, 16, 16), 31);
+    }
+
+    #[test]
+    fn position_is_inverse_of_index() {
+        for wiring in ALL_WIRINGS {
+            for start in ALL_CORNERS {
+                for flipped in [false, true] {
+                    let mut layout = LedLayout::new(wiring, start);
+                    layout.flipped = flipped;
+                    for (width, height) in [(8, 32), (16, 16), (32, 8)] {
+                        for idx in 0..width * height {
+                            let (x, y) = layout.position(idx, width, height);
+                            assert!(x < width && y < height);
+                            assert_eq!(layout.index(x, y, width, height), idx, "{:?}", layout);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn parse() {
+        assert_eq!("serpentine:top-right".parse(), Ok(LedLayout::SERPENTINE));
+        assert_eq!(
+            "progressive-columns:bottom-left:flipped".parse(),
+            Ok(LedLayout::new(Wiring::ProgressiveColumns, StartCorner::BottomLeft).flipped())
+        );
+        assert_eq!("zigzag".parse::<LedLayout>(), Err(ParseLayoutError));
+    }
+}
@@ -1,8 +1,27 @@
 #![no_std]
 #![allow(async_fn_in_trait)]
 
+#[cfg(any(test, feature = "testing"))]
+extern crate alloc;
+
+pub mod audio;
+pub mod bot;
+pub mod color;
 pub mod common;
 pub mod digits;
+pub mod driver;
 pub mod figure;
+pub mod font;
 pub mod games;
+pub mod highscores;
+pub mod hud;
+pub mod layout;
 pub mod log;
+pub mod replay;
+pub mod scheduler;
+pub mod settings;
+pub mod storage;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+pub mod theme;
+pub mod tiling;
@@ -0,0 +1,465 @@
+// Input recording and replay
+//
+// A game run is fully determined by the game, the PRNG seed, the settings
+// games are created with and the input of every tick. A replay stores exactly
+// that, along with the canvas size the game was played on:
+//
+//   "TRP" | version | game id | width | height | seed (u32, little endian) |
+//   start level |
+//   controls | randomizer | lock delay (u16 milliseconds, little endian) |
+//   Tetris mode | ticks...
+//
+// Every tick is a single byte: bits 0-1 hold the x axis and bits 2-3 the y
+// axis (0 = centered, 1 = positive, 2 = negative), bit 4 the joystick button,
+// bit 5 button A, bit 6 button B and bit 7 whether A and B are held down. A
+// tick can be followed by 0xff, which no tick encodes to, and the number of
+// further ticks with the same input, so idle stretches stay short.
+
+use crate::common::{GameController, InputSnapshot};
+use crate::settings::{Controls, Randomizer, Settings, TetrisMode, MAX_LEVEL};
+
+const MAGIC: &[u8; 3] = b"TRP";
+const VERSION: u8 = 2;
+/// Marks a run of ticks, both axes at 3 never come up in a tick
+const RUN: u8 = 0xff;
+
+/// What a replay was recorded from
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct ReplayHeader {
+    /// Index of the game in the menu
+    pub game: u8,
+    /// Size of the canvas the game was played on
+    pub width: u8,
+    pub height: u8,
+    pub seed: u32,
+    pub start_level: u8,
+    pub controls: Controls,
+    pub randomizer: Randomizer,
+    pub lock_delay_millis: u16,
+    pub tetris_mode: TetrisMode,
+}
+
+impl ReplayHeader {
+    pub const SIZE: usize = 17;
+
+    /// Header of game number `game` seeded with `seed`, played on a
+    /// `width`x`height` canvas with `settings`
+    pub fn new(game: u8, width: usize, height: usize, seed: u32, settings: &Settings) -> Self {
+        Self {
+            game,
+            width: width as u8,
+            height: height as u8,
+            seed,
+            start_level: settings.start_level,
+            controls: settings.controls,
+            randomizer: settings.randomizer,
+            lock_delay_millis: settings.lock_delay_millis,
+            tetris_mode: settings.tetris_mode,
+        }
+    }
+
+    /// Settings to play the replay with
+    pub fn settings(&self) -> Settings {
+        Settings {
+            start_level: self.start_level,
+            controls: self.controls,
+            randomizer: self.randomizer,
+            lock_delay_millis: self.lock_delay_millis,
+            tetris_mode: self.tetris_mode,
+            ..Settings::default()
+        }
+    }
+
+    pub fn encode(&self) -> [u8; Self::SIZE] {
+        let seed = self.seed.to_le_bytes();
+        let lock_delay = self.lock_delay_millis.to_le_bytes();
+        [
+            MAGIC[0],
+            MAGIC[1],
+            MAGIC[2],
+            VERSION,
+            self.game,
+            self.width,
+            self.height,
+            seed[0],
+            seed[1],
+            seed[2],
+            seed[3],
+            self.start_level,
+            self.controls as u8,
+            self.randomizer as u8,
+            lock_delay[0],
+            lock_delay[1],
+            self.tetris_mode as u8,
+        ]
+    }
+}
+
+/// Error returned for data that isn't a replay this version can play
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ReplayError {
+    BadMagic,
+    UnsupportedVersion(u8),
+    Truncated,
+    /// Recorded on a canvas of another size, given as width and height
+    WrongSize(u8, u8),
+}
+
+fn encode_axis(value: i8) -> u8 {
+    match value.signum() {
+        1 => 1,
+        -1 => 2,
+        _ => 0,
+    }
+}
+
+fn decode_axis(bits: u8) -> i8 {
+    match bits & 0b11 {
+        1 => 1,
+        2 => -1,
+        _ => 0,
+    }
+}
+
+fn encode_input(input: &InputSnapshot) -> u8 {
+    encode_axis(input.x)
+        | encode_axis(input.y) << 2
+        | (input.joystick as u8) << 4
+        | (input.a as u8) << 5
+        | (input.b as u8) << 6
+        | (input.ab_held as u8) << 7
+}
+
+fn decode_input(byte: u8) -> InputSnapshot {
+    InputSnapshot {
+        x: decode_axis(byte),
+        y: decode_axis(byte >> 2),
+        joystick: byte & 1 << 4 != 0,
+        a: byte & 1 << 5 != 0,
+        b: byte & 1 << 6 != 0,
+        ab_held: byte & 1 << 7 != 0,
+    }
+}
+
+/// Destination of recorded replay bytes
+pub trait ReplaySink {
+    fn write(&mut self, bytes: &[u8]);
+}
+
+/// Controller wrapper recording every tick read through `read_input`.
+///
+/// The individual `read_x`/`was_pressed` calls are passed through unrecorded,
+/// the game driver only uses `read_input`. Call `finish` once the game is
+/// over to write out the last run of ticks.
+pub struct RecordingController<C, S> {
+    inner: C,
+    sink: S,
+    /// Input of the ongoing run and its length
+    run: Option<(u8, u8)>,
+}
+
+impl<C, S> RecordingController<C, S>
+where
+    C: GameController,
+    S: ReplaySink,
+{
+    pub fn new(inner: C, mut sink: S, header: ReplayHeader) -> Self {
+        sink.write(&header.encode());
+        Self {
+            inner,
+            sink,
+            run: None,
+        }
+    }
+
+    fn record(&mut self, input: &InputSnapshot) {
+        let byte = encode_input(input);
+        self.run = match self.run {
+            Some((current, count)) if current == byte && count < u8::MAX => {
+                Some((current, count + 1))
+            }
+            _ => {
+                self.flush();
+                Some((byte, 1))
+            }
+        };
+    }
+
+    fn flush(&mut self) {
+        match self.run.take() {
+            Some((byte, 1)) => self.sink.write(&[byte]),
+            Some((byte, count)) => self.sink.write(&[byte, RUN, count - 1]),
+            None => {}
+        }
+    }
+
+    /// Writes the pending ticks and hands back the wrapped controller and sink
+    pub fn finish(mut self) -> (C, S) {
+        self.flush();
+        (self.inner, self.sink)
+    }
+}
+
+impl<C, S> GameController for RecordingController<C, S>
+where
+    C: GameController,
+    S: ReplaySink,
+{
+    async fn read_x(&mut self) -> i8 {
+        self.inner.read_x().await
+    }
+
+    async fn read_y(&mut self) -> i8 {
+        self.inner.read_y().await
+    }
+
+    fn joystick_was_pressed(&self) -> bool {
+        self.inner.joystick_was_pressed()
+    }
+
+    fn a_was_pressed(&self) -> bool {
+        self.inner.a_was_pressed()
+    }
+
+    fn b_was_pressed(&self) -> bool {
+        self.inner.b_was_pressed()
+    }
+
+    fn ab_held(&self) -> bool {
+        self.inner.ab_held()
+    }
+
+    async fn read_input(&mut self) -> InputSnapshot {
+        let input = self.inner.read_input().await;
+        self.record(&input);
+        input
+    }
+}
+
+/// A parsed replay
+#[derive(Debug, Copy, Clone)]
+pub struct Replay<'a> {
+    pub header: ReplayHeader,
+    ticks: &'a [u8],
+}
+
+impl<'a> Replay<'a> {
+    pub fn parse(data: &'a [u8]) -> Result<Self, ReplayError> {
+        if data.len() < 4 {
+            return Err(if data.starts_with(&MAGIC[..data.len().min(3)]) {
+                ReplayError::Truncated
+            } else {
+                ReplayError::BadMagic
+            });
+        }
+        if &data[..3] != MAGIC {
+            return Err(ReplayError::BadMagic);
+        }
+        if data[3] != VERSION {
+            return Err(ReplayError::UnsupportedVersion(data[3]));
+        }
+        if data.len() < ReplayHeader::SIZE {
+            return Err(ReplayError::Truncated);
+        }
+        let seed = u32::from_le_bytes([data[7], data[8], data[9], data[10]]);
+        let (width, height) = (data[5] as usize, data[6] as usize);
+        let mut header = ReplayHeader::new(data[4], width, height, seed, &Settings::default());
+        header.start_level = data[11].min(MAX_LEVEL);
+        header.controls = Controls::decode(data[12]).unwrap_or_default();
+        header.randomizer = Randomizer::decode(data[13]).unwrap_or_default();
+        header.lock_delay_millis = u16::from_le_bytes([data[14], data[15]]);
+        header.tetris_mode = TetrisMode::decode(data[16]).unwrap_or_default();
+        Ok(Self {
+            header,
+            ticks: &data[ReplayHeader::SIZE..],
+        })
+    }
+
+    /// Like `parse`, but also fails for replays recorded on a canvas other
+    /// than `width`x`height`, whose games would play out differently
+    pub fn parse_for(data: &'a [u8], width: usize, height: usize) -> Result<Self, ReplayError> {
+        let replay = Self::parse(data)?;
+        let header = replay.header;
+        if header.width as usize != width || header.height as usize != height {
+            return Err(ReplayError::WrongSize(header.width, header.height));
+        }
+        Ok(replay)
+    }
+
+    /// Controller playing the recorded ticks back
+    pub fn controller(&self) -> ReplayController<'a> {
+        ReplayController {
+            ticks: self.ticks,
+            current: InputSnapshot::default(),
+            repeat: 0,
+        }
+    }
+}
+
+/// Controller feeding the game with recorded input, one tick per `read_input`
+/// (or `read_x`). Once the recording ends the joystick stays centered and no
+/// button is pressed.
+pub struct ReplayController<'a> {
+    ticks: &'a [u8],
+    current: InputSnapshot,
+    /// Further ticks of the current run
+    repeat: u8,
+}
+
+impl ReplayController<'_> {
+    pub fn is_finished(&self) -> bool {
+        self.repeat == 0 && self.ticks.is_empty()
+    }
+
+    fn advance(&mut self) -> InputSnapshot {
+        if self.repeat > 0 {
+            self.repeat -= 1;
+            return self.current;
+        }
+
+        self.current = match *self.ticks {
+            [byte, RUN, count, ref rest @ ..] if byte != RUN => {
+                self.ticks = rest;
+                self.repeat = count;
+                decode_input(byte)
+            }
+            [byte, ref rest @ ..] if byte != RUN => {
+                self.ticks = rest;
+                decode_input(byte)
+            }
+            // End of the recording, or a run cut short
+            _ => {
+                self.ticks = &[];
+                InputSnapshot::default()
+            }
+        };
+        self.current
+    }
+}
+
+impl GameController for ReplayController<'_> {
+    async fn read_x(&mut self) -> i8 {
+        self.advance().x
+    }
+
+    async fn read_y(&mut self) -> i8 {
+        self.current.y
+    }
+
+    fn joystick_was_pressed(&self) -> bool {
+        self.current.joystick
+    }
+
+    fn a_was_pressed(&self) -> bool {
+        self.current.a
+    }
+
+    fn b_was_pressed(&self) -> bool {
+        self.current.b
+    }
+
+    fn ab_held(&self) -> bool {
+        self.current.ab_held
+    }
+
+    async fn read_input(&mut self) -> InputSnapshot {
+        self.advance()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::games::play;
+    use crate::testing::{
+        block_on, joystick, press, RecordingAudio, RecordingDisplay, ScriptedController,
+        VirtualTimer,
+    };
+    use alloc::vec::Vec;
+
+    #[test]
+    fn input_round_trip() {
+        for byte in 0..=u8::MAX {
+            let input = decode_input(byte);
+            if byte & 0b11 != 0b11 && byte & 0b1100 != 0b1100 {
+                assert_eq!(encode_input(&input), byte);
+            }
+        }
+    }
+
+    #[test]
+    fn replay_reproduces_the_game() {
+        let settings = Settings {
+            start_level: 4,
+            controls: Controls::Reversed,
+            ..Settings::default()
+        };
+        let header = ReplayHeader::new(0, 8, 32, 42, &settings);
+        let script = [joystick(0, 0); 300]
+            .into_iter()
+            .chain([joystick(-1, 0); 40])
+            .chain([press(); 3])
+            .chain([joystick(1, 1); 1000]);
+        let controller = ScriptedController::new(script).then_repeat(press());
+        let mut recorder = RecordingController::new(controller, Vec::new(), header);
+
+        let mut display = RecordingDisplay::<8, 32>::new();
+        let timer = VirtualTimer::with_limit(60 * 60 * 1000);
+        let mut audio = RecordingAudio::new();
+        let recorded = block_on(play(
+            0,
+            42,
+            &settings,
+            &mut display,
+            &mut audio,
+            &mut recorder,
+            &timer,
+        ));
+        let (_, data) = recorder.finish();
+        // Long idle stretches are stored as runs
+        assert!(data.len() < 200, "{} bytes", data.len());
+
+        let replay = Replay::parse_for(&data, 8, 32).unwrap();
+        assert_eq!(replay.header, header);
+        let mut controller = replay.controller();
+        let mut replayed_display = RecordingDisplay::<8, 32>::new();
+        let mut replayed_audio = RecordingAudio::new();
+        let replayed = block_on(play(
+            replay.header.game,
+            replay.header.seed,
+            &replay.header.settings(),
+            &mut replayed_display,
+            &mut replayed_audio,
+            &mut controller,
+            &VirtualTimer::new(),
+        ));
+        assert_eq!(replayed, recorded);
+        assert!(controller.is_finished());
+        assert_eq!(replayed_display.frames(), display.frames());
+        assert_eq!(replayed_audio.played(), audio.played());
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert_eq!(Replay::parse(b"TR").unwrap_err(), ReplayError::Truncated);
+        assert_eq!(
+            Replay::parse(b"XYZ\x01\x00\x00\x00\x00\x00").unwrap_err(),
+            ReplayError::BadMagic
+        );
+        assert_eq!(
+            Replay::parse(b"TRP\x07\x00\x00\x00\x00\x00").unwrap_err(),
+            ReplayError::UnsupportedVersion(7)
+        );
+        assert_eq!(
+            Replay::parse(b"TRP\x02\x00\x00\x00\x00\x00").unwrap_err(),
+            ReplayError::Truncated
+        );
+
+        let header = ReplayHeader::new(0, 8, 32, 1, &Settings::default()).encode();
+        assert!(Replay::parse_for(&header, 8, 32).is_ok());
+        assert_eq!(
+            Replay::parse_for(&header, 16, 32).unwrap_err(),
+            ReplayError::WrongSize(8, 32)
+        );
+    }
+}
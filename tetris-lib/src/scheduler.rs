@@ -0,0 +1,172 @@
+// Fixed-timestep scheduling
+//
+// Game logic advances in ticks of a fixed length measured against the
+// monotonic clock of the `Timer`, not by counting sleeps: time spent reading
+// the controller (ADC conversions, browser timers firing late, a busy
+// terminal) is made up for by running the ticks that fell due in a row.
+// Frames are paced separately, so a catch-up doesn't render every tick.
+
+/// A late clock is caught up by at most this many ticks in a row, beyond that
+/// the game slows down rather than freezing the screen
+pub const MAX_CATCH_UP_TICKS: u32 = 5;
+
+/// Shortest time between two frames by default
+pub const DEFAULT_FRAME_MILLIS: u64 = 20;
+
+/// Decides when logic ticks are due and when a frame should be rendered
+pub struct Scheduler {
+    tick_millis: u64,
+    frame_millis: u64,
+    next_tick: u64,
+    next_frame: u64,
+    /// Ticks ran since the last frame
+    pending: bool,
+}
+
+impl Scheduler {
+    /// Ticks of `tick_millis` starting at `now`
+    pub fn new(tick_millis: u64, now: u64) -> Self {
+        Self {
+            tick_millis: tick_millis.max(1),
+            frame_millis: DEFAULT_FRAME_MILLIS,
+            next_tick: now,
+            next_frame: now,
+            pending: false,
+        }
+    }
+
+    /// Renders at most one frame every `frame_millis`
+    pub fn with_frame_millis(mut self, frame_millis: u64) -> Self {
+        self.frame_millis = frame_millis;
+        self
+    }
+
+    pub fn tick_millis(&self) -> u64 {
+        self.tick_millis
+    }
+
+    /// Number of ticks to run at `now`
+    pub fn due_ticks(&mut self, now: u64) -> u32 {
+        if now < self.next_tick {
+            return 0;
+        }
+        let behind = (now - self.next_tick) / self.tick_millis + 1;
+        let ticks = behind.min(MAX_CATCH_UP_TICKS as u64);
+        self.next_tick = if behind > ticks {
+            // Too far behind, drop the backlog
+            now + self.tick_millis
+        } else {
+            self.next_tick + ticks * self.tick_millis
+        };
+        self.pending = true;
+        ticks as u32
+    }
+
+    /// Whether a frame should be rendered at `now`: some ticks ran since the
+    /// last one and the frame interval passed
+    pub fn frame_due(&mut self, now: u64) -> bool {
+        if !self.pending || now < self.next_frame {
+            return false;
+        }
+        self.pending = false;
+        self.next_frame = now + self.frame_millis;
+        true
+    }
+
+    /// Time to sleep from `now` until there is something to do
+    pub fn millis_until_next(&self, now: u64) -> u64 {
+        let next = if self.pending {
+            self.next_tick.min(self.next_frame)
+        } else {
+            self.next_tick
+        };
+        next.saturating_sub(now)
+    }
+}
+
+/// Fires once per `period` of progress. Games advance it every tick by their
+/// current speed instead of keeping their own step counters.
+#[derive(Clone, Copy, Debug)]
+pub struct Cadence {
+    period: u16,
+    progress: u16,
+}
+
+impl Cadence {
+    pub const fn new(period: u16) -> Self {
+        Self {
+            period,
+            progress: 0,
+        }
+    }
+
+    /// Cadence firing on the first `advance`
+    pub const fn due(period: u16) -> Self {
+        Self {
+            period,
+            progress: period,
+        }
+    }
+
+    /// Adds `speed` to the progress, returns true (and starts over) once a
+    /// whole period is reached
+    pub fn advance(&mut self, speed: u16) -> bool {
+        self.progress = self.progress.saturating_add(speed);
+        if self.progress >= self.period {
+            self.progress = 0;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn late_ticks_are_caught_up() {
+        let mut scheduler = Scheduler::new(50, 1000);
+        assert_eq!(scheduler.due_ticks(1000), 1);
+        assert_eq!(scheduler.due_ticks(1020), 0);
+        assert_eq!(scheduler.millis_until_next(1020), 0);
+        assert!(scheduler.frame_due(1020));
+        assert_eq!(scheduler.millis_until_next(1020), 30);
+        // Input took 80 ms, the ticks at 1050 and 1100 are due
+        assert_eq!(scheduler.due_ticks(1100), 2);
+        assert!(scheduler.frame_due(1110));
+        assert_eq!(scheduler.millis_until_next(1110), 40);
+        // Far behind, only a few ticks are run and the rest is dropped
+        assert_eq!(scheduler.due_ticks(5000), MAX_CATCH_UP_TICKS);
+        assert_eq!(scheduler.due_ticks(5049), 0);
+        assert_eq!(scheduler.due_ticks(5050), 1);
+    }
+
+    #[test]
+    fn frames_are_paced() {
+        let mut scheduler = Scheduler::new(10, 0).with_frame_millis(25);
+        assert_eq!(scheduler.due_ticks(0), 1);
+        assert!(scheduler.frame_due(0));
+        assert_eq!(scheduler.due_ticks(10), 1);
+        assert!(!scheduler.frame_due(10));
+        assert_eq!(scheduler.due_ticks(20), 1);
+        assert!(!scheduler.frame_due(20));
+        assert_eq!(scheduler.millis_until_next(20), 5);
+        assert!(scheduler.frame_due(25));
+        // Nothing changed since
+        assert!(!scheduler.frame_due(60));
+    }
+
+    #[test]
+    fn cadence_follows_speed() {
+        let mut cadence = Cadence::due(4);
+        let fired: [bool; 6] = core::array::from_fn(|_| cadence.advance(1));
+        assert_eq!(fired, [true, false, false, false, true, false]);
+
+        let mut cadence = Cadence::new(4);
+        assert!(!cadence.advance(3));
+        assert!(cadence.advance(3));
+        assert!(!cadence.advance(1));
+    }
+}
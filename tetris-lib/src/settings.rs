@@ -0,0 +1,442 @@
+// Player settings
+//
+// Everything the settings page of the menu edits. Games get the settings when
+// they are created, the menu applies the rest to the display, the audio and
+// the theme. They are kept in `Storage` under the "settings" key as:
+//
+//   "ST" | version | brightness | start level | muted | theme | controls |
+//   randomizer | lock delay (u16 milliseconds, little endian) | Tetris mode |
+//   ghost
+//
+// Settings written by another version are ignored.
+
+use crate::color::DEFAULT_BRIGHTNESS;
+use crate::common::{GameController, InputSnapshot};
+use crate::storage::Storage;
+use crate::theme::THEMES;
+
+const KEY: &str = "settings";
+const MAGIC: &[u8; 2] = b"ST";
+const VERSION: u8 = 1;
+
+/// Highest level games can start at
+pub const MAX_LEVEL: u8 = 9;
+
+/// Brightness steps of the settings page, shown as 1 to 9
+pub const BRIGHTNESS_LEVELS: [u8; 9] = [16, 32, 48, 64, 96, 128, 160, 208, 255];
+
+/// Lock delay steps of the settings page in milliseconds, and how they are
+/// shown
+pub const LOCK_DELAYS: [(u16, &str); 4] = [(0, "OFF"), (250, "250"), (500, "500"), (1000, "1000")];
+
+/// Lock delay until the player picks one, the usual half second
+const DEFAULT_LOCK_DELAY_MILLIS: u16 = 500;
+
+const NUMBERS: [&str; 10] = ["0", "1", "2", "3", "4", "5", "6", "7", "8", "9"];
+
+/// How the joystick maps to directions
+#[derive(Default, Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Controls {
+    #[default]
+    Normal,
+    /// Both axes reversed, for a controller mounted upside down
+    Reversed,
+}
+
+impl Controls {
+    /// Controls stored as `byte`, `None` for unknown ones
+    pub fn decode(byte: u8) -> Option<Self> {
+        match byte {
+            0 => Some(Controls::Normal),
+            1 => Some(Controls::Reversed),
+            _ => None,
+        }
+    }
+}
+
+/// How Tetris picks the tetraminos to come
+#[derive(Default, Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Randomizer {
+    /// Each one picked at random, the same one may come many times in a row
+    Classic,
+    /// The seven tetraminos shuffled, one bag after the other
+    #[default]
+    Bag,
+}
+
+impl Randomizer {
+    /// Randomizer stored as `byte`, `None` for unknown ones
+    pub fn decode(byte: u8) -> Option<Self> {
+        match byte {
+            0 => Some(Randomizer::Classic),
+            1 => Some(Randomizer::Bag),
+            _ => None,
+        }
+    }
+}
+
+/// Goal of a Tetris game
+#[derive(Default, Copy, Clone, PartialEq, Eq, Debug)]
+pub enum TetrisMode {
+    /// Up to level 15
+    #[default]
+    Marathon,
+    /// 40 lines as fast as possible
+    Sprint,
+    /// As many points as possible in two minutes
+    Ultra,
+    /// As long as possible while garbage rows come up from the bottom
+    Survival,
+}
+
+impl TetrisMode {
+    pub const ALL: [TetrisMode; 4] = [
+        TetrisMode::Marathon,
+        TetrisMode::Sprint,
+        TetrisMode::Ultra,
+        TetrisMode::Survival,
+    ];
+
+    /// Mode stored as `byte`, `None` for unknown ones
+    pub fn decode(byte: u8) -> Option<Self> {
+        Self::ALL.get(byte as usize).copied()
+    }
+}
+
+/// What the settings page can change, in page order, and the Tetris mode,
+/// picked as a Tetris game starts
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Item {
+    Brightness,
+    StartLevel,
+    Sound,
+    Theme,
+    Controls,
+    Randomizer,
+    LockDelay,
+    Ghost,
+    TetrisMode,
+}
+
+impl Item {
+    pub const ALL: [Item; 8] = [
+        Item::Brightness,
+        Item::StartLevel,
+        Item::Sound,
+        Item::Theme,
+        Item::Controls,
+        Item::Randomizer,
+        Item::LockDelay,
+        Item::Ghost,
+    ];
+
+    /// Two letter label, the width of an 8 pixel panel
+    pub fn label(self) -> &'static str {
+        match self {
+            Item::Brightness => "BR",
+            Item::StartLevel => "LV",
+            Item::Sound => "SN",
+            Item::Theme => "TH",
+            Item::Controls => "JS",
+            Item::Randomizer => "RN",
+            Item::LockDelay => "LD",
+            Item::Ghost => "GH",
+            Item::TetrisMode => "MD",
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct Settings {
+    /// LED brightness, 0 to 255
+    pub brightness: u8,
+    /// Level games start at, 0 to `MAX_LEVEL`
+    pub start_level: u8,
+    pub muted: bool,
+    /// Index of the color theme in `THEMES`
+    pub theme: u8,
+    pub controls: Controls,
+    pub randomizer: Randomizer,
+    /// Time a Tetris piece can still be moved once it landed
+    pub lock_delay_millis: u16,
+    /// Tetris mode picked last
+    pub tetris_mode: TetrisMode,
+    /// Whether Tetris shows where the falling piece lands
+    pub ghost: bool,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            brightness: DEFAULT_BRIGHTNESS,
+            start_level: 0,
+            muted: false,
+            theme: 0,
+            controls: Controls::Normal,
+            randomizer: Randomizer::Bag,
+            lock_delay_millis: DEFAULT_LOCK_DELAY_MILLIS,
+            tetris_mode: TetrisMode::Marathon,
+            ghost: true,
+        }
+    }
+}
+
+impl Settings {
+    pub const ENCODED_SIZE: usize = 13;
+
+    /// Reads the settings from `storage`, `None` if none were saved
+    pub async fn load<S: Storage>(storage: &mut S) -> Option<Self> {
+        let mut buf = [0; Self::ENCODED_SIZE];
+        let len = storage.load(KEY, &mut buf).await?;
+        Self::decode(&buf[..len])
+    }
+
+    pub async fn save<S: Storage>(&self, storage: &mut S) {
+        storage.save(KEY, &self.encode()).await;
+    }
+
+    pub fn encode(&self) -> [u8; Self::ENCODED_SIZE] {
+        let lock_delay = self.lock_delay_millis.to_le_bytes();
+        [
+            MAGIC[0],
+            MAGIC[1],
+            VERSION,
+            self.brightness,
+            self.start_level,
+            self.muted as u8,
+            self.theme,
+            self.controls as u8,
+            self.randomizer as u8,
+            lock_delay[0],
+            lock_delay[1],
+            self.tetris_mode as u8,
+            self.ghost as u8,
+        ]
+    }
+
+    pub fn decode(data: &[u8]) -> Option<Self> {
+        if data.len() != Self::ENCODED_SIZE || &data[..2] != MAGIC || data[2] != VERSION {
+            return None;
+        }
+        let controls = Controls::decode(data[7])?;
+        let randomizer = Randomizer::decode(data[8])?;
+        let tetris_mode = TetrisMode::decode(data[11])?;
+        Some(Self {
+            brightness: data[3],
+            start_level: data[4].min(MAX_LEVEL),
+            muted: data[5] != 0,
+            theme: data[6] % THEMES.len() as u8,
+            controls,
+            randomizer,
+            lock_delay_millis: u16::from_le_bytes([data[9], data[10]]),
+            tetris_mode,
+            ghost: data[12] != 0,
+        })
+    }
+
+    /// Position of the brightness in `BRIGHTNESS_LEVELS`, the first step at
+    /// least as bright
+    fn brightness_step(&self) -> usize {
+        BRIGHTNESS_LEVELS
+            .iter()
+            .position(|&level| level >= self.brightness)
+            .unwrap_or(BRIGHTNESS_LEVELS.len() - 1)
+    }
+
+    /// Position of the lock delay in `LOCK_DELAYS`, the first step at least
+    /// as long
+    fn lock_delay_step(&self) -> usize {
+        LOCK_DELAYS
+            .iter()
+            .position(|&(millis, _)| millis >= self.lock_delay_millis)
+            .unwrap_or(LOCK_DELAYS.len() - 1)
+    }
+
+    /// Steps `item` forward (`delta` > 0) or back, returns whether it changed
+    pub fn change(&mut self, item: Item, delta: i8) -> bool {
+        let before = *self;
+        let step = |value: u8, count: usize| {
+            let count = count as i16;
+            ((value as i16 + delta.signum() as i16).rem_euclid(count)) as u8
+        };
+        match item {
+            Item::Brightness => {
+                let step = self.brightness_step() as i16 + delta.signum() as i16;
+                let step = step.clamp(0, BRIGHTNESS_LEVELS.len() as i16 - 1);
+                self.brightness = BRIGHTNESS_LEVELS[step as usize];
+            }
+            Item::StartLevel => {
+                let level = self.start_level as i16 + delta.signum() as i16;
+                self.start_level = level.clamp(0, MAX_LEVEL as i16) as u8;
+            }
+            Item::Sound => self.muted = !self.muted,
+            Item::Ghost => self.ghost = !self.ghost,
+            Item::Theme => self.theme = step(self.theme, THEMES.len()),
+            Item::Controls => {
+                self.controls = match self.controls {
+                    Controls::Normal => Controls::Reversed,
+                    Controls::Reversed => Controls::Normal,
+                }
+            }
+            Item::Randomizer => {
+                self.randomizer = match self.randomizer {
+                    Randomizer::Classic => Randomizer::Bag,
+                    Randomizer::Bag => Randomizer::Classic,
+                }
+            }
+            Item::LockDelay => {
+                let step = self.lock_delay_step() as i16 + delta.signum() as i16;
+                let step = step.clamp(0, LOCK_DELAYS.len() as i16 - 1);
+                self.lock_delay_millis = LOCK_DELAYS[step as usize].0;
+            }
+            Item::TetrisMode => {
+                let mode = step(self.tetris_mode as u8, TetrisMode::ALL.len());
+                self.tetris_mode = TetrisMode::ALL[mode as usize];
+            }
+        }
+        *self != before
+    }
+
+    /// Value of `item` as shown on the settings page
+    pub fn value(&self, item: Item) -> &'static str {
+        match item {
+            Item::Brightness => NUMBERS[self.brightness_step() + 1],
+            Item::StartLevel => NUMBERS[self.start_level.min(MAX_LEVEL) as usize],
+            Item::Sound if self.muted => "OFF",
+            Item::Sound => "ON",
+            Item::Theme => THEMES[self.theme as usize % THEMES.len()].name,
+            Item::Controls => match self.controls {
+                Controls::Normal => "NORMAL",
+                Controls::Reversed => "REVERSED",
+            },
+            Item::Randomizer => match self.randomizer {
+                Randomizer::Classic => "RANDOM",
+                Randomizer::Bag => "7-BAG",
+            },
+            Item::LockDelay => LOCK_DELAYS[self.lock_delay_step()].1,
+            Item::Ghost if self.ghost => "ON",
+            Item::Ghost => "OFF",
+            Item::TetrisMode => match self.tetris_mode {
+                TetrisMode::Marathon => "MARATHON",
+                TetrisMode::Sprint => "SPRINT 40L",
+                TetrisMode::Ultra => "ULTRA",
+                TetrisMode::Survival => "SURVIVAL",
+            },
+        }
+    }
+}
+
+/// Controller applying the `Controls` setting to the joystick
+pub struct SettingsController<'a, C> {
+    inner: &'a mut C,
+    controls: Controls,
+}
+
+impl<'a, C: GameController> SettingsController<'a, C> {
+    pub fn new(inner: &'a mut C, settings: &Settings) -> Self {
+        Self {
+            inner,
+            controls: settings.controls,
+        }
+    }
+
+    fn axis(&self, value: i8) -> i8 {
+        match self.controls {
+            Controls::Normal => value,
+            Controls::Reversed => -value,
+        }
+    }
+}
+
+impl<C: GameController> GameController for SettingsController<'_, C> {
+    async fn read_x(&mut self) -> i8 {
+        let x = self.inner.read_x().await;
+        self.axis(x)
+    }
+
+    async fn read_y(&mut self) -> i8 {
+        let y = self.inner.read_y().await;
+        self.axis(y)
+    }
+
+    fn joystick_was_pressed(&self) -> bool {
+        self.inner.joystick_was_pressed()
+    }
+
+    fn a_was_pressed(&self) -> bool {
+        self.inner.a_was_pressed()
+    }
+
+    fn b_was_pressed(&self) -> bool {
+        self.inner.b_was_pressed()
+    }
+
+    fn ab_held(&self) -> bool {
+        self.inner.ab_held()
+    }
+
+    async fn read_input(&mut self) -> InputSnapshot {
+        let input = self.inner.read_input().await;
+        InputSnapshot {
+            x: self.axis(input.x),
+            y: self.axis(input.y),
+            ..input
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{block_on, MemoryStorage};
+
+    #[test]
+    fn settings_survive_storage() {
+        let mut storage = MemoryStorage::new();
+        assert_eq!(block_on(Settings::load(&mut storage)), None);
+
+        let settings = Settings {
+            brightness: 64,
+            start_level: 3,
+            muted: true,
+            theme: 2,
+            controls: Controls::Reversed,
+            randomizer: Randomizer::Classic,
+            lock_delay_millis: 250,
+            tetris_mode: TetrisMode::Ultra,
+            ghost: false,
+        };
+        block_on(settings.save(&mut storage));
+        assert_eq!(block_on(Settings::load(&mut storage)), Some(settings));
+
+        let mut data = settings.encode();
+        data[2] = VERSION + 1;
+        assert_eq!(Settings::decode(&data), None);
+    }
+
+    #[test]
+    fn items_step_through_their_values() {
+        let mut settings = Settings::default();
+        assert_eq!(settings.value(Item::Brightness), "6");
+        for _ in 0..BRIGHTNESS_LEVELS.len() {
+            settings.change(Item::Brightness, 1);
+        }
+        assert_eq!(settings.brightness, 255);
+        assert!(!settings.change(Item::Brightness, 1));
+
+        assert!(!settings.change(Item::StartLevel, -1));
+        assert!(settings.change(Item::StartLevel, 1));
+        assert_eq!(settings.value(Item::StartLevel), "1");
+
+        // Themes wrap around
+        settings.change(Item::Theme, -1);
+        assert_eq!(settings.theme as usize, THEMES.len() - 1);
+
+        settings.change(Item::Sound, 1);
+        assert_eq!(settings.value(Item::Sound), "OFF");
+
+        // So do modes
+        settings.change(Item::TetrisMode, -1);
+        assert_eq!(settings.tetris_mode, TetrisMode::Survival);
+    }
+}
@@ -0,0 +1,27 @@
+// Persistent storage
+//
+// Platforms keep a few small blobs (the high-score table, later settings)
+// across power cycles. Each blob lives under a short key; what a key maps to
+// is up to the backend: a file, a browser `localStorage` entry or a flash
+// sector.
+
+/// Small key-value store surviving restarts
+pub trait Storage {
+    /// Reads the value stored under `key` into `buf` and returns its length,
+    /// or `None` if there is no value (or it doesn't fit `buf`)
+    async fn load(&mut self, key: &str, buf: &mut [u8]) -> Option<usize>;
+    /// Replaces the value stored under `key`
+    async fn save(&mut self, key: &str, data: &[u8]);
+}
+
+/// Storage for platforms without persistence: nothing is ever stored
+#[derive(Default, Copy, Clone, Debug)]
+pub struct NoStorage;
+
+impl Storage for NoStorage {
+    async fn load(&mut self, _key: &str, _buf: &mut [u8]) -> Option<usize> {
+        None
+    }
+
+    async fn save(&mut self, _key: &str, _data: &[u8]) {}
+}
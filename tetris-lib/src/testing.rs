@@ -0,0 +1,487 @@
+// Headless simulation harness
+//
+// Stand-ins for the platform traits so that whole games can run under
+// `cargo test`: a display that records every frame, a controller fed from a
+// script of per-tick inputs, a timer that doesn't actually wait, audio
+// remembering what it played and an in-memory storage. Enabled with the
+// `testing` feature (and always for the crate's own tests).
+
+use alloc::collections::{BTreeMap, VecDeque};
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::cell::Cell;
+use core::future::Future;
+use core::pin::pin;
+use core::task::{Context, Poll, Waker};
+
+use smart_leds::RGB8;
+
+use crate::audio::{Audio, Note};
+use crate::common::{
+    get_pixel, FrameBuffer, GameController, InputSnapshot, LedBuffer, LedDisplay, Pixel, Timer,
+    BLACK_IDX,
+};
+use crate::layout::LedLayout;
+use crate::replay::ReplaySink;
+use crate::storage::Storage;
+use crate::theme::{Theme, CLASSIC};
+
+/// Polls `future` once, which runs it until it's done or stalls on a
+/// `ScriptedController` whose script ran out (see `then_stall`). For code
+/// like the menu that never returns.
+pub fn run_until_stalled<F: Future>(future: F) -> Option<F::Output> {
+    let mut cx = Context::from_waker(Waker::noop());
+    match pin!(future).poll(&mut cx) {
+        Poll::Ready(output) => Some(output),
+        Poll::Pending => None,
+    }
+}
+
+/// Runs a future to completion on the current thread.
+///
+/// The harness never waits on anything, so the future is simply polled until
+/// it's done.
+pub fn block_on<F: Future>(future: F) -> F::Output {
+    let mut future = pin!(future);
+    let mut cx = Context::from_waker(Waker::noop());
+    loop {
+        if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+            return output;
+        }
+    }
+}
+
+/// Text picture of a frame, one line per row: `.` for black pixels, the role
+/// in base 36 for theme colors and `*` for shades and other colors
+pub fn ascii<const W: usize, const H: usize>(frame: &FrameBuffer<W, H>) -> String {
+    let mut out = String::with_capacity((W + 1) * H);
+    for y in 0..H {
+        for x in 0..W {
+            out.push(match frame.pixel(x, y) {
+                Pixel::Palette(BLACK_IDX) => '.',
+                Pixel::Palette(role) => char::from_digit(role as u32, 36).unwrap_or('?'),
+                Pixel::Shade(..) | Pixel::Rgb(_) => '*',
+            });
+        }
+        out.push('\n');
+    }
+    out
+}
+
+/// A frame as the panel showed it: the color of every pixel, row by row
+pub type Colors<const W: usize, const H: usize> = [[RGB8; W]; H];
+
+/// Display keeping the colors of every frame written to it
+pub struct RecordingDisplay<const W: usize, const H: usize> {
+    layout: LedLayout,
+    theme: &'static Theme,
+    frames: Vec<Colors<W, H>>,
+}
+
+impl<const W: usize, const H: usize> RecordingDisplay<W, H> {
+    pub fn new() -> Self {
+        Self::with_layout(LedLayout::SERPENTINE)
+    }
+
+    /// Records a panel wired with `layout`, so frames go through the same
+    /// pixel mapping as on hardware
+    pub fn with_layout(layout: LedLayout) -> Self {
+        Self {
+            layout,
+            theme: &CLASSIC,
+            frames: Vec::new(),
+        }
+    }
+
+    pub fn frames(&self) -> &[Colors<W, H>] {
+        &self.frames
+    }
+
+    pub fn last_frame(&self) -> Option<&Colors<W, H>> {
+        self.frames.last()
+    }
+
+    /// Colors `frame` shows in the theme of the display, to compare with the
+    /// recorded frames
+    pub fn colors(&self, frame: &FrameBuffer<W, H>) -> Colors<W, H> {
+        let mut colors = [[RGB8::default(); W]; H];
+        for (y, row) in colors.iter_mut().enumerate() {
+            for (x, color) in row.iter_mut().enumerate() {
+                *color = frame.pixel(x, y).color(self.theme);
+            }
+        }
+        colors
+    }
+
+    /// ASCII dump of the frame at `index`, see [`ascii`]. Roles sharing a
+    /// color show as the first of them.
+    pub fn dump(&self, index: usize) -> Option<String> {
+        let colors = self.frames.get(index)?;
+        let mut frame = FrameBuffer::<W, H>::new();
+        for (y, row) in colors.iter().enumerate() {
+            for (x, &color) in row.iter().enumerate() {
+                frame.set_pixel(x, y, Pixel::from_color(color, self.theme));
+            }
+        }
+        Some(ascii(&frame))
+    }
+}
+
+impl<const W: usize, const H: usize> Default for RecordingDisplay<W, H> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const W: usize, const H: usize> LedDisplay<W, H> for RecordingDisplay<W, H> {
+    async fn write(&mut self, leds: &LedBuffer<W, H>) {
+        let mut colors = [[RGB8::default(); W]; H];
+        for (y, row) in colors.iter_mut().enumerate() {
+            for (x, color) in row.iter_mut().enumerate() {
+                *color = get_pixel(leds, &self.layout, x, y);
+            }
+        }
+        self.frames.push(colors);
+    }
+
+    fn layout(&self) -> LedLayout {
+        self.layout
+    }
+
+    fn theme(&self) -> &'static Theme {
+        self.theme
+    }
+
+    fn set_theme(&mut self, theme: &'static Theme) {
+        self.theme = theme;
+    }
+}
+
+/// Controller replaying a script with one input per tick.
+///
+/// Every `read_input` (or `read_x`, for code polling the axes one by one)
+/// starts a new tick and takes the next input of the script. Once the script
+/// runs out the `idle` input is repeated. Like on the real backends, a button
+/// press is reported only once.
+pub struct ScriptedController {
+    script: VecDeque<InputSnapshot>,
+    idle: InputSnapshot,
+    current: Cell<InputSnapshot>,
+    ticks: usize,
+    stall: bool,
+}
+
+impl ScriptedController {
+    pub fn new(script: impl IntoIterator<Item = InputSnapshot>) -> Self {
+        Self {
+            script: script.into_iter().collect(),
+            idle: InputSnapshot::default(),
+            current: Cell::new(InputSnapshot::default()),
+            ticks: 0,
+            stall: false,
+        }
+    }
+
+    /// Input repeated after the script is exhausted
+    pub fn then_repeat(mut self, idle: InputSnapshot) -> Self {
+        self.idle = idle;
+        self
+    }
+
+    /// Never hands out another tick once the script is exhausted
+    pub fn then_stall(mut self) -> Self {
+        self.stall = true;
+        self
+    }
+
+    /// Number of ticks played so far
+    pub fn ticks(&self) -> usize {
+        self.ticks
+    }
+
+    pub fn is_exhausted(&self) -> bool {
+        self.script.is_empty()
+    }
+
+    async fn advance(&mut self) -> InputSnapshot {
+        if self.stall && self.script.is_empty() {
+            core::future::pending::<()>().await;
+        }
+        self.ticks += 1;
+        let input = self.script.pop_front().unwrap_or(self.idle);
+        self.current.set(input);
+        input
+    }
+
+    /// Reads a press of the current tick and clears it
+    fn take(&self, button: fn(&mut InputSnapshot) -> &mut bool) -> bool {
+        let mut input = self.current.get();
+        let pressed = core::mem::take(button(&mut input));
+        self.current.set(input);
+        pressed
+    }
+}
+
+impl GameController for ScriptedController {
+    async fn read_x(&mut self) -> i8 {
+        self.advance().await.x
+    }
+
+    async fn read_y(&mut self) -> i8 {
+        self.current.get().y
+    }
+
+    fn joystick_was_pressed(&self) -> bool {
+        self.take(|input| &mut input.joystick)
+    }
+
+    fn a_was_pressed(&self) -> bool {
+        self.take(|input| &mut input.a)
+    }
+
+    fn b_was_pressed(&self) -> bool {
+        self.take(|input| &mut input.b)
+    }
+
+    fn ab_held(&self) -> bool {
+        self.current.get().ab_held
+    }
+
+    async fn read_input(&mut self) -> InputSnapshot {
+        let input = self.advance().await;
+        // The presses were just read
+        self.current.set(InputSnapshot {
+            joystick: false,
+            a: false,
+            b: false,
+            ..input
+        });
+        input
+    }
+}
+
+/// Timer that returns immediately and only counts the time it should have slept
+#[derive(Default)]
+pub struct VirtualTimer {
+    elapsed: Cell<u64>,
+    limit: Option<u64>,
+}
+
+impl VirtualTimer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Panics once more than `millis` of virtual time passed, so that a game
+    /// which never ends fails the test instead of hanging it
+    pub fn with_limit(millis: u64) -> Self {
+        Self {
+            elapsed: Cell::new(0),
+            limit: Some(millis),
+        }
+    }
+
+    pub fn elapsed_millis(&self) -> u64 {
+        self.elapsed.get()
+    }
+}
+
+impl Timer for VirtualTimer {
+    async fn sleep_millis(&self, millis: u64) {
+        let elapsed = self.elapsed.get() + millis;
+        if let Some(limit) = self.limit {
+            assert!(
+                elapsed <= limit,
+                "virtual time limit of {limit} ms exceeded"
+            );
+        }
+        self.elapsed.set(elapsed);
+    }
+
+    fn now_millis(&self) -> u64 {
+        self.elapsed.get()
+    }
+}
+
+impl ReplaySink for Vec<u8> {
+    fn write(&mut self, bytes: &[u8]) {
+        self.extend_from_slice(bytes);
+    }
+}
+
+/// Audio keeping the melodies and tones it was asked to play
+#[derive(Default)]
+pub struct RecordingAudio {
+    played: Vec<&'static [Note]>,
+    tones: Vec<Note>,
+}
+
+impl RecordingAudio {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn played(&self) -> &[&'static [Note]] {
+        &self.played
+    }
+
+    pub fn tones(&self) -> &[Note] {
+        &self.tones
+    }
+}
+
+impl Audio for RecordingAudio {
+    fn play(&mut self, melody: &'static [Note]) {
+        self.played.push(melody);
+    }
+
+    fn tone(&mut self, note: Note) {
+        self.tones.push(note);
+    }
+
+    fn stop(&mut self) {}
+}
+
+/// Storage keeping its values in memory
+#[derive(Default)]
+pub struct MemoryStorage {
+    values: BTreeMap<String, Vec<u8>>,
+}
+
+impl MemoryStorage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get(&self, key: &str) -> Option<&[u8]> {
+        self.values.get(key).map(Vec::as_slice)
+    }
+}
+
+impl Storage for MemoryStorage {
+    async fn load(&mut self, key: &str, buf: &mut [u8]) -> Option<usize> {
+        let value = self.values.get(key)?;
+        buf.get_mut(..value.len())?.copy_from_slice(value);
+        Some(value.len())
+    }
+
+    async fn save(&mut self, key: &str, data: &[u8]) {
+        self.values.insert(key.into(), data.into());
+    }
+}
+
+/// Input with the joystick deflected by (`x`, `y`)
+pub fn joystick(x: i8, y: i8) -> InputSnapshot {
+    InputSnapshot {
+        x,
+        y,
+        ..Default::default()
+    }
+}
+
+/// Input with only the joystick button pressed
+pub fn press() -> InputSnapshot {
+    InputSnapshot {
+        joystick: true,
+        ..Default::default()
+    }
+}
+
+/// Input with A and B held down, the quit gesture once it lasts
+pub fn hold_ab() -> InputSnapshot {
+    InputSnapshot {
+        ab_held: true,
+        ..Default::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::Sfx;
+    use crate::common::{EndReason, Game, Prng, BLACK};
+    use crate::driver::run_game;
+    use crate::games::life::LifeGame;
+    use crate::games::races::RacesGame;
+    use crate::games::tetris::TetrisGame;
+    use crate::settings::Settings;
+
+    #[test]
+    fn tetris_runs_to_game_over() {
+        let mut game = TetrisGame::<8, 32>::new(Prng::new(1), &Settings::default());
+        let mut display = RecordingDisplay::new();
+        let mut audio = RecordingAudio::new();
+        // Drop every piece straight down, then keep pressing to leave the
+        // game over screen
+        let mut controller = ScriptedController::new([joystick(0, 1); 2000]).then_repeat(press());
+        let timer = VirtualTimer::with_limit(60 * 60 * 1000);
+
+        let outcome = block_on(run_game(
+            &mut game,
+            &mut display,
+            &mut audio,
+            &mut controller,
+            &timer,
+        ));
+        assert_eq!(outcome.reason, EndReason::GameOver);
+        assert_eq!(outcome.score, 0);
+        // Ticks where nothing moved didn't send a frame
+        assert!(display.frames().len() < controller.ticks() - 1);
+        assert_eq!(
+            timer.elapsed_millis(),
+            (controller.ticks() - 1) as u64 * TetrisGame::<8, 32>::TICK_MILLIS
+        );
+        assert_eq!(outcome.duration_millis, timer.elapsed_millis());
+
+        // Score digits on top, then the delimiter
+        let first = display.dump(0).unwrap();
+        let rows: Vec<&str> = first.lines().collect();
+        assert_eq!(rows.len(), 32);
+        assert_eq!(rows[5], "bbbbbbbb");
+        // The stack reaches the top of the playfield
+        let last = display.last_frame().unwrap();
+        assert!(last[7].iter().any(|&color| color != BLACK));
+        // Pieces landed with a thud, the game ended with its tune
+        assert!(audio.played().contains(&Sfx::Lock.melody()));
+        assert_eq!(audio.played().last(), Some(&Sfx::GameOver.melody()));
+    }
+
+    #[test]
+    fn races_runs_to_game_over_on_a_wide_panel() {
+        let mut game = RacesGame::<16, 16>::new(Prng::new(3), &Settings::default());
+        let mut display = RecordingDisplay::new();
+        let mut audio = RecordingAudio::new();
+        let mut controller = ScriptedController::new([]).then_repeat(press());
+        let timer = VirtualTimer::with_limit(60 * 60 * 1000);
+
+        block_on(run_game(
+            &mut game,
+            &mut display,
+            &mut audio,
+            &mut controller,
+            &timer,
+        ));
+        // Only the score is left on the game over screen, the road is gone
+        let last = display.last_frame().unwrap();
+        assert!(last.iter().all(|row| row[15] == BLACK));
+        assert!(audio.played().contains(&Sfx::Crash.melody()));
+    }
+
+    #[test]
+    fn frames_survive_the_panel_wiring() {
+        let game = LifeGame::<8, 8>::new(Prng::new(5));
+        let mut expected = FrameBuffer::new();
+        game.draw(&mut expected);
+
+        for layout in [
+            LedLayout::SERPENTINE,
+            "progressive-columns:bottom-left".parse().unwrap(),
+        ] {
+            let mut display = RecordingDisplay::<8, 8>::with_layout(layout);
+            let mut leds = [[RGB8::default(); 8]; 8];
+            expected.render(&mut leds, &display.layout(), display.theme());
+            block_on(display.write(&leds));
+            assert_eq!(display.frames(), &[display.colors(&expected)]);
+        }
+    }
+}
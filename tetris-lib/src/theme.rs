@@ -0,0 +1,214 @@
+// Color themes
+//
+// Games don't paint with colors but with roles: the player, an enemy, a
+// hazard, the HUD. The pixels of a `FrameBuffer` hold roles, and a theme
+// decides which color each role gets when the frame is rendered. Displays
+// render with the theme picked in the menu, see `LedDisplay::set_theme`.
+
+use smart_leds::RGB8;
+
+use crate::common::{
+    BLACK, BLUE, BRICK, DARK_GREEN, GREEN, LIGHT_BLUE, LIGHT_GREEN, PINK, RED, YELLOW,
+};
+
+// Roles
+pub const BACKGROUND: u8 = 0;
+pub const PLAYER: u8 = 1;
+/// Front of the player, e.g. the snake's head
+pub const PLAYER_HEAD: u8 = 2;
+/// Back of the player, e.g. the snake's tail
+pub const PLAYER_TAIL: u8 = 3;
+pub const ENEMY: u8 = 4;
+/// Things in the way that don't fight back
+pub const OBSTACLE: u8 = 5;
+/// Anything that hurts: missiles and bullets
+pub const HAZARD: u8 = 6;
+/// Things to collect: apples and power-ups
+pub const PICKUP: u8 = 7;
+pub const WALL: u8 = 8;
+/// Scores and other status
+pub const HUD: u8 = 9;
+/// Secondary status: lives, pause, a quit game
+pub const HUD_ALT: u8 = 10;
+/// Line between the HUD and the playfield
+pub const DELIMITER: u8 = 11;
+pub const HIGHLIGHT: u8 = 12;
+/// Something needing attention: a new record, the drawing cursor
+pub const ALERT: u8 = 13;
+/// Tetrominoes I, O, T, S, Z, J and L
+pub const PIECES: [u8; 7] = [14, 15, 16, 17, 18, 19, 20];
+
+pub const ROLE_COUNT: usize = 21;
+
+/// Colors for all roles
+#[derive(Debug, PartialEq, Eq)]
+pub struct Theme {
+    pub name: &'static str,
+    colors: [RGB8; ROLE_COUNT],
+}
+
+impl Theme {
+    /// Color of `role`, black for unknown roles
+    pub fn color(&self, role: u8) -> RGB8 {
+        self.colors.get(role as usize).copied().unwrap_or(BLACK)
+    }
+
+    /// First role shown with `color`
+    pub fn role(&self, color: RGB8) -> Option<u8> {
+        self.colors
+            .iter()
+            .position(|&c| c == color)
+            .map(|role| role as u8)
+    }
+}
+
+const fn rgb(r: u8, g: u8, b: u8) -> RGB8 {
+    RGB8::new(r, g, b)
+}
+
+const fn gray(level: u8) -> RGB8 {
+    RGB8::new(level, level, level)
+}
+
+/// The original look
+pub const CLASSIC: Theme = Theme {
+    name: "classic",
+    colors: [
+        BLACK,       // background
+        GREEN,       // player
+        LIGHT_GREEN, // player head
+        DARK_GREEN,  // player tail
+        BLUE,        // enemy
+        DARK_GREEN,  // obstacle
+        RED,         // hazard
+        RED,         // pickup
+        BRICK,       // wall
+        GREEN,       // hud
+        YELLOW,      // hud alt
+        PINK,        // delimiter
+        LIGHT_BLUE,  // highlight
+        PINK,        // alert
+        LIGHT_BLUE,  // I
+        YELLOW,      // O
+        PINK,        // T
+        GREEN,       // S
+        RED,         // Z
+        BLUE,        // J
+        BRICK,       // L
+    ],
+};
+
+/// Saturated colors, every role in play clearly apart
+pub const HIGH_CONTRAST: Theme = Theme {
+    name: "high-contrast",
+    colors: [
+        BLACK,
+        rgb(0, 255, 0),
+        rgb(180, 255, 180),
+        rgb(0, 140, 0),
+        rgb(0, 80, 255),
+        gray(140),
+        rgb(255, 0, 0),
+        rgb(255, 0, 255),
+        rgb(255, 120, 0),
+        gray(255),
+        rgb(255, 255, 0),
+        rgb(0, 255, 255),
+        rgb(0, 255, 255),
+        rgb(255, 0, 255),
+        rgb(0, 255, 255),
+        rgb(255, 255, 0),
+        rgb(255, 0, 255),
+        rgb(0, 255, 0),
+        rgb(255, 0, 0),
+        rgb(0, 0, 255),
+        rgb(255, 128, 0),
+    ],
+};
+
+/// Okabe-Ito colors, telling roles apart without relying on red and green
+pub const DEUTERANOPIA: Theme = Theme {
+    name: "deuteranopia",
+    colors: [
+        BLACK,
+        rgb(86, 180, 233),
+        rgb(170, 220, 245),
+        rgb(0, 114, 178),
+        rgb(230, 159, 0),
+        gray(120),
+        rgb(213, 94, 0),
+        rgb(240, 228, 66),
+        rgb(204, 121, 167),
+        gray(200),
+        rgb(240, 228, 66),
+        rgb(204, 121, 167),
+        rgb(86, 180, 233),
+        rgb(213, 94, 0),
+        rgb(86, 180, 233),
+        rgb(240, 228, 66),
+        rgb(204, 121, 167),
+        rgb(0, 158, 115),
+        rgb(213, 94, 0),
+        rgb(0, 114, 178),
+        rgb(230, 159, 0),
+    ],
+};
+
+/// Shades of white only
+pub const MONOCHROME: Theme = Theme {
+    name: "monochrome",
+    colors: [
+        BLACK,
+        gray(255),
+        gray(255),
+        gray(120),
+        gray(160),
+        gray(90),
+        gray(255),
+        gray(200),
+        gray(60),
+        gray(160),
+        gray(255),
+        gray(60),
+        gray(255),
+        gray(255),
+        gray(255),
+        gray(150),
+        gray(200),
+        gray(110),
+        gray(230),
+        gray(130),
+        gray(180),
+    ],
+};
+
+/// All themes, in menu order
+pub const THEMES: [&Theme; 4] = [&CLASSIC, &HIGH_CONTRAST, &DEUTERANOPIA, &MONOCHROME];
+
+/// Theme number `index`, `None` for unknown themes
+pub fn by_index(index: u8) -> Option<&'static Theme> {
+    THEMES.get(index as usize).copied()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn themes_cover_all_roles() {
+        for theme in THEMES {
+            assert_eq!(theme.color(BACKGROUND), BLACK, "{}", theme.name);
+            // Everything in play is visible
+            for role in 1..ROLE_COUNT as u8 {
+                assert_ne!(theme.color(role), BLACK, "{} {}", theme.name, role);
+            }
+            assert_eq!(theme.color(ROLE_COUNT as u8), BLACK);
+        }
+    }
+
+    #[test]
+    fn unknown_themes_are_ignored() {
+        assert!(by_index(THEMES.len() as u8).is_none());
+        assert_eq!(by_index(0).map(|theme| theme.name), Some("classic"));
+    }
+}
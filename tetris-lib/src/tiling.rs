@@ -0,0 +1,360 @@
+// Tiled multi-panel canvas
+//
+// Several physical panels chained on one LED strip are combined into a single
+// virtual canvas. Each panel (tile) has its own position on the canvas, mounting
+// rotation and wiring layout. The strip visits the tiles in the order they are
+// listed.
+
+use core::ops::Range;
+use core::str::FromStr;
+
+use smart_leds::RGB8;
+
+use crate::common::{LedBuffer, LedDisplay};
+use crate::layout::{LedLayout, StartCorner, Wiring};
+use crate::theme::Theme;
+
+/// Clockwise rotation a panel is mounted with
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Rotation {
+    Deg0,
+    Deg90,
+    Deg180,
+    Deg270,
+}
+
+/// One physical panel of the canvas
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Tile {
+    /// Canvas column of the panel's top left corner (after rotation)
+    pub x: usize,
+    /// Canvas row of the panel's top left corner (after rotation)
+    pub y: usize,
+    /// Panel width in its own (unrotated) coordinates
+    pub width: usize,
+    /// Panel height in its own (unrotated) coordinates
+    pub height: usize,
+    pub rotation: Rotation,
+    pub layout: LedLayout,
+}
+
+impl Tile {
+    pub const fn new(x: usize, y: usize, width: usize, height: usize) -> Self {
+        Self {
+            x,
+            y,
+            width,
+            height,
+            rotation: Rotation::Deg0,
+            layout: LedLayout::SERPENTINE,
+        }
+    }
+
+    pub const fn rotated(mut self, rotation: Rotation) -> Self {
+        self.rotation = rotation;
+        self
+    }
+
+    pub const fn with_layout(mut self, layout: LedLayout) -> Self {
+        self.layout = layout;
+        self
+    }
+
+    pub fn led_count(&self) -> usize {
+        self.width * self.height
+    }
+
+    /// Size the panel takes on the canvas
+    pub fn canvas_size(&self) -> (usize, usize) {
+        match self.rotation {
+            Rotation::Deg0 | Rotation::Deg180 => (self.width, self.height),
+            Rotation::Deg90 | Rotation::Deg270 => (self.height, self.width),
+        }
+    }
+
+    fn contains(&self, x: usize, y: usize) -> bool {
+        let (width, height) = self.canvas_size();
+        x >= self.x && x < self.x + width && y >= self.y && y < self.y + height
+    }
+
+    /// Canvas pixel of the panel pixel (`px`, `py`)
+    fn canvas_pixel(&self, px: usize, py: usize) -> (usize, usize) {
+        let (w, h) = (self.width, self.height);
+        let (x, y) = match self.rotation {
+            Rotation::Deg0 => (px, py),
+            Rotation::Deg90 => (h - 1 - py, px),
+            Rotation::Deg180 => (w - 1 - px, h - 1 - py),
+            Rotation::Deg270 => (py, w - 1 - px),
+        };
+        (self.x + x, self.y + y)
+    }
+
+    /// Panel pixel shown at canvas pixel (`x`, `y`), which must be inside the tile
+    fn panel_pixel(&self, x: usize, y: usize) -> (usize, usize) {
+        let (w, h) = (self.width, self.height);
+        let (x, y) = (x - self.x, y - self.y);
+        match self.rotation {
+            Rotation::Deg0 => (x, y),
+            Rotation::Deg90 => (y, h - 1 - x),
+            Rotation::Deg180 => (w - 1 - x, h - 1 - y),
+            Rotation::Deg270 => (w - 1 - y, x),
+        }
+    }
+}
+
+/// Error returned when a tile description can't be parsed
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct ParseTileError;
+
+impl FromStr for Tile {
+    type Err = ParseTileError;
+
+    /// Parses an `X,Y,WxH[,ROTATION][,LAYOUT]` description, for example
+    /// `8,0,8x32,180,serpentine:top-right`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.split(',');
+        let number = |value: Option<&str>| -> Result<usize, ParseTileError> {
+            value
+                .ok_or(ParseTileError)?
+                .trim()
+                .parse()
+                .map_err(|_| ParseTileError)
+        };
+        let x = number(parts.next())?;
+        let y = number(parts.next())?;
+        let (width, height) = parts
+            .next()
+            .and_then(|size| size.trim().split_once('x'))
+            .ok_or(ParseTileError)?;
+        let mut tile = Tile::new(x, y, number(Some(width))?, number(Some(height))?);
+
+        for part in parts {
+            tile.rotation = match part.trim() {
+                "0" => Rotation::Deg0,
+                "90" => Rotation::Deg90,
+                "180" => Rotation::Deg180,
+                "270" => Rotation::Deg270,
+                layout => {
+                    tile.layout = layout.parse().map_err(|_| ParseTileError)?;
+                    continue;
+                }
+            };
+        }
+        Ok(tile)
+    }
+}
+
+/// Mapping between a virtual canvas and the LED strip running through its tiles
+#[derive(Debug, Copy, Clone)]
+pub struct Tiling<'a> {
+    tiles: &'a [Tile],
+}
+
+impl<'a> Tiling<'a> {
+    pub const fn new(tiles: &'a [Tile]) -> Self {
+        Self { tiles }
+    }
+
+    pub fn tiles(&self) -> &'a [Tile] {
+        self.tiles
+    }
+
+    /// Total number of LEDs on the strip
+    pub fn led_count(&self) -> usize {
+        self.tiles.iter().map(Tile::led_count).sum()
+    }
+
+    /// Checks that all tiles lie on a `width`x`height` canvas, don't overlap
+    /// and that the strip isn't longer than the canvas
+    pub fn fits(&self, width: usize, height: usize) -> bool {
+        let inside = self.tiles.iter().all(|tile| {
+            let (w, h) = tile.canvas_size();
+            tile.x + w <= width && tile.y + h <= height
+        });
+        let overlaps = self.tiles.iter().enumerate().any(|(i, a)| {
+            self.tiles[i + 1..].iter().any(|b| {
+                let (aw, ah) = a.canvas_size();
+                let (bw, bh) = b.canvas_size();
+                a.x < b.x + bw && b.x < a.x + aw && a.y < b.y + bh && b.y < a.y + ah
+            })
+        });
+        inside && !overlaps && self.led_count() <= width * height
+    }
+
+    /// Index in the strip of the LED shown at canvas pixel (`x`, `y`), if any
+    /// panel covers it
+    pub fn index(&self, x: usize, y: usize) -> Option<usize> {
+        let mut base = 0;
+        for tile in self.tiles {
+            if tile.contains(x, y) {
+                let (px, py) = tile.panel_pixel(x, y);
+                return Some(base + tile.layout.index(px, py, tile.width, tile.height));
+            }
+            base += tile.led_count();
+        }
+        None
+    }
+
+    /// Canvas pixel lit by the LED at `index` in the strip
+    pub fn position(&self, index: usize) -> Option<(usize, usize)> {
+        let mut base = 0;
+        for tile in self.tiles {
+            if index < base + tile.led_count() {
+                let (px, py) = tile.layout.position(index - base, tile.width, tile.height);
+                return Some(tile.canvas_pixel(px, py));
+            }
+            base += tile.led_count();
+        }
+        None
+    }
+}
+
+/// Display presenting a `W`x`H` canvas spread over several chained panels.
+///
+/// Games render into it row by row; the pixels are then rearranged into strip
+/// order and passed to the `output` display, which drives the actual strip.
+pub struct TiledDisplay<'a, D, const W: usize, const H: usize> {
+    output: D,
+    tiling: Tiling<'a>,
+    strip: LedBuffer<W, H>,
+}
+
+impl<'a, D, const W: usize, const H: usize> TiledDisplay<'a, D, W, H>
+where
+    D: LedDisplay<W, H>,
+{
+    pub fn new(output: D, tiling: Tiling<'a>) -> Self {
+        debug_assert!(tiling.fits(W, H), "tiles don't fit the canvas");
+        Self {
+            output,
+            tiling,
+            strip: [[RGB8::default(); W]; H],
+        }
+    }
+
+    pub fn output(&mut self) -> &mut D {
+        &mut self.output
+    }
+}
+
+impl<D, const W: usize, const H: usize> LedDisplay<W, H> for TiledDisplay<'_, D, W, H>
+where
+    D: LedDisplay<W, H>,
+{
+    async fn write(&mut self, leds: &LedBuffer<W, H>) {
+        let strip = self.strip.as_flattened_mut();
+        for (idx, led) in strip.iter_mut().enumerate() {
+            *led = match self.tiling.position(idx) {
+                Some((x, y)) => leds[y][x],
+                None => RGB8::default(),
+            };
+        }
+        self.output.write(&self.strip).await;
+    }
+
+    async fn write_dirty(&mut self, leds: &LedBuffer<W, H>, dirty: Range<usize>) {
+        // The canvas comes row by row, LED `i` is canvas pixel (i % W, i / W)
+        let strip = self.strip.as_flattened_mut();
+        let mut strip_dirty: Option<Range<usize>> = None;
+        for i in dirty {
+            let (x, y) = (i % W, i / W);
+            if let Some(idx) = self.tiling.index(x, y) {
+                strip[idx] = leds[y][x];
+                strip_dirty = Some(match strip_dirty {
+                    Some(range) => range.start.min(idx)..range.end.max(idx + 1),
+                    None => idx..idx + 1,
+                });
+            }
+        }
+        // Nothing to do if no panel shows the changes
+        if let Some(strip_dirty) = strip_dirty {
+            self.output.write_dirty(&self.strip, strip_dirty).await;
+        }
+    }
+
+    fn layout(&self) -> LedLayout {
+        // The canvas is handed over row by row, the tiling does the rest
+        LedLayout::new(Wiring::ProgressiveRows, StartCorner::TopLeft)
+    }
+
+    fn brightness(&self) -> Option<u8> {
+        self.output.brightness()
+    }
+
+    fn set_brightness(&mut self, brightness: u8) {
+        self.output.set_brightness(brightness);
+    }
+
+    fn theme(&self) -> &'static Theme {
+        self.output.theme()
+    }
+
+    fn set_theme(&mut self, theme: &'static Theme) {
+        self.output.set_theme(theme);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rotation_round_trip() {
+        for rotation in [
+            Rotation::Deg0,
+            Rotation::Deg90,
+            Rotation::Deg180,
+            Rotation::Deg270,
+        ] {
+            let tile = Tile::new(4, 2, 8, 4).rotated(rotation);
+            let (width, height) = tile.canvas_size();
+            for y in tile.y..tile.y + height {
+                for x in tile.x..tile.x + width {
+                    let (px, py) = tile.panel_pixel(x, y);
+                    assert!(px < 8 && py < 4);
+                    assert_eq!(tile.canvas_pixel(px, py), (x, y), "{:?}", rotation);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn chained_panels() {
+        // Two 8x32 panels side by side, the second one mounted upside down
+        let tiles = [
+            Tile::new(0, 0, 8, 32),
+            Tile::new(8, 0, 8, 32).rotated(Rotation::Deg180),
+        ];
+        let tiling = Tiling::new(&tiles);
+        assert!(tiling.fits(16, 32));
+        assert!(!tiling.fits(8, 32));
+        assert_eq!(tiling.led_count(), 512);
+
+        // The first panel keeps the serpentine wiring of a single panel
+        assert_eq!(tiling.index(0, 0), Some(7));
+        // The second one starts after it, from the bottom left corner
+        assert_eq!(tiling.index(8, 31), Some(256));
+        assert_eq!(tiling.index(15, 31), Some(256 + 7));
+        for idx in 0..512 {
+            let (x, y) = tiling.position(idx).unwrap();
+            assert_eq!(tiling.index(x, y), Some(idx));
+        }
+        assert_eq!(tiling.position(512), None);
+    }
+
+    #[test]
+    fn parse_tile() {
+        assert_eq!("0,0,8x32".parse(), Ok(Tile::new(0, 0, 8, 32)));
+        assert_eq!(
+            "8,0,8x32,180,progressive:top-left".parse(),
+            Ok(Tile::new(8, 0, 8, 32)
+                .rotated(Rotation::Deg180)
+                .with_layout(LedLayout::new(
+                    Wiring::ProgressiveRows,
+                    StartCorner::TopLeft
+                )))
+        );
+        assert_eq!("0,0".parse::<Tile>(), Err(ParseTileError));
+        assert_eq!("0,0,8x32,45".parse::<Tile>(), Err(ParseTileError));
+    }
+}
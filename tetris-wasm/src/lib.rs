@@ -1,11 +1,20 @@
-use smart_leds::RGB8;
+use std::ops::Range;
 use std::sync::atomic::{AtomicBool, AtomicI8, Ordering};
 use tetris_lib::{
-    common::{GameController, LedDisplay, Timer, SCREEN_HEIGHT, SCREEN_WIDTH},
-    games::run_game_menu,
+    audio::{Audio, Note},
+    color::{preview, ColorPipeline, CorrectedDisplay},
+    common::{GameController, LedBuffer, LedDisplay, Timer, SCREEN_HEIGHT, SCREEN_WIDTH},
+    games::{play, run_game_menu},
+    layout::LedLayout,
+    replay::{Replay, ReplayError},
+    storage::Storage,
+    tiling::{Tile, TiledDisplay, Tiling},
 };
 use wasm_bindgen::prelude::*;
-use web_sys::{CanvasRenderingContext2d, HtmlCanvasElement, ImageData, KeyboardEvent};
+use web_sys::{
+    AudioContext, CanvasRenderingContext2d, HtmlCanvasElement, ImageData, KeyboardEvent,
+    OscillatorNode, OscillatorType,
+};
 
 // Console logging macro (currently unused but may be useful for debugging)
 #[allow(unused_macros)]
@@ -28,74 +37,88 @@ impl Timer for WasmTimer {
         });
         wasm_bindgen_futures::JsFuture::from(promise).await.unwrap();
     }
+
+    fn now_millis(&self) -> u64 {
+        // performance.now() is monotonic, unlike Date.now()
+        web_sys::window()
+            .and_then(|window| window.performance())
+            .map(|performance| performance.now() as u64)
+            .unwrap_or_default()
+    }
 }
 
-// Display implementation for WASM
+// Display implementation for WASM. It receives the color corrected LED strip
+// and uses the tiling to draw every LED where it sits on the (possibly
+// multi-panel) canvas, the way it would look on hardware.
 pub struct WasmDisplay {
     canvas: HtmlCanvasElement,
     context: CanvasRenderingContext2d,
+    tiling: Tiling<'static>,
 }
 
 impl WasmDisplay {
-    pub fn new(canvas: HtmlCanvasElement, pixel_size: f64) -> Result<Self, JsValue> {
+    pub fn new(
+        canvas: HtmlCanvasElement,
+        pixel_size: f64,
+        width: usize,
+        height: usize,
+        tiling: Tiling<'static>,
+    ) -> Result<Self, JsValue> {
         let context = canvas
             .get_context("2d")?
             .unwrap()
             .dyn_into::<CanvasRenderingContext2d>()?;
 
         // Set canvas size
-        canvas.set_width((SCREEN_WIDTH as f64 * pixel_size) as u32);
-        canvas.set_height((SCREEN_HEIGHT as f64 * pixel_size) as u32);
-
-        Ok(Self { canvas, context })
+        canvas.set_width((width as f64 * pixel_size) as u32);
+        canvas.set_height((height as f64 * pixel_size) as u32);
+
+        Ok(Self {
+            canvas,
+            context,
+            tiling,
+        })
     }
 }
 
-impl LedDisplay for WasmDisplay {
-    async fn write(&mut self, leds: &[RGB8; 256]) {
-        let width = SCREEN_WIDTH as u32;
-        let height = SCREEN_HEIGHT as u32;
-
+impl WasmDisplay {
+    // Draws the canvas pixels in the `width`x`height` rectangle at (`left`,
+    // `top`), scaled up to the size of the HTML canvas
+    fn draw<const W: usize, const H: usize>(
+        &self,
+        leds: &LedBuffer<W, H>,
+        (left, top): (usize, usize),
+        (width, height): (usize, usize),
+    ) {
         // Create image data
-        let mut data = Vec::with_capacity((width * height * 4) as usize);
-
-        for y in 0..height {
-            for x in 0..width {
-                let led_idx = if y % 2 == 0 {
-                    y * width + (width - 1 - x)
-                } else {
-                    y * width + x
-                } as usize;
-
-                let led = leds[led_idx];
-                data.push(led.r * 8); // Scale up from 0-31 to 0-248
-                data.push(led.g * 8);
-                data.push(led.b * 8);
+        let mut data = Vec::with_capacity(width * height * 4);
+
+        let strip = leds.as_flattened();
+        for y in top..top + height {
+            for x in left..left + width {
+                // Parts of the canvas without a panel stay black
+                let led = self
+                    .tiling
+                    .index(x, y)
+                    .map(|idx| preview(strip[idx]))
+                    .unwrap_or_default();
+                data.push(led.r);
+                data.push(led.g);
+                data.push(led.b);
                 data.push(255); // Alpha
             }
         }
 
         let image_data = ImageData::new_with_u8_clamped_array_and_sh(
             wasm_bindgen::Clamped(&data),
-            width,
-            height,
+            width as u32,
+            height as u32,
         )
         .unwrap();
 
-        // Clear the canvas
-        self.context.clear_rect(
-            0.0,
-            0.0,
-            self.canvas.width() as f64,
-            self.canvas.height() as f64,
-        );
-
         // Disable image smoothing for pixel-perfect scaling
         self.context.set_image_smoothing_enabled(false);
 
-        // Scale the 8x32 pixel image to fill the entire canvas
-        self.context.put_image_data(&image_data, 0.0, 0.0).unwrap();
-
         // Create a temporary canvas for scaling
         let temp_canvas = web_sys::window()
             .unwrap()
@@ -106,8 +129,8 @@ impl LedDisplay for WasmDisplay {
             .dyn_into::<web_sys::HtmlCanvasElement>()
             .unwrap();
 
-        temp_canvas.set_width(width);
-        temp_canvas.set_height(height);
+        temp_canvas.set_width(width as u32);
+        temp_canvas.set_height(height as u32);
 
         let temp_context = temp_canvas
             .get_context("2d")
@@ -118,19 +141,170 @@ impl LedDisplay for WasmDisplay {
 
         temp_context.put_image_data(&image_data, 0.0, 0.0).unwrap();
 
-        // Scale from temporary canvas to main canvas
+        // Scale from temporary canvas to its place on the main canvas
+        let scale_x = self.canvas.width() as f64 / W as f64;
+        let scale_y = self.canvas.height() as f64 / H as f64;
         self.context
             .draw_image_with_html_canvas_element_and_dw_and_dh(
                 &temp_canvas,
-                0.0,
-                0.0,
-                self.canvas.width() as f64,
-                self.canvas.height() as f64,
+                left as f64 * scale_x,
+                top as f64 * scale_y,
+                width as f64 * scale_x,
+                height as f64 * scale_y,
             )
             .unwrap();
     }
 }
 
+impl<const W: usize, const H: usize> LedDisplay<W, H> for WasmDisplay {
+    async fn write(&mut self, leds: &LedBuffer<W, H>) {
+        // Clear the canvas
+        self.context.clear_rect(
+            0.0,
+            0.0,
+            self.canvas.width() as f64,
+            self.canvas.height() as f64,
+        );
+
+        self.draw(leds, (0, 0), (W, H));
+    }
+
+    async fn write_dirty(&mut self, leds: &LedBuffer<W, H>, dirty: Range<usize>) {
+        // Only redraw the part of the canvas around the changed LEDs
+        let (mut left, mut top, mut right, mut bottom) = (W, H, 0, 0);
+        for (x, y) in dirty.filter_map(|idx| self.tiling.position(idx)) {
+            left = left.min(x);
+            top = top.min(y);
+            right = right.max(x + 1);
+            bottom = bottom.max(y + 1);
+        }
+        if left < right && top < bottom {
+            self.draw(leds, (left, top), (right - left, bottom - top));
+        }
+    }
+}
+
+// Volume of the oscillators, square waves are loud
+const VOLUME: f32 = 0.1;
+
+// Audio implementation for WASM, every note is a WebAudio square wave
+// oscillator scheduled on the audio clock
+pub struct WebAudio {
+    context: Option<AudioContext>,
+    // Oscillators of the melody playing now, to cut it off
+    playing: Vec<OscillatorNode>,
+}
+
+impl WebAudio {
+    pub fn new() -> Self {
+        Self {
+            context: AudioContext::new().ok(),
+            playing: Vec::new(),
+        }
+    }
+
+    fn schedule(&mut self, notes: &[Note]) {
+        self.stop();
+        let Some(context) = &self.context else {
+            return;
+        };
+        // Browsers keep the context suspended until the page got a key press
+        let _ = context.resume();
+
+        let mut at = context.current_time();
+        for note in notes {
+            let seconds = note.millis as f64 / 1000.0;
+            if !note.is_rest() {
+                if let Some(oscillator) = Self::oscillator(context, note.freq_hz) {
+                    let _ = oscillator.start_with_when(at);
+                    let _ = oscillator.stop_with_when(at + seconds);
+                    self.playing.push(oscillator);
+                }
+            }
+            at += seconds;
+        }
+    }
+
+    fn oscillator(context: &AudioContext, freq_hz: u16) -> Option<OscillatorNode> {
+        let oscillator = context.create_oscillator().ok()?;
+        oscillator.set_type(OscillatorType::Square);
+        oscillator.frequency().set_value(freq_hz as f32);
+        let gain = context.create_gain().ok()?;
+        gain.gain().set_value(VOLUME);
+        oscillator.connect_with_audio_node(&gain).ok()?;
+        gain.connect_with_audio_node(&context.destination()).ok()?;
+        Some(oscillator)
+    }
+}
+
+impl Default for WebAudio {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Audio for WebAudio {
+    fn play(&mut self, melody: &'static [Note]) {
+        self.schedule(melody);
+    }
+
+    fn tone(&mut self, note: Note) {
+        self.schedule(&[note]);
+    }
+
+    fn stop(&mut self) {
+        for oscillator in self.playing.drain(..) {
+            let _ = oscillator.stop();
+        }
+    }
+}
+
+// Storage implementation for WASM, keeping every key hex encoded in the
+// browser's localStorage under "tetris.<key>"
+pub struct LocalStorage {
+    storage: Option<web_sys::Storage>,
+}
+
+impl LocalStorage {
+    pub fn new() -> Self {
+        let storage = web_sys::window().and_then(|window| window.local_storage().ok().flatten());
+        Self { storage }
+    }
+}
+
+impl Default for LocalStorage {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Storage for LocalStorage {
+    async fn load(&mut self, key: &str, buf: &mut [u8]) -> Option<usize> {
+        let value = self
+            .storage
+            .as_ref()?
+            .get_item(&format!("tetris.{key}"))
+            .ok()??;
+        if value.len() % 2 != 0 || value.len() / 2 > buf.len() {
+            return None;
+        }
+        for (byte, hex) in buf.iter_mut().zip(value.as_bytes().chunks_exact(2)) {
+            *byte = u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok()?;
+        }
+        Some(value.len() / 2)
+    }
+
+    async fn save(&mut self, key: &str, data: &[u8]) {
+        let Some(storage) = &self.storage else {
+            return;
+        };
+        let value: String = data.iter().map(|byte| format!("{byte:02x}")).collect();
+        if storage.set_item(&format!("tetris.{key}"), &value).is_err() {
+            log!("Failed to save {}", key);
+        }
+    }
+}
+
 // Global input state using a struct with atomic fields
 #[derive(Default)]
 struct InputState {
@@ -226,6 +400,11 @@ impl GameController for WasmController {
         let prev = INPUT_STATE.prev_b_pressed.swap(current, Ordering::Relaxed);
         current && !prev
     }
+
+    fn ab_held(&self) -> bool {
+        INPUT_STATE.a_pressed.load(Ordering::Relaxed)
+            && INPUT_STATE.b_pressed.load(Ordering::Relaxed)
+    }
 }
 
 // This was already replaced above with static functions, so this section should be removed
@@ -236,25 +415,164 @@ pub fn main() {
     console_error_panic_hook::set_once();
 }
 
-// Export the start_game function for JavaScript
-#[wasm_bindgen]
-pub async fn start_game(canvas: HtmlCanvasElement, pixel_size: f64) -> Result<(), JsValue> {
-    // Create display
-    let mut display = WasmDisplay::new(canvas, pixel_size)?;
+async fn run_menu<const W: usize, const H: usize>(display: WasmDisplay, tiling: Tiling<'static>) {
+    let mut display =
+        TiledDisplay::new(CorrectedDisplay::new(display, ColorPipeline::new()), tiling);
 
-    // Create controller and timer
+    // Create audio, controller, timer and storage
+    let mut audio = WebAudio::new();
     let mut controller = WasmController::new();
     let timer = WasmTimer;
+    let mut storage = LocalStorage::new();
 
     // Seed function using current timestamp
     let seed_fn = || js_sys::Date::now() as u32;
 
     // Run the game menu
-    run_game_menu(&mut display, &mut controller, &timer, seed_fn).await;
+    run_game_menu::<_, _, _, _, _, _, W, H>(
+        &mut display,
+        &mut audio,
+        &mut controller,
+        &timer,
+        &mut storage,
+        seed_fn,
+    )
+    .await;
+}
+
+async fn run_replay<const W: usize, const H: usize>(
+    display: WasmDisplay,
+    tiling: Tiling<'static>,
+    replay: Replay<'_>,
+) -> Result<(), JsValue> {
+    let mut display =
+        TiledDisplay::new(CorrectedDisplay::new(display, ColorPipeline::new()), tiling);
+    let mut controller = replay.controller();
+    play::<_, _, _, _, W, H>(
+        replay.header.game,
+        replay.header.seed,
+        &replay.header.settings(),
+        &mut display,
+        &mut WebAudio::new(),
+        &mut controller,
+        &WasmTimer,
+    )
+    .await
+    .map(|_| ())
+    .ok_or_else(|| {
+        JsValue::from_str(&format!(
+            "game {} is unknown or doesn't fit a {W}x{H} canvas",
+            replay.header.game
+        ))
+    })
+}
+
+// Builds the display for a `width`x`height` canvas out of the optional layout
+// and tile descriptions passed from JavaScript
+fn create_display(
+    canvas: HtmlCanvasElement,
+    pixel_size: f64,
+    width: usize,
+    height: usize,
+    layout: Option<String>,
+    tiles: Option<String>,
+) -> Result<(WasmDisplay, Tiling<'static>), JsValue> {
+    let layout = match layout {
+        Some(layout) => layout
+            .parse()
+            .map_err(|_| JsValue::from_str(&format!("invalid layout '{layout}'")))?,
+        None => LedLayout::default(),
+    };
+
+    let tiles = match tiles {
+        Some(tiles) => tiles
+            .split(';')
+            .map(|tile| {
+                tile.parse::<Tile>()
+                    .map_err(|_| JsValue::from_str(&format!("invalid tile '{tile}'")))
+            })
+            .collect::<Result<Vec<_>, _>>()?,
+        None => vec![Tile::new(0, 0, width, height).with_layout(layout)],
+    };
+    // The game runs until the page is closed, so the tiles can live forever
+    let tiling = Tiling::new(tiles.leak());
+    if !tiling.fits(width, height) {
+        return Err(JsValue::from_str(&format!(
+            "tiles overlap or don't fit the {width}x{height} canvas"
+        )));
+    }
+
+    let display = WasmDisplay::new(canvas, pixel_size, width, height, tiling)?;
+    Ok((display, tiling))
+}
+
+fn unsupported_size(width: usize, height: usize) -> JsValue {
+    JsValue::from_str(&format!("unsupported panel size {width}x{height}"))
+}
+
+// Export the start_game function for JavaScript. The canvas geometry is optional
+// and defaults to the 8x32 matrix; 16x16, 32x8, 16x32 and 32x32 are also
+// supported. The wiring layout uses the `wiring[:corner][:flipped]` syntax of
+// `LedLayout`. Chained panels are given as `;`-separated `Tile` descriptions
+// (`X,Y,WxH[,ROTATION][,LAYOUT]`), otherwise the canvas is a single panel.
+#[wasm_bindgen]
+pub async fn start_game(
+    canvas: HtmlCanvasElement,
+    pixel_size: f64,
+    width: Option<usize>,
+    height: Option<usize>,
+    layout: Option<String>,
+    tiles: Option<String>,
+) -> Result<(), JsValue> {
+    let width = width.unwrap_or(SCREEN_WIDTH);
+    let height = height.unwrap_or(SCREEN_HEIGHT);
+    let (display, tiling) = create_display(canvas, pixel_size, width, height, layout, tiles)?;
+
+    match (width, height) {
+        (8, 32) => run_menu::<8, 32>(display, tiling).await,
+        (16, 16) => run_menu::<16, 16>(display, tiling).await,
+        (32, 8) => run_menu::<32, 8>(display, tiling).await,
+        (16, 32) => run_menu::<16, 32>(display, tiling).await,
+        (32, 32) => run_menu::<32, 32>(display, tiling).await,
+        _ => return Err(unsupported_size(width, height)),
+    }
 
     Ok(())
 }
 
+// Plays back a replay recorded with `tetris-console --record`. Replays
+// recorded on a canvas of another size are rejected; the other arguments are
+// the same as for `start_game`.
+#[wasm_bindgen]
+pub async fn start_replay(
+    canvas: HtmlCanvasElement,
+    pixel_size: f64,
+    replay: Vec<u8>,
+    width: Option<usize>,
+    height: Option<usize>,
+    layout: Option<String>,
+    tiles: Option<String>,
+) -> Result<(), JsValue> {
+    let width = width.unwrap_or(SCREEN_WIDTH);
+    let height = height.unwrap_or(SCREEN_HEIGHT);
+    let replay = Replay::parse_for(&replay, width, height).map_err(|err| match err {
+        ReplayError::WrongSize(w, h) => JsValue::from_str(&format!(
+            "replay was recorded on a {w}x{h} canvas, not {width}x{height}"
+        )),
+        err => JsValue::from_str(&format!("invalid replay: {err:?}")),
+    })?;
+    let (display, tiling) = create_display(canvas, pixel_size, width, height, layout, tiles)?;
+
+    match (width, height) {
+        (8, 32) => run_replay::<8, 32>(display, tiling, replay).await,
+        (16, 16) => run_replay::<16, 16>(display, tiling, replay).await,
+        (32, 8) => run_replay::<32, 8>(display, tiling, replay).await,
+        (16, 32) => run_replay::<16, 32>(display, tiling, replay).await,
+        (32, 32) => run_replay::<32, 32>(display, tiling, replay).await,
+        _ => Err(unsupported_size(width, height)),
+    }
+}
+
 // Export key handling functions for JavaScript
 #[wasm_bindgen]
 pub fn handle_key_down(event: KeyboardEvent) {